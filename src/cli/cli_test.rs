@@ -0,0 +1,102 @@
+use super::*;
+
+fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn parse_command_line() -> error::Result<()> {
+    let options = parse_args(&strings(&["--vm", "script.mk", "-e", "x"]))?;
+    assert_eq!(Some(Source::File("script.mk".to_string())), options.source);
+    assert_eq!(strings(&["-e", "x"]), options.args);
+    assert!(options.use_vm);
+
+    let options = parse_args(&strings(&["-e", "puts(1)", "a"]))?;
+    assert_eq!(Some(Source::Inline("puts(1)".to_string())), options.source);
+    assert_eq!(strings(&["a"]), options.args);
+
+    let options = parse_args(&strings(&["-", "--vm"]))?;
+    assert_eq!(Some(Source::Stdin), options.source);
+    assert_eq!(strings(&["--vm"]), options.args);
+    assert!(!options.use_vm);
+
+    let options = parse_args(&strings(&["--emit=json-ast", "-"]))?;
+    assert_eq!(Some(Emit::JsonAst), options.emit);
+
+    assert_eq!(None, parse_args(&[])?.source);
+    Ok(())
+}
+
+#[test]
+fn parse_bad_command_line() {
+    for args in &[&["--bogus"][..], &["-e"][..], &["--emit=bytes", "-"][..]] {
+        match parse_args(&strings(args)) {
+            Err(err @ MonkeyErr::UsageErr { .. }) => assert_eq!(EXIT_USAGE_ERR, exit_code(&err)),
+            other => panic!("expected a usage error for {:?}, got {:?}", args, other),
+        }
+    }
+}
+
+#[test]
+fn exit_codes() {
+    let cases = &[
+        (&["-e", "let x = 1; x + 1"][..], 0),
+        (&["-e", ""][..], 0),
+        (&["--vm", "-e", "// nothing"][..], 0),
+        (&["-e", "let x = ;"][..], EXIT_SYNTAX_ERR),
+        (&["-e", "1 / 0"][..], EXIT_RUNTIME_ERR),
+        (&["--vm", "-e", "1 / 0"][..], EXIT_RUNTIME_ERR),
+        (&["--vm", "-e", "undefined"][..], EXIT_RUNTIME_ERR),
+        (
+            &["--vm", "-e", "let f = fn(n) { 1 + f(n + 1) }; f(0)"][..],
+            EXIT_RUNTIME_ERR,
+        ),
+        (&["no/such/script.mk"][..], EXIT_NO_INPUT),
+        (&["--bogus"][..], EXIT_USAGE_ERR),
+    ];
+    for (args, expected) in cases {
+        assert_eq!(*expected, run(&strings(args)), "args: {:?}", args);
+    }
+}
+
+#[test]
+fn script_arguments() {
+    let check = r#"if (len(args) != 2) { 1 / 0 } else { if (args[1] != "b") { 1 / 0 } }"#;
+    assert_eq!(0, run(&strings(&["-e", check, "a", "b"])));
+    assert_eq!(0, run(&strings(&["--vm", "-e", check, "a", "b"])));
+    assert_eq!(EXIT_RUNTIME_ERR, run(&strings(&["-e", check, "a"])));
+}
+
+#[test]
+fn fmt_check_and_write() -> error::Result<()> {
+    let path = std::env::temp_dir().join(format!("monkey_fmt_{}.mk", std::process::id()));
+    let file = path.to_string_lossy().to_string();
+    fs::write(&path, "let x=1;x")?;
+
+    assert_eq!(EXIT_UNFORMATTED, run(&strings(&["fmt", "--check", &file])));
+    assert_eq!(0, run(&strings(&["fmt", "--write", &file])));
+    assert_eq!("let x = 1;\nx;\n", fs::read_to_string(&path)?);
+    assert_eq!(0, run(&strings(&["fmt", "--check", &file])));
+
+    fs::write(&path, "")?;
+    assert_eq!(0, run(&strings(&["fmt", "--check", &file])));
+
+    fs::write(&path, "let x = ;")?;
+    assert_eq!(EXIT_SYNTAX_ERR, run(&strings(&["fmt", "--check", &file])));
+    fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[test]
+fn parse_bad_fmt_command_line() {
+    for args in &[
+        &["--check", "--write", "a.mk"][..],
+        &["--write"][..],
+        &["--bogus"][..],
+    ] {
+        match parse_fmt_args(&strings(args)) {
+            Err(MonkeyErr::UsageErr { .. }) => {}
+            other => panic!("expected a usage error for {:?}, got {:?}", args, other),
+        }
+    }
+}
@@ -0,0 +1,339 @@
+#[cfg(test)]
+mod cli_test;
+
+use std::fs;
+use std::io::{self, Read, Write};
+
+use crate::diagnostic;
+use crate::emit;
+use crate::engine::Engine;
+use crate::error::{self, MonkeyErr, MonkeyWarn};
+use crate::formatter;
+use crate::lexer::Lexer;
+use crate::object::Object;
+use crate::parser::ast::Program;
+use crate::parser::Parser;
+use crate::repl;
+
+// Exit codes, so that scripts can tell why a program failed
+pub const EXIT_RUNTIME_ERR: i32 = 1;
+pub const EXIT_UNFORMATTED: i32 = 1;
+pub const EXIT_SYNTAX_ERR: i32 = 2;
+pub const EXIT_USAGE_ERR: i32 = 64;
+pub const EXIT_NO_INPUT: i32 = 66;
+
+const USAGE: &str = "\
+Usage: monkey [options] [script.mk | -e <code> | -] [args...]
+       monkey fmt [--check | --write] [files...]
+
+Starts the REPL when no script is given. The arguments after the script
+are bound to `args` as an array of strings.
+
+Options:
+  -e <code>   run the given code
+  -           read the script from stdin
+  --vm        run on the bytecode vm instead of the evaluator
+  --emit=<kind>
+              print the script instead of running it, where kind is one of
+              tokens, ast or json-ast
+  -h, --help  print this message
+
+The formatter prints the formatted files, or stdin when no file is given.
+  --check     only list the files which are not formatted, and fail if any
+  --write     rewrite the files in place
+";
+
+#[derive(Debug, PartialEq)]
+enum Source {
+    File(String),
+    Inline(String),
+    Stdin,
+}
+
+// What --emit prints in place of running the script
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Emit {
+    Tokens,
+    Ast,
+    JsonAst,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct FmtOptions {
+    sources: Vec<Source>,
+    check: bool,
+    write: bool,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Options {
+    source: Option<Source>,
+    args: Vec<String>,
+    use_vm: bool,
+    emit: Option<Emit>,
+    help: bool,
+}
+
+// Options come before the script. Everything after it belongs to the script.
+fn parse_args(args: &[String]) -> error::Result<Options> {
+    let mut options = Options::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => options.help = true,
+            "--vm" => options.use_vm = true,
+            _ if arg.starts_with("--emit=") => {
+                options.emit = Some(match &arg["--emit=".len()..] {
+                    "tokens" => Emit::Tokens,
+                    "ast" => Emit::Ast,
+                    "json-ast" => Emit::JsonAst,
+                    kind => {
+                        return Err(MonkeyErr::UsageErr {
+                            msg: format!(
+                                "unknown kind `{}` for --emit, expected tokens, ast or json-ast",
+                                kind
+                            ),
+                        })
+                    }
+                });
+            }
+            "-e" => {
+                let code = args.next().ok_or_else(|| MonkeyErr::UsageErr {
+                    msg: "`-e` needs the code to run".to_string(),
+                })?;
+                options.source = Some(Source::Inline(code.clone()));
+                break;
+            }
+            "-" => {
+                options.source = Some(Source::Stdin);
+                break;
+            }
+            _ if arg.starts_with('-') => {
+                return Err(MonkeyErr::UsageErr {
+                    msg: format!("unknown option `{}`", arg),
+                })
+            }
+            _ => {
+                options.source = Some(Source::File(arg.clone()));
+                break;
+            }
+        }
+    }
+    options.args = args.cloned().collect();
+    Ok(options)
+}
+
+fn exit_code(err: &MonkeyErr) -> i32 {
+    match err {
+        MonkeyErr::UsageErr { .. } => EXIT_USAGE_ERR,
+        MonkeyErr::IOErr(_) => EXIT_NO_INPUT,
+        MonkeyErr::FmtErr(_)
+        | MonkeyErr::EvalErr { .. }
+        | MonkeyErr::UndeclaredNameErr { .. }
+        | MonkeyErr::IlligalOpcodeFoundErr
+        | MonkeyErr::FmtOperandsInputLenErr { .. }
+        | MonkeyErr::UnhandledOperandCountErr { .. }
+        | MonkeyErr::CompileErr { .. } => EXIT_RUNTIME_ERR,
+        // The rest come from lexing and parsing
+        _ => EXIT_SYNTAX_ERR,
+    }
+}
+
+// A diagnostic which cannot be rendered is reported by its message alone.
+// Nothing more can be done when even that fails.
+fn report(err: &MonkeyErr, file_name: &str, source: &str) -> i32 {
+    if diagnostic::emit(err, file_name, source).is_err() {
+        let _ = writeln!(io::stderr(), "error: {}", err.message());
+    }
+    exit_code(err)
+}
+
+fn report_warning(warning: &MonkeyWarn, file_name: &str, source: &str) {
+    if diagnostic::emit_warning(warning, file_name, source).is_err() {
+        let _ = writeln!(io::stderr(), "warning: {}", warning.message());
+    }
+}
+
+fn print_or_report(output: error::Result<String>, file_name: &str, source: &str) -> i32 {
+    match output {
+        Ok(output) => {
+            print!("{}", output);
+            0
+        }
+        Err(err) => report(&err, file_name, source),
+    }
+}
+
+impl Source {
+    // The name of the source in diagnostics
+    fn name(&self) -> &str {
+        match self {
+            Source::File(path) => path,
+            Source::Inline(_) => "-e",
+            Source::Stdin => "<stdin>",
+        }
+    }
+
+    fn read(&self) -> error::Result<String> {
+        match self {
+            Source::File(path) => Ok(fs::read_to_string(path)?),
+            Source::Inline(code) => Ok(code.clone()),
+            Source::Stdin => {
+                let mut code = String::new();
+                io::stdin().read_to_string(&mut code)?;
+                Ok(code)
+            }
+        }
+    }
+}
+
+// Reports every syntax error and gives the exit code of the first one. The
+// warnings are reported only when warn is set, as the formatter has no use
+// for them.
+fn parse_or_report(code: &str, file_name: &str, warn: bool) -> Result<Program, i32> {
+    let mut parser = Parser::new(Lexer::new(code));
+    let (program, errors) = parser.parse_program_recovering();
+    if warn {
+        for warning in parser.take_warnings() {
+            report_warning(&warning, file_name, code);
+        }
+    }
+    match errors.first() {
+        Some(first) => {
+            for err in &errors {
+                report(err, file_name, code);
+            }
+            Err(exit_code(first))
+        }
+        None => Ok(program),
+    }
+}
+
+fn parse_fmt_args(args: &[String]) -> error::Result<FmtOptions> {
+    let mut options = FmtOptions::default();
+    for arg in args {
+        match arg.as_str() {
+            "--check" => options.check = true,
+            "--write" => options.write = true,
+            "-" => options.sources.push(Source::Stdin),
+            _ if arg.starts_with('-') => {
+                return Err(MonkeyErr::UsageErr {
+                    msg: format!("unknown option `{}` for fmt", arg),
+                })
+            }
+            _ => options.sources.push(Source::File(arg.clone())),
+        }
+    }
+    if options.sources.is_empty() {
+        options.sources.push(Source::Stdin);
+    }
+    if options.check && options.write {
+        return Err(MonkeyErr::UsageErr {
+            msg: "`--check` and `--write` cannot be used together".to_string(),
+        });
+    }
+    if options.write && options.sources.contains(&Source::Stdin) {
+        return Err(MonkeyErr::UsageErr {
+            msg: "`--write` needs files to rewrite".to_string(),
+        });
+    }
+    Ok(options)
+}
+
+// The worst failure decides the exit code, so a syntax error is not hidden
+// behind an unformatted file
+fn run_fmt(args: &[String]) -> i32 {
+    let options = match parse_fmt_args(args) {
+        Ok(options) => options,
+        Err(err) => return report(&err, "monkey fmt", ""),
+    };
+    let mut status = 0;
+    for source in &options.sources {
+        let file_name = source.name();
+        let code = match source.read() {
+            Ok(code) => code,
+            Err(err) => {
+                status = status.max(report(&err, file_name, ""));
+                continue;
+            }
+        };
+        let program = match parse_or_report(&code, file_name, false) {
+            Ok(program) => program,
+            Err(code) => {
+                status = status.max(code);
+                continue;
+            }
+        };
+
+        let formatted = formatter::format_program(&program, &code);
+        match source {
+            _ if options.check => {
+                if formatted != code {
+                    println!("{} is not formatted", file_name);
+                    status = status.max(EXIT_UNFORMATTED);
+                }
+            }
+            Source::File(path) if options.write => {
+                if formatted != code {
+                    if let Err(err) = fs::write(path, formatted) {
+                        status = status.max(report(&err.into(), file_name, ""));
+                    }
+                }
+            }
+            _ => print!("{}", formatted),
+        }
+    }
+    status
+}
+
+pub fn run(args: &[String]) -> i32 {
+    if args.first().map(String::as_str) == Some("fmt") {
+        return run_fmt(&args[1..]);
+    }
+    let options = match parse_args(args) {
+        Ok(options) => options,
+        Err(err) => return report(&err, "monkey", ""),
+    };
+    if options.help {
+        print!("{}", USAGE);
+        return 0;
+    }
+    let source = match options.source {
+        Some(source) => source,
+        None => {
+            repl::start_repl();
+            return 0;
+        }
+    };
+    let file_name = source.name();
+    let code = match source.read() {
+        Ok(code) => code,
+        Err(err) => return report(&err, file_name, ""),
+    };
+
+    if options.emit == Some(Emit::Tokens) {
+        return print_or_report(emit::tokens(&code), file_name, &code);
+    }
+
+    let program = match parse_or_report(&code, file_name, options.emit.is_none()) {
+        Ok(program) => program,
+        Err(code) => return code,
+    };
+    match options.emit {
+        Some(Emit::Ast) => return print_or_report(emit::ast_tree(&program), file_name, &code),
+        Some(Emit::JsonAst) => return print_or_report(emit::json_ast(&program), file_name, &code),
+        _ => {}
+    }
+
+    let mut engine = if options.use_vm {
+        Engine::new_vm()
+    } else {
+        Engine::new_eval()
+    };
+    let script_args = options.args.into_iter().map(Object::String).collect();
+    engine.define("args", Object::Array(script_args));
+    match engine.run(&program, false) {
+        Ok(_) => 0,
+        Err(err) => report(&err, file_name, &code),
+    }
+}
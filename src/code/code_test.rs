@@ -0,0 +1,92 @@
+use super::*;
+
+#[test]
+fn test_make() {
+    assert_eq!(
+        make(Opcode::Constant, &[65534]),
+        vec![Opcode::Constant as u8, 255, 254]
+    );
+    assert_eq!(make(Opcode::Add, &[]), vec![Opcode::Add as u8]);
+    assert_eq!(
+        make(Opcode::GetLocal, &[255]),
+        vec![Opcode::GetLocal as u8, 255]
+    );
+    assert_eq!(
+        make(Opcode::Closure, &[65534, 255]),
+        vec![Opcode::Closure as u8, 255, 254, 255]
+    );
+}
+
+#[test]
+fn test_oversized_operand() {
+    assert_eq!(None, oversized_operand(Opcode::Constant, &[65535]));
+    assert_eq!(Some(0), oversized_operand(Opcode::Constant, &[65536]));
+    assert_eq!(None, oversized_operand(Opcode::GetLocal, &[255]));
+    assert_eq!(Some(0), oversized_operand(Opcode::GetLocal, &[256]));
+    assert_eq!(Some(1), oversized_operand(Opcode::Closure, &[65535, 256]));
+    assert_eq!(None, oversized_operand(Opcode::Add, &[]));
+}
+
+#[test]
+fn test_read_operands() {
+    let cases: &[(Opcode, &[usize], usize)] = &[
+        (Opcode::Constant, &[65535], 2),
+        (Opcode::GetLocal, &[255], 1),
+        (Opcode::Closure, &[65535, 255], 3),
+        (Opcode::MatchPattern, &[1, 65535], 4),
+        (Opcode::CallNamed, &[255, 65535], 3),
+    ];
+    for (op, operands, bytes_read) in cases {
+        let instruction = make(*op, operands);
+        let (read, n) = read_operands(&op.definition(), &instruction[1..]);
+        assert_eq!(*bytes_read, n);
+        assert_eq!(operands.to_vec(), read);
+    }
+}
+
+#[test]
+fn test_opcode_round_trip() -> error::Result<()> {
+    for op in OPCODES {
+        assert_eq!(*op, Opcode::try_from(*op as u8)?);
+    }
+    assert_eq!(
+        Err(Error::IlligalOpcodeFoundErr),
+        Opcode::try_from(OPCODES.len() as u8)
+    );
+    Ok(())
+}
+
+#[test]
+fn test_disassemble() -> error::Result<()> {
+    let instructions: Instructions = [
+        make(Opcode::Add, &[]),
+        make(Opcode::GetLocal, &[1]),
+        make(Opcode::Constant, &[2]),
+        make(Opcode::Constant, &[65535]),
+        make(Opcode::Closure, &[65535, 255]),
+    ]
+    .concat();
+    let expected = "0000 OpAdd
+0001 OpGetLocal 1
+0003 OpConstant 2
+0006 OpConstant 65535
+0009 OpClosure 65535 255
+";
+    assert_eq!(expected, disassemble(&instructions)?);
+    Ok(())
+}
+
+#[test]
+fn test_source_map() {
+    let mut map = SourceMap::default();
+    map.add(0, Span::new(0, 1, 1, 1));
+    map.add(3, Span::new(4, 5, 1, 5));
+    map.add(4, Span::new(8, 9, 1, 9));
+    let column_at = |map: &SourceMap, position| map.span_at(position).map(|span| span.column);
+    assert_eq!(Some(1), column_at(&map, 2));
+    assert_eq!(Some(5), column_at(&map, 3));
+    assert_eq!(Some(9), column_at(&map, 7));
+    map.truncate(3);
+    assert_eq!(Some(1), column_at(&map, 3));
+    assert_eq!(None, column_at(&SourceMap::default(), 0));
+}
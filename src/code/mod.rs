@@ -0,0 +1,319 @@
+#[cfg(test)]
+mod code_test;
+
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+use crate::error;
+use crate::lexer::span::Span;
+
+type Error = error::MonkeyErr;
+
+pub type Instructions = Vec<u8>;
+
+// The spans of the nodes which instructions were compiled from, by the
+// position of each instruction. Like spans, it takes no part in comparing.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap(Vec<(usize, Span)>);
+
+impl SourceMap {
+    pub fn add(&mut self, position: usize, span: Span) {
+        self.0.push((position, span));
+    }
+
+    // Forgets the instructions from position on
+    pub fn truncate(&mut self, position: usize) {
+        self.0.retain(|(start, _)| *start < position);
+    }
+
+    // The span of the instruction which position is part of
+    pub fn span_at(&self, position: usize) -> Option<Span> {
+        let after = self.0.partition_point(|(start, _)| *start <= position);
+        after.checked_sub(1).map(|i| self.0[i].1)
+    }
+}
+
+impl PartialEq for SourceMap {
+    fn eq(&self, _other: &SourceMap) -> bool {
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Opcode {
+    Constant,
+    Pop,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    True,
+    False,
+    Equal,
+    NotEqual,
+    GreaterThan,
+    Minus,
+    Bang,
+    JumpNotTruthy,
+    Jump,
+    Null,
+    GetGlobal,
+    SetGlobal,
+    Array,
+    Hash,
+    Index,
+    Call,
+    ReturnValue,
+    Return,
+    GetLocal,
+    SetLocal,
+    GetBuiltin,
+    Closure,
+    GetFree,
+    CurrentClosure,
+    Interpolate,
+    GreaterEqual,
+    Mod,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    BitNot,
+    Assign,
+    Iter,
+    IterNext,
+    Range,
+    Slice,
+    MatchPattern,
+    Destructure,
+    JumpArgGiven,
+    CallNamed,
+    EnterLoop,
+    ExitLoop,
+    UnwindLoop,
+    GetLocalCell,
+    GetFreeCell,
+    SetFree,
+    BindLocal,
+    LessThan,
+    LessEqual,
+}
+
+pub struct Definition {
+    pub name: &'static str,
+    pub operand_widths: &'static [usize],
+}
+
+impl Opcode {
+    pub fn definition(self) -> Definition {
+        let (name, operand_widths): (&'static str, &'static [usize]) = match self {
+            Opcode::Constant => ("OpConstant", &[2]),
+            Opcode::Pop => ("OpPop", &[]),
+            Opcode::Add => ("OpAdd", &[]),
+            Opcode::Sub => ("OpSub", &[]),
+            Opcode::Mul => ("OpMul", &[]),
+            Opcode::Div => ("OpDiv", &[]),
+            Opcode::Pow => ("OpPow", &[]),
+            Opcode::True => ("OpTrue", &[]),
+            Opcode::False => ("OpFalse", &[]),
+            Opcode::Equal => ("OpEqual", &[]),
+            Opcode::NotEqual => ("OpNotEqual", &[]),
+            Opcode::GreaterThan => ("OpGreaterThan", &[]),
+            Opcode::Minus => ("OpMinus", &[]),
+            Opcode::Bang => ("OpBang", &[]),
+            Opcode::JumpNotTruthy => ("OpJumpNotTruthy", &[2]),
+            Opcode::Jump => ("OpJump", &[2]),
+            Opcode::Null => ("OpNull", &[]),
+            Opcode::GetGlobal => ("OpGetGlobal", &[2]),
+            Opcode::SetGlobal => ("OpSetGlobal", &[2]),
+            Opcode::Array => ("OpArray", &[2]),
+            Opcode::Hash => ("OpHash", &[2]),
+            Opcode::Index => ("OpIndex", &[]),
+            Opcode::Call => ("OpCall", &[1]),
+            Opcode::ReturnValue => ("OpReturnValue", &[]),
+            Opcode::Return => ("OpReturn", &[]),
+            Opcode::GetLocal => ("OpGetLocal", &[1]),
+            Opcode::SetLocal => ("OpSetLocal", &[1]),
+            Opcode::GetBuiltin => ("OpGetBuiltin", &[1]),
+            Opcode::Closure => ("OpClosure", &[2, 1]),
+            Opcode::GetFree => ("OpGetFree", &[1]),
+            Opcode::CurrentClosure => ("OpCurrentClosure", &[]),
+            Opcode::Interpolate => ("OpInterpolate", &[2]),
+            Opcode::GreaterEqual => ("OpGreaterEqual", &[]),
+            Opcode::Mod => ("OpMod", &[]),
+            Opcode::BitAnd => ("OpBitAnd", &[]),
+            Opcode::BitOr => ("OpBitOr", &[]),
+            Opcode::BitXor => ("OpBitXor", &[]),
+            Opcode::Shl => ("OpShl", &[]),
+            Opcode::Shr => ("OpShr", &[]),
+            Opcode::BitNot => ("OpBitNot", &[]),
+            Opcode::Assign => ("OpAssign", &[1, 1]),
+            Opcode::Iter => ("OpIter", &[]),
+            Opcode::IterNext => ("OpIterNext", &[2]),
+            Opcode::Range => ("OpRange", &[1]),
+            Opcode::Slice => ("OpSlice", &[1]),
+            Opcode::MatchPattern => ("OpMatchPattern", &[2, 2]),
+            Opcode::Destructure => ("OpDestructure", &[2]),
+            Opcode::JumpArgGiven => ("OpJumpArgGiven", &[1, 2]),
+            Opcode::CallNamed => ("OpCallNamed", &[1, 2]),
+            Opcode::EnterLoop => ("OpEnterLoop", &[]),
+            Opcode::ExitLoop => ("OpExitLoop", &[]),
+            Opcode::UnwindLoop => ("OpUnwindLoop", &[1]),
+            Opcode::GetLocalCell => ("OpGetLocalCell", &[1]),
+            Opcode::GetFreeCell => ("OpGetFreeCell", &[1]),
+            Opcode::SetFree => ("OpSetFree", &[1]),
+            Opcode::BindLocal => ("OpBindLocal", &[1]),
+            Opcode::LessThan => ("OpLessThan", &[]),
+            Opcode::LessEqual => ("OpLessEqual", &[]),
+        };
+        Definition {
+            name,
+            operand_widths,
+        }
+    }
+}
+
+// Every opcode is listed in the order of its discriminant
+const OPCODES: &[Opcode] = &[
+    Opcode::Constant,
+    Opcode::Pop,
+    Opcode::Add,
+    Opcode::Sub,
+    Opcode::Mul,
+    Opcode::Div,
+    Opcode::Pow,
+    Opcode::True,
+    Opcode::False,
+    Opcode::Equal,
+    Opcode::NotEqual,
+    Opcode::GreaterThan,
+    Opcode::Minus,
+    Opcode::Bang,
+    Opcode::JumpNotTruthy,
+    Opcode::Jump,
+    Opcode::Null,
+    Opcode::GetGlobal,
+    Opcode::SetGlobal,
+    Opcode::Array,
+    Opcode::Hash,
+    Opcode::Index,
+    Opcode::Call,
+    Opcode::ReturnValue,
+    Opcode::Return,
+    Opcode::GetLocal,
+    Opcode::SetLocal,
+    Opcode::GetBuiltin,
+    Opcode::Closure,
+    Opcode::GetFree,
+    Opcode::CurrentClosure,
+    Opcode::Interpolate,
+    Opcode::GreaterEqual,
+    Opcode::Mod,
+    Opcode::BitAnd,
+    Opcode::BitOr,
+    Opcode::BitXor,
+    Opcode::Shl,
+    Opcode::Shr,
+    Opcode::BitNot,
+    Opcode::Assign,
+    Opcode::Iter,
+    Opcode::IterNext,
+    Opcode::Range,
+    Opcode::Slice,
+    Opcode::MatchPattern,
+    Opcode::Destructure,
+    Opcode::JumpArgGiven,
+    Opcode::CallNamed,
+    Opcode::EnterLoop,
+    Opcode::ExitLoop,
+    Opcode::UnwindLoop,
+    Opcode::GetLocalCell,
+    Opcode::GetFreeCell,
+    Opcode::SetFree,
+    Opcode::BindLocal,
+    Opcode::LessThan,
+    Opcode::LessEqual,
+];
+
+impl TryFrom<u8> for Opcode {
+    type Error = Error;
+
+    fn try_from(byte: u8) -> error::Result<Self> {
+        OPCODES
+            .get(byte as usize)
+            .copied()
+            .ok_or(Error::IlligalOpcodeFoundErr)
+    }
+}
+
+pub fn make(op: Opcode, operands: &[usize]) -> Instructions {
+    let def = op.definition();
+    let mut instruction = vec![op as u8];
+    for (operand, width) in operands.iter().zip(def.operand_widths) {
+        match width {
+            2 => instruction.extend_from_slice(&(*operand as u16).to_be_bytes()),
+            1 => instruction.push(*operand as u8),
+            _ => unreachable!("operand width {} is not defined", width),
+        }
+    }
+    instruction
+}
+
+// The position of the first operand which does not fit in its width
+pub fn oversized_operand(op: Opcode, operands: &[usize]) -> Option<usize> {
+    op.definition()
+        .operand_widths
+        .iter()
+        .zip(operands)
+        .position(|(width, operand)| operand >> (8 * width) != 0)
+}
+
+pub fn read_operands(def: &Definition, ins: &[u8]) -> (Vec<usize>, usize) {
+    let mut operands = Vec::with_capacity(def.operand_widths.len());
+    let mut offset = 0;
+    for width in def.operand_widths {
+        match width {
+            2 => operands.push(read_u16(&ins[offset..]) as usize),
+            1 => operands.push(ins[offset] as usize),
+            _ => unreachable!("operand width {} is not defined", width),
+        }
+        offset += width;
+    }
+    (operands, offset)
+}
+
+pub fn read_u16(ins: &[u8]) -> u16 {
+    u16::from_be_bytes([ins[0], ins[1]])
+}
+
+pub fn disassemble(ins: &[u8]) -> error::Result<String> {
+    let mut out = String::new();
+    let mut i = 0;
+    while i < ins.len() {
+        let def = Opcode::try_from(ins[i])?.definition();
+        let (operands, read) = read_operands(&def, &ins[i + 1..]);
+        writeln!(out, "{:04} {}", i, fmt_instruction(&def, &operands)?)?;
+        i += 1 + read;
+    }
+    Ok(out)
+}
+
+fn fmt_instruction(def: &Definition, operands: &[usize]) -> error::Result<String> {
+    if operands.len() != def.operand_widths.len() {
+        return Err(Error::FmtOperandsInputLenErr {
+            expected: operands.len(),
+            got: def.operand_widths.len(),
+        });
+    }
+    match operands {
+        [] => Ok(def.name.to_string()),
+        [a] => Ok(format!("{} {}", def.name, a)),
+        [a, b] => Ok(format!("{} {} {}", def.name, a, b)),
+        _ => Err(Error::UnhandledOperandCountErr {
+            name: def.name.to_string(),
+        }),
+    }
+}
@@ -0,0 +1,349 @@
+use super::*;
+use crate::code::{make, SourceMap};
+use crate::error;
+use crate::lexer::Lexer;
+use crate::parser::ast::{Parameters, Pattern, PatternKind};
+use crate::parser::Parser;
+
+fn compile(input: &str) -> error::Result<Bytecode> {
+    let program = Parser::new(Lexer::new(input)).parse_program()?;
+    let mut compiler = Compiler::new();
+    compiler.compile(&program)?;
+    Ok(compiler.bytecode())
+}
+
+fn function(
+    instructions: Vec<Instructions>,
+    num_locals: usize,
+    name: Option<&str>,
+    parameters: Parameters,
+) -> Object {
+    Object::CompiledFunction(Rc::new(CompiledFunction {
+        instructions: instructions.concat(),
+        source_map: SourceMap::default(),
+        num_locals,
+        name: name.map(String::from),
+        parameters,
+    }))
+}
+
+fn names(names: &[&str]) -> Parameters {
+    Parameters::new(names.iter().map(|name| name.to_string()).collect())
+}
+
+macro_rules! test_compiler {
+    ($test: ident => $input: expr; [$($constant: expr),*]; $($ins: expr),+ $(,)?) => {
+        #[test]
+        fn $test() -> error::Result<()> {
+            let bytecode = compile($input)?;
+            let expected: Vec<Instructions> = vec![$($ins,)+];
+            assert_eq!(
+                code::disassemble(&expected.concat())?,
+                code::disassemble(&bytecode.instructions)?
+            );
+            let constants: Vec<Object> = vec![$($constant,)*];
+            assert_eq!(constants, bytecode.constants);
+            Ok(())
+        }
+    };
+}
+
+test_compiler!(
+    compile_integer_arithmetic => "1 + 2; 1 * 2";
+    [Object::Integer(1), Object::Integer(2), Object::Integer(1), Object::Integer(2)];
+    make(Opcode::Constant, &[0]),
+    make(Opcode::Constant, &[1]),
+    make(Opcode::Add, &[]),
+    make(Opcode::Pop, &[]),
+    make(Opcode::Constant, &[2]),
+    make(Opcode::Constant, &[3]),
+    make(Opcode::Mul, &[]),
+    make(Opcode::Pop, &[]),
+);
+
+test_compiler!(
+    compile_less_than => "1 < 2";
+    [Object::Integer(1), Object::Integer(2)];
+    make(Opcode::Constant, &[0]),
+    make(Opcode::Constant, &[1]),
+    make(Opcode::LessThan, &[]),
+    make(Opcode::Pop, &[]),
+);
+
+test_compiler!(
+    compile_prefix => "-1; !true";
+    [Object::Integer(1)];
+    make(Opcode::Constant, &[0]),
+    make(Opcode::Minus, &[]),
+    make(Opcode::Pop, &[]),
+    make(Opcode::True, &[]),
+    make(Opcode::Bang, &[]),
+    make(Opcode::Pop, &[]),
+);
+
+test_compiler!(
+    compile_conditionals => "if (true) { 10 } else { 20 }; 3333;";
+    [Object::Integer(10), Object::Integer(20), Object::Integer(3333)];
+    make(Opcode::True, &[]),
+    make(Opcode::JumpNotTruthy, &[10]),
+    make(Opcode::Constant, &[0]),
+    make(Opcode::Jump, &[13]),
+    make(Opcode::Constant, &[1]),
+    make(Opcode::Pop, &[]),
+    make(Opcode::Constant, &[2]),
+    make(Opcode::Pop, &[]),
+);
+
+test_compiler!(
+    compile_while_loop => "while (true) { break; continue; }";
+    [];
+    make(Opcode::EnterLoop, &[]),
+    make(Opcode::True, &[]),
+    make(Opcode::JumpNotTruthy, &[18]),
+    make(Opcode::UnwindLoop, &[0]),
+    make(Opcode::Jump, &[18]),
+    make(Opcode::UnwindLoop, &[0]),
+    make(Opcode::Jump, &[1]),
+    make(Opcode::Jump, &[1]),
+    make(Opcode::ExitLoop, &[]),
+);
+
+test_compiler!(
+    compile_for_loop => "for (x in [1]) { break; x }";
+    [Object::Integer(1)];
+    make(Opcode::Constant, &[0]),
+    make(Opcode::Array, &[1]),
+    make(Opcode::Iter, &[]),
+    make(Opcode::EnterLoop, &[]),
+    make(Opcode::IterNext, &[28]),
+    make(Opcode::SetGlobal, &[0]),
+    make(Opcode::UnwindLoop, &[0]),
+    make(Opcode::Pop, &[]),
+    make(Opcode::Pop, &[]),
+    make(Opcode::Jump, &[28]),
+    make(Opcode::GetGlobal, &[0]),
+    make(Opcode::Pop, &[]),
+    make(Opcode::Jump, &[8]),
+    make(Opcode::ExitLoop, &[]),
+);
+
+test_compiler!(
+    compile_ranges => "1..=2; len[1..]";
+    [Object::Integer(1), Object::Integer(2), Object::Integer(1)];
+    make(Opcode::Constant, &[0]),
+    make(Opcode::Constant, &[1]),
+    make(Opcode::Range, &[1]),
+    make(Opcode::Pop, &[]),
+    make(Opcode::GetBuiltin, &[0]),
+    make(Opcode::Constant, &[2]),
+    make(Opcode::Null, &[]),
+    make(Opcode::Slice, &[0]),
+    make(Opcode::Pop, &[]),
+);
+
+test_compiler!(
+    compile_global_let => "let one = 1; let two = one; two;";
+    [Object::Integer(1)];
+    make(Opcode::Constant, &[0]),
+    make(Opcode::SetGlobal, &[0]),
+    make(Opcode::GetGlobal, &[0]),
+    make(Opcode::SetGlobal, &[1]),
+    make(Opcode::GetGlobal, &[1]),
+    make(Opcode::Pop, &[]),
+);
+
+test_compiler!(
+    compile_hash_and_index => "{1: 2}[1]";
+    [Object::Integer(1), Object::Integer(2), Object::Integer(1)];
+    make(Opcode::Constant, &[0]),
+    make(Opcode::Constant, &[1]),
+    make(Opcode::Hash, &[2]),
+    make(Opcode::Constant, &[2]),
+    make(Opcode::Index, &[]),
+    make(Opcode::Pop, &[]),
+);
+
+test_compiler!(
+    compile_function => "fn(a) { let b = 1; a + b }";
+    [
+        Object::Integer(1),
+        function(
+            vec![
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetLocal, &[1]),
+                make(Opcode::GetLocal, &[0]),
+                make(Opcode::GetLocal, &[1]),
+                make(Opcode::Add, &[]),
+                make(Opcode::ReturnValue, &[]),
+            ],
+            2,
+            None,
+            names(&["a"])
+        )
+    ];
+    make(Opcode::Closure, &[1, 0]),
+    make(Opcode::Pop, &[]),
+);
+
+test_compiler!(
+    compile_closure => "fn(a) { fn(b) { a + b } }";
+    [
+        function(
+            vec![
+                make(Opcode::GetFree, &[0]),
+                make(Opcode::GetLocal, &[0]),
+                make(Opcode::Add, &[]),
+                make(Opcode::ReturnValue, &[]),
+            ],
+            1,
+            None,
+            names(&["b"])
+        ),
+        function(
+            vec![
+                make(Opcode::GetLocalCell, &[0]),
+                make(Opcode::Closure, &[0, 1]),
+                make(Opcode::ReturnValue, &[]),
+            ],
+            1,
+            None,
+            names(&["a"])
+        )
+    ];
+    make(Opcode::Closure, &[1, 0]),
+    make(Opcode::Pop, &[]),
+);
+
+test_compiler!(
+    compile_recursive_function => "let f = fn(x) { f(x) }; len([]);";
+    [
+        function(
+            vec![
+                make(Opcode::CurrentClosure, &[]),
+                make(Opcode::GetLocal, &[0]),
+                make(Opcode::Call, &[1]),
+                make(Opcode::ReturnValue, &[]),
+            ],
+            1,
+            Some("f"),
+            names(&["x"])
+        )
+    ];
+    make(Opcode::Closure, &[0, 0]),
+    make(Opcode::SetGlobal, &[0]),
+    make(Opcode::GetBuiltin, &[0]),
+    make(Opcode::Array, &[0]),
+    make(Opcode::Call, &[1]),
+    make(Opcode::Pop, &[]),
+);
+
+test_compiler!(
+    compile_parameters => "let f = fn(a, b = 2) { b }; f(b: 1, a: 0)";
+    [
+        Object::Integer(2),
+        function(
+            vec![
+                make(Opcode::JumpArgGiven, &[1, 9]),
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetLocal, &[1]),
+                make(Opcode::GetLocal, &[1]),
+                make(Opcode::ReturnValue, &[]),
+            ],
+            2,
+            Some("f"),
+            Parameters {
+                defaults: vec![Expression::from(ExprKind::Integer(2))],
+                ..names(&["a", "b"])
+            }
+        ),
+        Object::Integer(1),
+        Object::Integer(0),
+        Object::Array(vec![Object::String("b".to_string()), Object::String("a".to_string())])
+    ];
+    make(Opcode::Closure, &[1, 0]),
+    make(Opcode::SetGlobal, &[0]),
+    make(Opcode::GetGlobal, &[0]),
+    make(Opcode::Constant, &[2]),
+    make(Opcode::Constant, &[3]),
+    make(Opcode::CallNamed, &[2, 4]),
+    make(Opcode::Pop, &[]),
+);
+
+test_compiler!(
+    compile_match => "match (1) { x if x => x, _ => 2 }";
+    [
+        Object::Integer(1),
+        Object::Pattern(Rc::new(Pattern::from(PatternKind::Binding("x".to_string())))),
+        Object::Pattern(Rc::new(Pattern::from(PatternKind::Wildcard))),
+        Object::Integer(2)
+    ];
+    make(Opcode::Constant, &[0]),
+    make(Opcode::MatchPattern, &[1, 24]),
+    make(Opcode::SetGlobal, &[0]),
+    make(Opcode::GetGlobal, &[0]),
+    make(Opcode::JumpNotTruthy, &[24]),
+    make(Opcode::Pop, &[]),
+    make(Opcode::GetGlobal, &[0]),
+    make(Opcode::Jump, &[38]),
+    make(Opcode::MatchPattern, &[2, 36]),
+    make(Opcode::Pop, &[]),
+    make(Opcode::Constant, &[3]),
+    make(Opcode::Jump, &[38]),
+    make(Opcode::Pop, &[]),
+    make(Opcode::Null, &[]),
+    make(Opcode::Pop, &[]),
+);
+
+test_compiler!(
+    compile_let_pattern => "let [a, ..b] = []; b";
+    [Object::Pattern(Rc::new(Pattern::from(PatternKind::Array {
+        elements: vec![Pattern::from(PatternKind::Binding("a".to_string()))],
+        rest: Some(Some("b".to_string())),
+    })))];
+    make(Opcode::Array, &[0]),
+    make(Opcode::Destructure, &[0]),
+    make(Opcode::SetGlobal, &[1]),
+    make(Opcode::SetGlobal, &[0]),
+    make(Opcode::GetGlobal, &[1]),
+    make(Opcode::Pop, &[]),
+);
+
+#[test]
+fn compile_undefined_variable() {
+    assert_eq!(
+        Err(Error::UndeclaredNameErr {
+            name: "foo".to_string(),
+            assigned: false,
+            span: None
+        }),
+        compile("foo").map(|_| ())
+    );
+}
+
+#[test]
+fn compile_operand_limits() {
+    let lets = |n: usize| {
+        let lets: String = (0..n).map(|i| format!("let a{} = {};", i, i)).collect();
+        format!("fn() {{ {} a1 }}()", lets)
+    };
+    let call = |n: usize| format!("fn(..r) {{ r }}({})", vec!["0"; n].join(", "));
+    let constants = |n: usize| (0..n).map(|i| format!("{};", i)).collect::<String>();
+    let jump = |n: usize| format!("let x = 1; if (x) {{ {} }}", "x;".repeat(n));
+    let cases = [
+        (lets(256), lets(257), "too many locals"),
+        (call(255), call(256), "too many arguments"),
+        (constants(65536), constants(65537), "too many constants"),
+        (
+            jump(16380),
+            jump(16381),
+            "too many instructions to jump over",
+        ),
+    ];
+    for (fits, too_large, expected) in &cases {
+        assert!(compile(fits).is_ok(), "{}", expected);
+        match compile(too_large) {
+            Err(Error::CompileErr { msg, .. }) => assert_eq!(*expected, msg),
+            other => panic!("expected {:?}, got {:?}", expected, other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,815 @@
+#[cfg(test)]
+mod compiler_test;
+pub mod symbol_table;
+
+use std::convert::TryFrom;
+use std::mem;
+use std::rc::Rc;
+
+use crate::code::{self, Instructions, Opcode, SourceMap};
+use crate::error;
+use crate::lexer::span::Span;
+use crate::lexer::token::Token;
+use crate::object::builtins::BUILTINS;
+use crate::object::{CompiledFunction, Object};
+use crate::parser::ast::{
+    BlockStmt, ExprKind, Expression, MatchArm, Parameters, Pattern, Program, Statement, StmtKind,
+    StringPart,
+};
+
+use self::symbol_table::{Symbol, SymbolScope, SymbolTable};
+
+type Error = error::MonkeyErr;
+
+pub struct Bytecode {
+    pub instructions: Instructions,
+    pub source_map: SourceMap,
+    pub constants: Vec<Object>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct EmittedInstruction {
+    opcode: Opcode,
+    position: usize,
+}
+
+#[derive(Debug, Default)]
+struct CompilationScope {
+    instructions: Instructions,
+    source_map: SourceMap,
+    last_instruction: Option<EmittedInstruction>,
+    previous_instruction: Option<EmittedInstruction>,
+    // The loops around the instruction being compiled, innermost last
+    loops: Vec<Loop>,
+}
+
+#[derive(Debug)]
+struct Loop {
+    // Where continue jumps to
+    start: usize,
+    // The jumps of break statements, changed to the end of the loop once it
+    // is known
+    breaks: Vec<usize>,
+    // A for loop keeps its sequence and position on the stack
+    iterating: bool,
+}
+
+pub struct Compiler {
+    constants: Vec<Object>,
+    symbol_table: SymbolTable,
+    scopes: Vec<CompilationScope>,
+    // The span of the node being compiled
+    span: Span,
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        let mut symbol_table = SymbolTable::new();
+        for (i, (name, _)) in BUILTINS.iter().enumerate() {
+            symbol_table.define_builtin(i, name);
+        }
+        Self::new_with_state(symbol_table, Vec::new())
+    }
+
+    // The REPL keeps the symbol table and the constants between each line
+    pub fn new_with_state(symbol_table: SymbolTable, constants: Vec<Object>) -> Self {
+        Self {
+            constants,
+            symbol_table,
+            scopes: vec![CompilationScope::default()],
+            span: Span::default(),
+        }
+    }
+
+    pub fn into_state(self) -> (SymbolTable, Vec<Object>) {
+        (self.symbol_table, self.constants)
+    }
+
+    pub fn bytecode(&self) -> Bytecode {
+        Bytecode {
+            instructions: self.current_scope().instructions.clone(),
+            source_map: self.current_scope().source_map.clone(),
+            constants: self.constants.clone(),
+        }
+    }
+
+    pub fn compile(&mut self, program: &Program) -> error::Result<()> {
+        for stmt in program.get_stmts() {
+            self.compile_statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn compile_block_statement(&mut self, block: &BlockStmt) -> error::Result<()> {
+        for stmt in block {
+            self.compile_statement(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn compile_statement(&mut self, stmt: &Statement) -> error::Result<()> {
+        let outer = mem::replace(&mut self.span, stmt.span);
+        let result = self.compile_statement_kind(stmt);
+        self.span = outer;
+        result.map_err(|err| err.at(stmt.span))
+    }
+
+    fn compile_statement_kind(&mut self, stmt: &Statement) -> error::Result<()> {
+        match &stmt.kind {
+            StmtKind::LetStmt { name, value } => {
+                if let ExprKind::Function {
+                    parameters, body, ..
+                } = &value.kind
+                {
+                    self.compile_function(Some(name), parameters, body)?;
+                } else {
+                    self.compile_expression(value)?;
+                }
+                let symbol = self.symbol_table.define(name);
+                self.emit_set_symbol(&symbol)?;
+            }
+            StmtKind::LetPatternStmt { pattern, value } => {
+                self.compile_expression(value)?;
+                let pattern_index = self.add_constant(Object::Pattern(Rc::new(pattern.clone())));
+                self.emit(Opcode::Destructure, &[pattern_index])?;
+                self.emit_set_bindings(pattern, false)?;
+            }
+            StmtKind::ReturnStmt { value } => {
+                self.compile_expression(value)?;
+                self.emit(Opcode::ReturnValue, &[])?;
+            }
+            StmtKind::ExpressionStmt { expression } => {
+                self.compile_expression(expression)?;
+                self.emit(Opcode::Pop, &[])?;
+            }
+            StmtKind::WhileStmt { condition, body } => {
+                self.emit(Opcode::EnterLoop, &[])?;
+                let start = self.current_scope().instructions.len();
+                self.compile_expression(condition)?;
+                let jump_not_truthy_pos = self.emit(Opcode::JumpNotTruthy, &[9999])?;
+                self.compile_loop_body(start, false, body, jump_not_truthy_pos)?;
+            }
+            StmtKind::ForStmt {
+                variable,
+                iterable,
+                body,
+            } => {
+                self.compile_expression(iterable)?;
+                self.emit(Opcode::Iter, &[])?;
+                self.emit(Opcode::EnterLoop, &[])?;
+                let start = self.current_scope().instructions.len();
+                let iter_next_pos = self.emit(Opcode::IterNext, &[9999])?;
+                let symbol = self.symbol_table.define(variable);
+                self.emit_set_symbol(&symbol)?;
+                self.compile_loop_body(start, true, body, iter_next_pos)?;
+            }
+            StmtKind::BreakStmt => {
+                let iterating = self.current_loop()?.iterating;
+                let depth = self.current_scope().loops.len() - 1;
+                self.emit(Opcode::UnwindLoop, &[depth])?;
+                if iterating {
+                    self.emit(Opcode::Pop, &[])?;
+                    self.emit(Opcode::Pop, &[])?;
+                }
+                let jump_pos = self.emit(Opcode::Jump, &[9999])?;
+                self.current_loop()?.breaks.push(jump_pos);
+            }
+            StmtKind::ContinueStmt => {
+                let start = self.current_loop()?.start;
+                let depth = self.current_scope().loops.len() - 1;
+                self.emit(Opcode::UnwindLoop, &[depth])?;
+                self.emit(Opcode::Jump, &[start])?;
+            }
+        }
+        Ok(())
+    }
+
+    // The body jumps back to start, and exit_pos is the jump which leaves
+    // the loop when it is done. A loop leaves nothing on the stack, and
+    // break and continue first drop what is above it on the stack.
+    fn compile_loop_body(
+        &mut self,
+        start: usize,
+        iterating: bool,
+        body: &BlockStmt,
+        exit_pos: usize,
+    ) -> error::Result<()> {
+        self.current_scope_mut().loops.push(Loop {
+            start,
+            breaks: Vec::new(),
+            iterating,
+        });
+        self.compile_block_statement(body)?;
+        self.emit(Opcode::Jump, &[start])?;
+
+        let end = self.current_scope().instructions.len();
+        self.change_operand(exit_pos, end)?;
+        let lp = self.current_scope_mut().loops.pop();
+        for jump_pos in lp.map_or_else(Vec::new, |lp| lp.breaks) {
+            self.change_operand(jump_pos, end)?;
+        }
+        self.emit(Opcode::ExitLoop, &[])?;
+        Ok(())
+    }
+
+    // The parser only accepts break and continue inside of a loop
+    fn current_loop(&mut self) -> error::Result<&mut Loop> {
+        self.current_scope_mut()
+            .loops
+            .last_mut()
+            .ok_or_else(|| Error::CompileErr {
+                msg: "break or continue outside of a loop".to_string(),
+                span: None,
+            })
+    }
+
+    fn compile_expression(&mut self, expr: &Expression) -> error::Result<()> {
+        let outer = mem::replace(&mut self.span, expr.span);
+        let result = self.compile_expression_kind(expr);
+        self.span = outer;
+        result.map_err(|err| err.at(expr.span))
+    }
+
+    fn compile_expression_kind(&mut self, expr: &Expression) -> error::Result<()> {
+        match &expr.kind {
+            ExprKind::Ident(name) => {
+                let symbol =
+                    self.symbol_table
+                        .resolve(name)
+                        .ok_or_else(|| Error::UndeclaredNameErr {
+                            name: name.to_string(),
+                            assigned: false,
+                            span: None,
+                        })?;
+                self.load_symbol(&symbol)?;
+            }
+            ExprKind::String(s) => self.emit_constant(Object::String(s.clone()))?,
+            ExprKind::Interpolated(parts) => {
+                for part in parts {
+                    match part {
+                        StringPart::Literal(s) => self.emit_constant(Object::String(s.clone()))?,
+                        StringPart::Expr(expr) => self.compile_expression(expr)?,
+                    }
+                }
+                self.emit(Opcode::Interpolate, &[parts.len()])?;
+            }
+            ExprKind::Boolean(true) => {
+                self.emit(Opcode::True, &[])?;
+            }
+            ExprKind::Boolean(false) => {
+                self.emit(Opcode::False, &[])?;
+            }
+            ExprKind::Integer(n) => self.emit_constant(Object::Integer(*n))?,
+            ExprKind::Float(n) => self.emit_constant(Object::Float(*n))?,
+            ExprKind::Imaginary(im) => self.emit_constant(Object::Complex { re: 0.0, im: *im })?,
+            ExprKind::Array(elements) => {
+                for element in elements {
+                    self.compile_expression(element)?;
+                }
+                self.emit(Opcode::Array, &[elements.len()])?;
+            }
+            ExprKind::Hash { key, value } => {
+                for (k, v) in key.iter().zip(value) {
+                    self.compile_expression(k)?;
+                    self.compile_expression(v)?;
+                }
+                self.emit(Opcode::Hash, &[key.len() * 2])?;
+            }
+            ExprKind::Prefix { operator, right } => {
+                self.compile_expression(right)?;
+                match operator {
+                    Token::BANG => self.emit(Opcode::Bang, &[])?,
+                    Token::MINUS => self.emit(Opcode::Minus, &[])?,
+                    Token::BITNOT => self.emit(Opcode::BitNot, &[])?,
+                    _ => return Err(unknown_operator(operator)),
+                };
+            }
+            ExprKind::Infix {
+                left,
+                operator,
+                right,
+            } => self.compile_infix_expr(left, operator, right)?,
+            ExprKind::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                self.compile_range_bounds(start, end)?;
+                self.emit(Opcode::Range, &[usize::from(*inclusive)])?;
+            }
+            ExprKind::Assign {
+                target,
+                operator,
+                value,
+            } => self.compile_assign_expr(target, operator, value)?,
+            ExprKind::IfExpr {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                self.compile_expression(condition)?;
+                let jump_not_truthy_pos = self.emit(Opcode::JumpNotTruthy, &[9999])?;
+
+                self.compile_block_statement(consequence)?;
+                self.finish_block_value()?;
+
+                let jump_pos = self.emit(Opcode::Jump, &[9999])?;
+                let after_consequence_pos = self.current_scope().instructions.len();
+                self.change_operand(jump_not_truthy_pos, after_consequence_pos)?;
+
+                if let Some(alternative) = alternative {
+                    self.compile_block_statement(alternative)?;
+                    self.finish_block_value()?;
+                } else {
+                    self.emit(Opcode::Null, &[])?;
+                }
+                let after_alternative_pos = self.current_scope().instructions.len();
+                self.change_operand(jump_pos, after_alternative_pos)?;
+            }
+            ExprKind::Function {
+                parameters, body, ..
+            } => self.compile_function(None, parameters, body)?,
+            ExprKind::Call {
+                function,
+                arguments,
+                keywords,
+                ..
+            } => {
+                self.compile_expression(function)?;
+                for arg in arguments {
+                    self.compile_expression(arg)?;
+                }
+                if keywords.is_empty() {
+                    self.emit(Opcode::Call, &[arguments.len()])?;
+                } else {
+                    let names = keywords.iter().cloned().map(Object::String).collect();
+                    let names_index = self.add_constant(Object::Array(names));
+                    self.emit(Opcode::CallNamed, &[arguments.len(), names_index])?;
+                }
+            }
+            ExprKind::Match { subject, arms } => self.compile_match_expr(subject, arms)?,
+        }
+        Ok(())
+    }
+
+    // The subject stays on the stack while the arms are tried. OpMatchPattern
+    // pushes the values of the bindings of a pattern which matches, and jumps
+    // to the next arm otherwise. The variables of an arm get slots of their
+    // own, and are seen only by its guard and body.
+    fn compile_match_expr(&mut self, subject: &Expression, arms: &[MatchArm]) -> error::Result<()> {
+        self.compile_expression(subject)?;
+        let mut end_jumps = Vec::new();
+        for arm in arms {
+            let outer = self.symbol_table.definitions();
+            let pattern = self.add_constant(Object::Pattern(Rc::new(arm.pattern.clone())));
+            let match_pos = self.emit(Opcode::MatchPattern, &[pattern, 9999])?;
+            self.emit_set_bindings(&arm.pattern, true)?;
+            let guard_pos = match &arm.guard {
+                Some(guard) => {
+                    self.compile_expression(guard)?;
+                    Some(self.emit(Opcode::JumpNotTruthy, &[9999])?)
+                }
+                None => None,
+            };
+
+            self.emit(Opcode::Pop, &[])?;
+            self.compile_expression(&arm.body)?;
+            end_jumps.push(self.emit(Opcode::Jump, &[9999])?);
+            self.symbol_table.restore(outer);
+
+            let next_arm = self.current_scope().instructions.len();
+            self.change_operands(match_pos, &[pattern, next_arm])?;
+            if let Some(guard_pos) = guard_pos {
+                self.change_operand(guard_pos, next_arm)?;
+            }
+        }
+        self.emit(Opcode::Pop, &[])?;
+        self.emit(Opcode::Null, &[])?;
+
+        let end = self.current_scope().instructions.len();
+        for jump_pos in end_jumps {
+            self.change_operand(jump_pos, end)?;
+        }
+        Ok(())
+    }
+
+    fn compile_infix_expr(
+        &mut self,
+        left: &Expression,
+        operator: &Token,
+        right: &Expression,
+    ) -> error::Result<()> {
+        if matches!(operator, Token::AND | Token::OR) {
+            return self.compile_logical_expr(left, operator, right);
+        }
+        if let (
+            Token::LBRACKET,
+            ExprKind::Range {
+                start,
+                end,
+                inclusive,
+            },
+        ) = (operator, &right.kind)
+        {
+            self.compile_expression(left)?;
+            self.compile_range_bounds(start, end)?;
+            self.emit(Opcode::Slice, &[usize::from(*inclusive)])?;
+            return Ok(());
+        }
+
+        self.compile_expression(left)?;
+        self.compile_expression(right)?;
+        let opcode = match operator {
+            Token::PLUS => Opcode::Add,
+            Token::MINUS => Opcode::Sub,
+            Token::ASTERISK => Opcode::Mul,
+            Token::SLASH => Opcode::Div,
+            Token::PERCENT => Opcode::Mod,
+            Token::BITAND => Opcode::BitAnd,
+            Token::BITOR => Opcode::BitOr,
+            Token::BITXOR => Opcode::BitXor,
+            Token::SHL => Opcode::Shl,
+            Token::SHR => Opcode::Shr,
+            Token::POWER => Opcode::Pow,
+            Token::GT => Opcode::GreaterThan,
+            Token::GTEQ => Opcode::GreaterEqual,
+            Token::LT => Opcode::LessThan,
+            Token::LTEQ => Opcode::LessEqual,
+            Token::EQ => Opcode::Equal,
+            Token::NOTEQ => Opcode::NotEqual,
+            Token::LBRACKET => Opcode::Index,
+            _ => return Err(unknown_operator(operator)),
+        };
+        self.emit(opcode, &[])?;
+        Ok(())
+    }
+
+    // A bound which is left out is pushed as null
+    fn compile_range_bounds(
+        &mut self,
+        start: &Option<Box<Expression>>,
+        end: &Option<Box<Expression>>,
+    ) -> error::Result<()> {
+        for bound in &[start, end] {
+            match bound {
+                Some(bound) => self.compile_expression(bound)?,
+                None => {
+                    self.emit(Opcode::Null, &[])?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // The variable, the indexes and the value are pushed for OpAssign, which
+    // leaves the assigned value below the updated variable
+    fn compile_assign_expr(
+        &mut self,
+        target: &Expression,
+        operator: &Token,
+        value: &Expression,
+    ) -> error::Result<()> {
+        let mut place = target;
+        let mut indexes = Vec::new();
+        while let ExprKind::Infix {
+            left,
+            operator: Token::LBRACKET,
+            right,
+        } = &place.kind
+        {
+            indexes.push(right);
+            place = left;
+        }
+        let name = match &place.kind {
+            ExprKind::Ident(name) => name,
+            _ => {
+                return Err(Error::CompileErr {
+                    msg: format!("cannot assign to {}", target),
+                    span: None,
+                })
+            }
+        };
+        let symbol = self.symbol_table.resolve(name);
+        let symbol = match symbol {
+            Some(symbol) if matches!(symbol.scope, SymbolScope::Global | SymbolScope::Local) => {
+                symbol
+            }
+            Some(symbol) if symbol.scope == SymbolScope::Builtin => {
+                return Err(Error::CompileErr {
+                    msg: format!("cannot assign to builtin function {}", name),
+                    span: None,
+                })
+            }
+            Some(symbol) if symbol.scope == SymbolScope::Free => symbol,
+            Some(_) => {
+                return Err(Error::CompileErr {
+                    msg: format!("cannot assign to {} inside of the function itself", name),
+                    span: None,
+                })
+            }
+            None => {
+                return Err(Error::UndeclaredNameErr {
+                    name: name.to_string(),
+                    assigned: true,
+                    span: None,
+                })
+            }
+        };
+        let operator_index = ASSIGN_OPERATORS
+            .iter()
+            .position(|op| op == operator)
+            .ok_or_else(|| unknown_operator(operator))?;
+
+        self.load_symbol(&symbol)?;
+        for index in indexes.iter().rev() {
+            self.compile_expression(index)?;
+        }
+        self.compile_expression(value)?;
+        self.emit(Opcode::Assign, &[indexes.len(), operator_index])?;
+        self.emit_set_symbol(&symbol)?;
+        Ok(())
+    }
+
+    // The right operand is jumped over when the left one decides the value.
+    // Both give a boolean, which `!!` makes of the right operand.
+    fn compile_logical_expr(
+        &mut self,
+        left: &Expression,
+        operator: &Token,
+        right: &Expression,
+    ) -> error::Result<()> {
+        self.compile_expression(left)?;
+        let jump_not_truthy_pos = self.emit(Opcode::JumpNotTruthy, &[9999])?;
+        if operator == &Token::OR {
+            self.emit(Opcode::True, &[])?;
+            let jump_pos = self.emit(Opcode::Jump, &[9999])?;
+            let right_pos = self.current_scope().instructions.len();
+            self.change_operand(jump_not_truthy_pos, right_pos)?;
+            self.compile_truthiness(right)?;
+            let end_pos = self.current_scope().instructions.len();
+            self.change_operand(jump_pos, end_pos)?;
+        } else {
+            self.compile_truthiness(right)?;
+            let jump_pos = self.emit(Opcode::Jump, &[9999])?;
+            let false_pos = self.current_scope().instructions.len();
+            self.change_operand(jump_not_truthy_pos, false_pos)?;
+            self.emit(Opcode::False, &[])?;
+            let end_pos = self.current_scope().instructions.len();
+            self.change_operand(jump_pos, end_pos)?;
+        }
+        Ok(())
+    }
+
+    fn compile_truthiness(&mut self, expr: &Expression) -> error::Result<()> {
+        self.compile_expression(expr)?;
+        self.emit(Opcode::Bang, &[])?;
+        self.emit(Opcode::Bang, &[])?;
+        Ok(())
+    }
+
+    fn compile_function(
+        &mut self,
+        name: Option<&str>,
+        parameters: &Parameters,
+        body: &BlockStmt,
+    ) -> error::Result<()> {
+        self.enter_scope();
+        if let Some(name) = name {
+            self.symbol_table.define_function_name(name);
+        }
+        let symbols: Vec<Symbol> = parameters
+            .bound_names()
+            .map(|param| self.symbol_table.define(param))
+            .collect();
+        // A default is evaluated only when the call gave no argument for it
+        for (i, default) in parameters.defaults.iter().enumerate() {
+            let symbol = &symbols[parameters.required() + i];
+            let jump_pos = self.emit(Opcode::JumpArgGiven, &[symbol.index, 9999])?;
+            self.compile_expression(default)?;
+            self.emit(Opcode::SetLocal, &[symbol.index])?;
+            let after_default_pos = self.current_scope().instructions.len();
+            self.change_operands(jump_pos, &[symbol.index, after_default_pos])?;
+        }
+
+        self.compile_block_statement(body)?;
+        if self.last_instruction_is(Opcode::Pop) {
+            self.replace_last_pop_with_return();
+        }
+        if !self.last_instruction_is(Opcode::ReturnValue) {
+            self.emit(Opcode::Return, &[])?;
+        }
+
+        let free_symbols = self.symbol_table.free_symbols.clone();
+        let num_locals = self.symbol_table.num_definitions();
+        let (instructions, source_map) = self.leave_scope();
+
+        for symbol in &free_symbols {
+            self.load_cell(symbol)?;
+        }
+
+        let func = CompiledFunction {
+            instructions,
+            source_map,
+            num_locals,
+            name: name.map(String::from),
+            parameters: parameters.clone(),
+        };
+        let idx = self.add_constant(Object::CompiledFunction(Rc::new(func)));
+        self.emit(Opcode::Closure, &[idx, free_symbols.len()])?;
+        Ok(())
+    }
+
+    // A block used as a value leaves exactly one object on the stack
+    fn finish_block_value(&mut self) -> error::Result<()> {
+        if self.last_instruction_is(Opcode::Pop) {
+            self.remove_last_pop();
+        } else {
+            self.emit(Opcode::Null, &[])?;
+        }
+        Ok(())
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol) -> error::Result<()> {
+        match symbol.scope {
+            SymbolScope::Global => self.emit(Opcode::GetGlobal, &[symbol.index])?,
+            SymbolScope::Local => self.emit(Opcode::GetLocal, &[symbol.index])?,
+            SymbolScope::Builtin => self.emit(Opcode::GetBuiltin, &[symbol.index])?,
+            SymbolScope::Free => self.emit(Opcode::GetFree, &[symbol.index])?,
+            SymbolScope::Function => self.emit(Opcode::CurrentClosure, &[])?,
+        };
+        Ok(())
+    }
+
+    // A closure captures a variable by the cell it is kept in, so that
+    // assignments are seen both inside and outside of it
+    fn load_cell(&mut self, symbol: &Symbol) -> error::Result<()> {
+        match symbol.scope {
+            SymbolScope::Local => self.emit(Opcode::GetLocalCell, &[symbol.index])?,
+            SymbolScope::Free => self.emit(Opcode::GetFreeCell, &[symbol.index])?,
+            _ => return self.load_symbol(symbol),
+        };
+        Ok(())
+    }
+
+    fn emit_set_symbol(&mut self, symbol: &Symbol) -> error::Result<()> {
+        match symbol.scope {
+            SymbolScope::Global => self.emit(Opcode::SetGlobal, &[symbol.index])?,
+            SymbolScope::Free => self.emit(Opcode::SetFree, &[symbol.index])?,
+            _ => self.emit(Opcode::SetLocal, &[symbol.index])?,
+        };
+        Ok(())
+    }
+
+    // The values of the bindings of pattern are on the stack, the last one on
+    // top. Fresh locals are not shared with closures made by an earlier run of
+    // the same code.
+    fn emit_set_bindings(&mut self, pattern: &Pattern, fresh: bool) -> error::Result<()> {
+        let symbols: Vec<Symbol> = pattern
+            .bindings()
+            .into_iter()
+            .map(|name| self.symbol_table.define(name))
+            .collect();
+        for symbol in symbols.iter().rev() {
+            if fresh && symbol.scope == SymbolScope::Local {
+                self.emit(Opcode::BindLocal, &[symbol.index])?;
+            } else {
+                self.emit_set_symbol(symbol)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn add_constant(&mut self, obj: Object) -> usize {
+        self.constants.push(obj);
+        self.constants.len() - 1
+    }
+
+    fn emit_constant(&mut self, obj: Object) -> error::Result<()> {
+        let idx = self.add_constant(obj);
+        self.emit(Opcode::Constant, &[idx])?;
+        Ok(())
+    }
+
+    fn emit(&mut self, op: Opcode, operands: &[usize]) -> error::Result<usize> {
+        check_operands(op, operands)?;
+        let ins = code::make(op, operands);
+        let span = self.span;
+        let scope = self.current_scope_mut();
+        let position = scope.instructions.len();
+        scope.instructions.extend(ins);
+        scope.source_map.add(position, span);
+        scope.previous_instruction = scope.last_instruction;
+        scope.last_instruction = Some(EmittedInstruction {
+            opcode: op,
+            position,
+        });
+        Ok(position)
+    }
+
+    fn last_instruction_is(&self, op: Opcode) -> bool {
+        matches!(self.current_scope().last_instruction, Some(last) if last.opcode == op)
+    }
+
+    fn remove_last_pop(&mut self) {
+        let scope = self.current_scope_mut();
+        if let Some(last) = scope.last_instruction {
+            scope.instructions.truncate(last.position);
+            scope.source_map.truncate(last.position);
+            scope.last_instruction = scope.previous_instruction;
+        }
+    }
+
+    fn replace_last_pop_with_return(&mut self) {
+        let scope = self.current_scope_mut();
+        if let Some(last) = scope.last_instruction.as_mut() {
+            scope.instructions[last.position] = Opcode::ReturnValue as u8;
+            last.opcode = Opcode::ReturnValue;
+        }
+    }
+
+    fn change_operand(&mut self, op_position: usize, operand: usize) -> error::Result<()> {
+        self.change_operands(op_position, &[operand])
+    }
+
+    fn change_operands(&mut self, op_position: usize, operands: &[usize]) -> error::Result<()> {
+        let scope = self.current_scope_mut();
+        let op = scope.instructions[op_position];
+        let op = Opcode::try_from(op).expect("the compiler only emits valid opcodes");
+        check_operands(op, operands)?;
+        let new_instruction = code::make(op, operands);
+        scope.instructions[op_position..op_position + new_instruction.len()]
+            .copy_from_slice(&new_instruction);
+        Ok(())
+    }
+
+    fn current_scope(&self) -> &CompilationScope {
+        self.scopes.last().expect("main scope is never left")
+    }
+
+    fn current_scope_mut(&mut self) -> &mut CompilationScope {
+        self.scopes.last_mut().expect("main scope is never left")
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(CompilationScope::default());
+        let outer = mem::take(&mut self.symbol_table);
+        self.symbol_table = SymbolTable::new_enclosed(outer);
+    }
+
+    fn leave_scope(&mut self) -> (Instructions, SourceMap) {
+        let scope = self.scopes.pop().expect("main scope is never left");
+        let outer = self
+            .symbol_table
+            .outer
+            .take()
+            .expect("function scope always has an outer symbol table");
+        self.symbol_table = *outer;
+        (scope.instructions, scope.source_map)
+    }
+}
+
+// Operands are one or two bytes wide, so a program which needs larger ones
+// cannot be compiled
+fn check_operands(op: Opcode, operands: &[usize]) -> error::Result<()> {
+    match code::oversized_operand(op, operands) {
+        Some(i) => Err(Error::CompileErr {
+            msg: format!("too many {}", operand_kind(op, i)),
+            span: None,
+        }),
+        None => Ok(()),
+    }
+}
+
+// What the i-th operand of op counts
+fn operand_kind(op: Opcode, i: usize) -> &'static str {
+    match (op, i) {
+        (Opcode::GetLocal | Opcode::SetLocal | Opcode::BindLocal, _) => "locals",
+        (Opcode::GetGlobal | Opcode::SetGlobal, _) => "globals",
+        (Opcode::GetFree, _) | (Opcode::Closure, 1) => "free variables",
+        (Opcode::Call, _) | (Opcode::CallNamed, 0) => "arguments",
+        (Opcode::JumpArgGiven, 0) => "parameters",
+        (Opcode::Array | Opcode::Hash, _) => "elements",
+        (Opcode::Interpolate, _) => "parts in a string",
+        (Opcode::Assign, _) => "indexes",
+        (Opcode::UnwindLoop, _) => "nested loops",
+        (Opcode::Jump | Opcode::JumpNotTruthy | Opcode::IterNext, _)
+        | (Opcode::JumpArgGiven | Opcode::MatchPattern, 1) => "instructions to jump over",
+        _ => "constants",
+    }
+}
+
+// The operand of OpAssign which tells the operator
+pub const ASSIGN_OPERATORS: &[Token] = &[
+    Token::ASSIGN,
+    Token::PLUSEQ,
+    Token::MINUSEQ,
+    Token::ASTERISKEQ,
+    Token::SLASHEQ,
+];
+
+fn unknown_operator(operator: &Token) -> Error {
+    Error::CompileErr {
+        msg: format!("unknown operator {}", operator),
+        span: None,
+    }
+}
@@ -0,0 +1,263 @@
+use super::*;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+fn render_parse_error(source: &str, color: bool) -> error::Result<String> {
+    match Parser::new(Lexer::new(source)).parse_program() {
+        Err(err) => render(&Diagnostic::from(&err), "test.mk", source, color),
+        Ok(program) => panic!("expected a parse error, got {:?}", program),
+    }
+}
+
+#[test]
+fn render_unclosed_paren() -> error::Result<()> {
+    let source = "let a = 1;\nlet x = (1 + 2;\n";
+    let expected = "\
+error: Expected next token to be RPAREN to close LPAREN, got SEMICOLON instead
+ --> test.mk:2:15
+  |
+2 | let x = (1 + 2;
+  |         - opening paren here
+  |               ^ expected RPAREN here
+  = help: add the missing `)`
+";
+    assert_eq!(expected, render_parse_error(source, false)?);
+    Ok(())
+}
+
+#[test]
+fn render_labels_on_different_lines() -> error::Result<()> {
+    let source = "if (x) {\n  x\n";
+    let expected = "\
+error: Expected next token to be RBRACE to close LBRACE, got EOF instead
+ --> test.mk:3:1
+  |
+1 | if (x) {
+  |        - opening brace here
+3 |
+  | ^ expected RBRACE here
+  = help: add the missing `}`
+";
+    assert_eq!(expected, render_parse_error(source, false)?);
+    Ok(())
+}
+
+#[test]
+fn render_wide_span() -> error::Result<()> {
+    let source = "let foobar";
+    let expected = "\
+error: Expected next token to be ASSIGN, got EOF instead
+ --> test.mk:1:11
+  |
+1 | let foobar
+  |           ^ expected ASSIGN here
+";
+    assert_eq!(expected, render_parse_error(source, false)?);
+
+    let source = "fn(x, 12) { x }";
+    let rendered = render_parse_error(source, false)?;
+    assert!(rendered.contains("  |       ^^ expected an identifier here\n"));
+    Ok(())
+}
+
+#[test]
+fn render_without_location() -> error::Result<()> {
+    let err = MonkeyErr::UndeclaredNameErr {
+        name: "foo".to_string(),
+        assigned: false,
+        span: None,
+    };
+    let expected = "\
+error: identifier not found: foo
+  --> <repl>
+  = help: bind it first with `let`
+";
+    assert_eq!(
+        expected,
+        render(&Diagnostic::from(&err), "<repl>", "foo", false)?
+    );
+    Ok(())
+}
+
+#[test]
+fn render_with_color() -> error::Result<()> {
+    let rendered = render_parse_error("(1", true)?;
+    assert!(rendered.starts_with("\x1b[1;31merror\x1b[0m"));
+    assert!(rendered.contains("\x1b[1;31m^ expected RPAREN here\x1b[0m"));
+    assert!(!render_parse_error("(1", false)?.contains('\x1b'));
+    Ok(())
+}
+
+#[test]
+fn render_wide_chars() -> error::Result<()> {
+    let source = "let 이름 = (\"값\" + 1;";
+    let expected = "\
+error: Expected next token to be RPAREN to close LPAREN, got SEMICOLON instead
+ --> test.mk:1:18
+  |
+1 | let 이름 = (\"값\" + 1;
+  |            - opening paren here
+  |                     ^ expected RPAREN here
+  = help: add the missing `)`
+";
+    assert_eq!(expected, render_parse_error(source, false)?);
+    Ok(())
+}
+
+#[test]
+fn render_unreachable_arm_warning() -> error::Result<()> {
+    let source = "match (x) {\n  [a, ..] => a,\n  [1, 2] => 0,\n}";
+    let mut parser = Parser::new(Lexer::new(source));
+    parser.parse_program()?;
+    let warnings = parser.take_warnings();
+    let expected = "\
+warning: unreachable match arm
+ --> test.mk:3:3
+  |
+2 |   [a, ..] => a,
+  |   ------- this arm matches all of its values first
+3 |   [1, 2] => 0,
+  |   ^^^^^^ this arm is never reached
+  = help: move the arm up, or remove it
+";
+    assert_eq!(
+        expected,
+        render(&Diagnostic::from(&warnings[0]), "test.mk", source, false)?
+    );
+    let rendered = render(&Diagnostic::from(&warnings[0]), "test.mk", source, true)?;
+    assert!(rendered.starts_with("\x1b[1;33mwarning\x1b[0m"));
+    Ok(())
+}
+
+#[test]
+fn render_parameter_order() -> error::Result<()> {
+    let source = "let f = fn(x, y = 1, z) { x };";
+    let expected = "\
+error: Parameter z needs a default, as it follows a parameter with one
+ --> test.mk:1:22
+  |
+1 | let f = fn(x, y = 1, z) { x };
+  |                      ^ this parameter has no default
+  = help: give it a default too, or move it before the parameters with defaults
+";
+    assert_eq!(expected, render_parse_error(source, false)?);
+    Ok(())
+}
+
+#[test]
+fn render_pipeline_without_call() -> error::Result<()> {
+    let source = "let n = xs |> len;";
+    let expected = "\
+error: Expected a call after |>
+ --> test.mk:1:15
+  |
+1 | let n = xs |> len;
+  |               ^^^ this is not a call
+  = help: the value goes in as the first argument of a call, like `xs |> f()`
+";
+    assert_eq!(expected, render_parse_error(source, false)?);
+    Ok(())
+}
+
+#[test]
+fn render_line_breaks() -> error::Result<()> {
+    let source = "let a = b\n  - 1\n";
+    let expected = "\
+error: Ambiguous line break before `-`
+ --> test.mk:2:3
+  |
+2 |   - 1
+  |   ^ this starts a new statement
+  = help: move `-` to the end of the line before to go on with it, or end that line with `;`
+";
+    assert_eq!(expected, render_parse_error(source, false)?);
+
+    let source = "if (a)\n{ 1 }";
+    let expected = "\
+error: Expected next token to be LBRACE, got NEWLINE instead
+ --> test.mk:1:7
+  |
+1 | if (a)
+  |       ^ expected LBRACE here
+  = help: a line break ends the statement here, so put the `{` on this line
+";
+    assert_eq!(expected, render_parse_error(source, false)?);
+
+    let source = "let a = 1 puts(a)";
+    let expected = "\
+error: Expected `;` or a line break after the statement, got IDENT instead
+ --> test.mk:1:11
+  |
+1 | let a = 1 puts(a)
+  |           ^^^^ a new statement cannot start here
+  = help: add `;` before it, or move it to a new line
+";
+    assert_eq!(expected, render_parse_error(source, false)?);
+    Ok(())
+}
+
+#[test]
+fn render_smallest_integer() -> error::Result<()> {
+    let source = "let n = -9223372036854775808;";
+    let expected = "\
+error: integer literal is too large
+ --> test.mk:1:10
+  |
+1 | let n = -9223372036854775808;
+  |          ^^^^^^^^^^^^^^^^^^^
+  = help: integer literals can be at most 9223372036854775807, write -9223372036854775808 as -9223372036854775807 - 1
+";
+    assert_eq!(expected, render_parse_error(source, false)?);
+    Ok(())
+}
+
+#[test]
+fn render_tab_indented_line() -> error::Result<()> {
+    let source = "if (x) {\n\tlet y = \t;\n}";
+    let expected = "\
+error: Cannot take prefix function for SEMICOLON found
+ --> test.mk:2:11
+  |
+2 |     let y =     ;
+  |                 ^ expected an expression here
+";
+    assert_eq!(expected, render_parse_error(source, false)?);
+    Ok(())
+}
+
+#[test]
+fn lex_error_labels_and_help() {
+    let cases = &[
+        (
+            "1 /* a",
+            "this comment is never closed",
+            Some("close it with `*/`"),
+        ),
+        (
+            "\"ab",
+            "this string is never closed",
+            Some("add the closing `\"`"),
+        ),
+        (".5", "", Some("write a `0` before the point")),
+        (r#""\q""#, "", Some("the escapes are")),
+        ("0b12", "", None),
+    ];
+    for (source, label, help) in cases {
+        let err = match Parser::new(Lexer::new(source)).parse_program() {
+            Err(err @ MonkeyErr::LexErr { .. }) => err,
+            other => panic!("expected a lexing error for {}, got {:?}", source, other),
+        };
+        let diag = Diagnostic::from(&err);
+        assert_eq!(
+            Some(label.to_string()),
+            diag.primary.map(|primary| primary.message),
+            "source: {}",
+            source
+        );
+        match (help, diag.help) {
+            (Some(expected), Some(got)) => assert!(got.starts_with(expected), "got: {}", got),
+            (None, None) => {}
+            (_, got) => panic!("unexpected help for {}: {:?}", source, got),
+        }
+    }
+}
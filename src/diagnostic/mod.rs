@@ -0,0 +1,362 @@
+#[cfg(test)]
+mod diagnostic_test;
+
+use std::fmt::Write;
+use std::io::{self, IsTerminal};
+
+use crate::error::{self, LexErrKind, MonkeyErr, MonkeyWarn};
+use crate::lexer::span::Span;
+use crate::lexer::token::Token;
+use unicode_width::UnicodeWidthChar;
+
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+// How many columns a tab is printed as
+const TAB_WIDTH: usize = 4;
+
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub primary: Option<Label>,
+    pub secondary: Vec<Label>,
+    pub help: Option<String>,
+}
+
+impl Label {
+    pub fn new(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<&MonkeyErr> for Diagnostic {
+    fn from(err: &MonkeyErr) -> Self {
+        let primary = err.span().map(|span| Label::new(span, primary_label(err)));
+        let secondary = match err {
+            MonkeyErr::UnclosedDelimErr {
+                open, open_span, ..
+            } => vec![Label::new(
+                *open_span,
+                format!("opening {} here", delimiter_name(open)),
+            )],
+            _ => Vec::new(),
+        };
+        Self {
+            severity: Severity::Error,
+            message: err.message(),
+            primary,
+            secondary,
+            help: help_note(err),
+        }
+    }
+}
+
+impl From<&MonkeyWarn> for Diagnostic {
+    fn from(warning: &MonkeyWarn) -> Self {
+        match warning {
+            MonkeyWarn::UnreachableArm { span, covered_by } => Self {
+                severity: Severity::Warning,
+                message: warning.message(),
+                primary: Some(Label::new(*span, "this arm is never reached")),
+                secondary: vec![Label::new(
+                    *covered_by,
+                    "this arm matches all of its values first",
+                )],
+                help: Some("move the arm up, or remove it".to_string()),
+            },
+        }
+    }
+}
+
+fn primary_label(err: &MonkeyErr) -> String {
+    match err {
+        MonkeyErr::ParseTokDiffErr { expected, .. } => {
+            format!("expected {} here", expected.take_tok_name())
+        }
+        MonkeyErr::UnclosedDelimErr { open, .. } => {
+            format!("expected {} here", open.closing_delimiter().take_tok_name())
+        }
+        MonkeyErr::LexErr { kind, .. } => match kind {
+            LexErrKind::UnterminatedBlockComment => "this comment is never closed".to_string(),
+            LexErrKind::UnterminatedString => "this string is never closed".to_string(),
+            _ => String::new(),
+        },
+        MonkeyErr::PrefixParseNoneErr { .. } => "expected an expression here".to_string(),
+        MonkeyErr::InfixParseNoneErr { .. } => "not an operator".to_string(),
+        MonkeyErr::AssignTargetErr { .. } => {
+            "only names and index expressions can be assigned".to_string()
+        }
+        MonkeyErr::LoopControlErr { .. } => "not inside a `while` or `for` loop".to_string(),
+        MonkeyErr::ParameterOrderErr { .. } => "this parameter has no default".to_string(),
+        MonkeyErr::ArgumentOrderErr { .. } => "this argument has no name".to_string(),
+        MonkeyErr::PipelineErr { .. } => "this is not a call".to_string(),
+        MonkeyErr::AmbiguousLineBreakErr { .. } => "this starts a new statement".to_string(),
+        MonkeyErr::StatementEndErr { .. } => "a new statement cannot start here".to_string(),
+        MonkeyErr::ParseExprErr { expected, .. } => format!("expected {} here", expected),
+        MonkeyErr::CannotConvertStringErr { .. } | MonkeyErr::CannotConvertSymbolErr { .. } => {
+            "expected an identifier here".to_string()
+        }
+        _ => String::new(),
+    }
+}
+
+fn help_note(err: &MonkeyErr) -> Option<String> {
+    match err {
+        MonkeyErr::UnclosedDelimErr { open, .. } => {
+            Some(format!("add the missing `{}`", open.closing_delimiter()))
+        }
+        MonkeyErr::PrefixParseNoneErr {
+            got: Token::EOF, ..
+        } => Some("the input ended in the middle of an expression".to_string()),
+        MonkeyErr::PrefixParseNoneErr {
+            got: Token::DOTDOT | Token::DOTDOTEQ,
+            ..
+        } => Some(
+            "only the index of a slice, like `arr[..2]`, may leave out the start of a range"
+                .to_string(),
+        ),
+        MonkeyErr::CannotConvertStringErr { .. } => {
+            Some("names of bindings and parameters must be identifiers".to_string())
+        }
+        MonkeyErr::ParameterOrderErr { .. } => Some(
+            "give it a default too, or move it before the parameters with defaults".to_string(),
+        ),
+        MonkeyErr::ArgumentOrderErr { .. } => {
+            Some("give the arguments by position first, like `f(1, y: 2)`".to_string())
+        }
+        MonkeyErr::PipelineErr { .. } => {
+            Some("the value goes in as the first argument of a call, like `xs |> f()`".to_string())
+        }
+        MonkeyErr::AmbiguousLineBreakErr { next, .. } => Some(format!(
+            "move `{}` to the end of the line before to go on with it, or end that line with `;`",
+            next
+        )),
+        MonkeyErr::StatementEndErr { .. } => {
+            Some("add `;` before it, or move it to a new line".to_string())
+        }
+        MonkeyErr::ParseTokDiffErr {
+            expected,
+            got: Token::NEWLINE,
+            ..
+        } => Some(format!(
+            "a line break ends the statement here, so put the `{}` on this line",
+            expected
+        )),
+        MonkeyErr::LexErr { kind, .. } => match kind {
+            LexErrKind::UnterminatedBlockComment => {
+                Some("close it with `*/`, block comments nest".to_string())
+            }
+            LexErrKind::UnterminatedString => Some("add the closing `\"`".to_string()),
+            // A literal has no sign, so i64::MIN can only be computed
+            LexErrKind::IntegerTooLarge => Some(format!(
+                "integer literals can be at most {}, write {} as {} - 1",
+                i64::MAX,
+                i64::MIN,
+                -i64::MAX
+            )),
+            LexErrKind::LeadingPoint => {
+                Some("write a `0` before the point, like `0.5`".to_string())
+            }
+            LexErrKind::UnknownEscape(_) => Some(
+                "the escapes are \\n \\t \\r \\0 \\\\ \\\" \\$ and \\u{...}, \
+                 or write a raw string r\"...\" instead"
+                    .to_string(),
+            ),
+            _ => None,
+        },
+        MonkeyErr::UndeclaredNameErr { assigned: true, .. } => {
+            Some("declare it first with `let`".to_string())
+        }
+        MonkeyErr::UndeclaredNameErr { .. } => Some("bind it first with `let`".to_string()),
+        MonkeyErr::UsageErr { .. } => Some("run `monkey --help` to see the usage".to_string()),
+        _ => None,
+    }
+}
+
+fn delimiter_name(tok: &Token) -> &'static str {
+    match tok {
+        Token::LPAREN => "paren",
+        Token::LBRACKET => "bracket",
+        Token::LBRACE => "brace",
+        _ => "delimiter",
+    }
+}
+
+// Writes the diagnostic of err to stderr. Colour is used only when stderr is
+// a terminal, so that redirected output stays plain text.
+pub fn emit(err: &MonkeyErr, file_name: &str, source: &str) -> error::Result<()> {
+    let color = io::stderr().is_terminal();
+    eprint!(
+        "{}",
+        render(&Diagnostic::from(err), file_name, source, color)?
+    );
+    Ok(())
+}
+
+pub fn emit_warning(warning: &MonkeyWarn, file_name: &str, source: &str) -> error::Result<()> {
+    let color = io::stderr().is_terminal();
+    eprint!(
+        "{}",
+        render(&Diagnostic::from(warning), file_name, source, color)?
+    );
+    Ok(())
+}
+
+pub fn render(
+    diag: &Diagnostic,
+    file_name: &str,
+    source: &str,
+    color: bool,
+) -> error::Result<String> {
+    let paint = |style: &str, text: &str| {
+        if color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    };
+    let (header, primary_style) = match diag.severity {
+        Severity::Error => ("error", RED),
+        Severity::Warning => ("warning", YELLOW),
+    };
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{}{}",
+        paint(primary_style, header),
+        paint(BOLD, &format!(": {}", diag.message))
+    )?;
+
+    let primary = match &diag.primary {
+        Some(primary) => primary,
+        None => {
+            writeln!(out, "  {} {}", paint(BLUE, "-->"), file_name)?;
+            write_help(&mut out, diag, &paint, 1)?;
+            return Ok(out);
+        }
+    };
+
+    // Every label is drawn below its own line, ordered by the position
+    let mut labels: Vec<(&Label, bool)> = vec![(primary, true)];
+    labels.extend(diag.secondary.iter().map(|label| (label, false)));
+    labels.sort_by_key(|(label, _)| (label.span.line, label.span.column));
+
+    let gutter = labels
+        .iter()
+        .map(|(label, _)| label.span.line)
+        .max()
+        .unwrap_or(1)
+        .to_string()
+        .len();
+    writeln!(
+        out,
+        "{:gutter$}{} {}:{}",
+        "",
+        paint(BLUE, "-->"),
+        file_name,
+        primary.span,
+        gutter = gutter
+    )?;
+    writeln!(out, "{:gutter$} {}", "", paint(BLUE, "|"), gutter = gutter)?;
+
+    let lines: Vec<&str> = source.lines().collect();
+    let mut last_line = None;
+    for (label, is_primary) in labels {
+        let line_no = label.span.line;
+        let line = lines.get(line_no.wrapping_sub(1)).copied().unwrap_or("");
+        if last_line != Some(line_no) {
+            let numbered_line = format!(
+                "{} {} {}",
+                paint(BLUE, &format!("{:>gutter$}", line_no, gutter = gutter)),
+                paint(BLUE, "|"),
+                line.replace('\t', &" ".repeat(TAB_WIDTH))
+            );
+            writeln!(out, "{}", numbered_line.trim_end())?;
+            last_line = Some(line_no);
+        }
+        let (marker, style) = if is_primary {
+            ('^', primary_style)
+        } else {
+            ('-', BLUE)
+        };
+        let underline = marker
+            .to_string()
+            .repeat(underline_width(&label.span, source));
+        writeln!(
+            out,
+            "{:gutter$} {} {:pad$}{}",
+            "",
+            paint(BLUE, "|"),
+            "",
+            paint(style, format!("{} {}", underline, label.message).trim_end()),
+            gutter = gutter,
+            pad = display_column(&label.span, line)
+        )?;
+    }
+    write_help(&mut out, diag, &paint, gutter)?;
+    Ok(out)
+}
+
+fn write_help(
+    out: &mut String,
+    diag: &Diagnostic,
+    paint: &dyn Fn(&str, &str) -> String,
+    gutter: usize,
+) -> error::Result<()> {
+    if let Some(help) = &diag.help {
+        writeln!(
+            out,
+            "{:gutter$} {} {}: {}",
+            "",
+            paint(BLUE, "="),
+            paint(BOLD, "help"),
+            help,
+            gutter = gutter
+        )?;
+    }
+    Ok(())
+}
+
+// Columns count chars, but wide chars like CJK take two cells on the
+// terminal, so the markers are placed by the display width instead
+fn display_column(span: &Span, line: &str) -> usize {
+    line.chars()
+        .take(span.column.saturating_sub(1))
+        .map(char_width)
+        .sum()
+}
+
+// The underline never goes past the end of the line it is drawn below
+fn underline_width(span: &Span, source: &str) -> usize {
+    source
+        .get(span.start..span.end)
+        .and_then(|text| text.split('\n').next())
+        .map_or(0, |text| text.chars().map(char_width).sum())
+        .max(1)
+}
+
+// Tabs are printed as TAB_WIDTH spaces
+fn char_width(ch: char) -> usize {
+    match ch {
+        '\t' => TAB_WIDTH,
+        _ => ch.width().unwrap_or(0),
+    }
+}
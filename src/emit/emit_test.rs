@@ -0,0 +1,62 @@
+use super::*;
+use crate::parser::Parser;
+
+fn parse(input: &str) -> error::Result<Program> {
+    Parser::new(Lexer::new(input)).parse_program()
+}
+
+#[test]
+fn emit_tokens() -> error::Result<()> {
+    let expected = "\
+1:1\t0..3\tLET\tlet
+1:5\t4..5\tIDENT\tx
+1:7\t6..7\tASSIGN\t=
+2:3\t10..15\tSTRING\t\"a b\"
+2:8\t15..16\tSEMICOLON\t;
+2:9\t16..16\tEOF\tEOF
+";
+    assert_eq!(expected, tokens("let x =\n  \"a b\";")?);
+    Ok(())
+}
+
+#[test]
+fn emit_ast_tree() -> error::Result<()> {
+    let input = "let f = fn(a, b = 1, ..c) { a[0] };\nif (!x) { f(1, b: 2) }";
+    let expected = r#"Program
+  Let 1:1 name="f"
+    value: Function 1:9 parameters=[a, b] rest="c" shorthand=false
+      defaults:
+        Integer 1:19 value=1
+      body:
+        Expression 1:29
+          expression: Index 1:29
+            left: Ident 1:29 name="a"
+            index: Integer 1:31 value=0
+  Expression 2:1
+    expression: If 2:1
+      condition: Prefix 2:5 operator="!"
+        right: Ident 2:6 name="x"
+      consequence:
+        Expression 2:11
+          expression: Call 2:11 keywords=[b] piped=false
+            function: Ident 2:11 name="f"
+            arguments:
+              Integer 2:13 value=1
+              Integer 2:19 value=2
+"#;
+    assert_eq!(expected, ast_tree(&parse(input)?)?);
+    Ok(())
+}
+
+#[test]
+fn emit_json_ast() -> error::Result<()> {
+    let expected = concat!(
+        r#"{"type":"Program","statements":[{"type":"Return","span":{"start":0,"end":21,"line":1,"column":1},"#,
+        r#""value":{"type":"Hash","span":{"start":7,"end":20,"line":1,"column":8},"keys":["#,
+        r#"{"type":"String","span":{"start":8,"end":13,"line":1,"column":9},"value":"a\tb"}],"#,
+        r#""values":[{"type":"Boolean","span":{"start":15,"end":19,"line":1,"column":16},"value":true}]}}]}"#,
+        "\n"
+    );
+    assert_eq!(expected, json_ast(&parse("return {\"a\tb\": true};")?)?);
+    Ok(())
+}
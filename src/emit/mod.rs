@@ -0,0 +1,384 @@
+#[cfg(test)]
+mod emit_test;
+
+use std::fmt::Write;
+
+use crate::error;
+use crate::lexer::span::Span;
+use crate::lexer::token::{self, Token};
+use crate::lexer::Lexer;
+use crate::parser::ast::{
+    ExprKind, Expression, MatchArm, Pattern, PatternKind, Program, Statement, StmtKind, StringPart,
+};
+
+// One token per line: position, byte range, kind and source text
+pub fn tokens(input: &str) -> error::Result<String> {
+    let mut out = String::new();
+    for tok in Lexer::new(input) {
+        let span = tok.span;
+        writeln!(
+            out,
+            "{}\t{}..{}\t{}\t{}",
+            span,
+            span.start,
+            span.end,
+            tok.kind.take_tok_name(),
+            tok.kind
+        )?;
+    }
+    Ok(out)
+}
+
+pub fn ast_tree(program: &Program) -> error::Result<String> {
+    let mut out = String::from("Program\n");
+    for stmt in program.get_stmts() {
+        write_tree(&mut out, &stmt_node(stmt), None, 1)?;
+    }
+    Ok(out)
+}
+
+// Keys are always written in the same order, so that the output is stable
+pub fn json_ast(program: &Program) -> error::Result<String> {
+    let mut out = String::from(r#"{"type":"Program","statements":["#);
+    for (i, stmt) in program.get_stmts().iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write_json(&mut out, &stmt_node(stmt))?;
+    }
+    out.push_str("]}\n");
+    Ok(out)
+}
+
+// Both outputs are written from this generic form of the ast
+struct Node {
+    kind: &'static str,
+    // The literal parts of an interpolated string have no span of their own
+    span: Option<Span>,
+    fields: Vec<(&'static str, Field)>,
+}
+
+enum Field {
+    Text(String),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Names(Vec<String>),
+    Node(Node),
+    List(Vec<Node>),
+    Nothing,
+}
+
+impl Node {
+    fn new(kind: &'static str, span: Span) -> Self {
+        Self {
+            kind,
+            span: Some(span),
+            fields: Vec::new(),
+        }
+    }
+
+    fn with(mut self, name: &'static str, field: Field) -> Self {
+        self.fields.push((name, field));
+        self
+    }
+}
+
+fn expr_field(expr: &Expression) -> Field {
+    Field::Node(expr_node(expr))
+}
+
+fn exprs_field(exprs: &[Expression]) -> Field {
+    Field::List(exprs.iter().map(expr_node).collect())
+}
+
+fn block_field(block: &[Statement]) -> Field {
+    Field::List(block.iter().map(stmt_node).collect())
+}
+
+fn stmt_node(stmt: &Statement) -> Node {
+    let node = |kind| Node::new(kind, stmt.span);
+    match &stmt.kind {
+        StmtKind::LetStmt { name, value } => node("Let")
+            .with("name", Field::Text(name.clone()))
+            .with("value", expr_field(value)),
+        StmtKind::LetPatternStmt { pattern, value } => node("LetPattern")
+            .with("pattern", Field::Node(pattern_node(pattern)))
+            .with("value", expr_field(value)),
+        StmtKind::ReturnStmt { value } => node("Return").with("value", expr_field(value)),
+        StmtKind::ExpressionStmt { expression } => {
+            node("Expression").with("expression", expr_field(expression))
+        }
+        StmtKind::WhileStmt { condition, body } => node("While")
+            .with("condition", expr_field(condition))
+            .with("body", block_field(body)),
+        StmtKind::ForStmt {
+            variable,
+            iterable,
+            body,
+        } => node("For")
+            .with("variable", Field::Text(variable.clone()))
+            .with("iterable", expr_field(iterable))
+            .with("body", block_field(body)),
+        StmtKind::BreakStmt => node("Break"),
+        StmtKind::ContinueStmt => node("Continue"),
+    }
+}
+
+fn expr_node(expr: &Expression) -> Node {
+    let node = |kind| Node::new(kind, expr.span);
+    match &expr.kind {
+        ExprKind::Ident(name) => node("Ident").with("name", Field::Text(name.clone())),
+        ExprKind::String(s) => node("String").with("value", Field::Text(s.clone())),
+        ExprKind::Interpolated(parts) => node("Interpolated").with(
+            "parts",
+            Field::List(
+                parts
+                    .iter()
+                    .map(|part| match part {
+                        StringPart::Literal(s) => Node {
+                            kind: "Literal",
+                            span: None,
+                            fields: vec![("value", Field::Text(s.clone()))],
+                        },
+                        StringPart::Expr(expr) => expr_node(expr),
+                    })
+                    .collect(),
+            ),
+        ),
+        ExprKind::Boolean(b) => node("Boolean").with("value", Field::Bool(*b)),
+        ExprKind::Integer(n) => node("Integer").with("value", Field::Int(*n)),
+        ExprKind::Float(n) => node("Float").with("value", Field::Float(*n)),
+        ExprKind::Array(elements) => node("Array").with("elements", exprs_field(elements)),
+        ExprKind::Imaginary(im) => node("Imaginary").with("value", Field::Float(*im)),
+        ExprKind::Hash { key, value } => node("Hash")
+            .with("keys", exprs_field(key))
+            .with("values", exprs_field(value)),
+        ExprKind::Prefix { operator, right } => node("Prefix")
+            .with("operator", Field::Text(operator.to_string()))
+            .with("right", expr_field(right)),
+        ExprKind::Infix {
+            left,
+            operator: Token::LBRACKET,
+            right,
+        } => node("Index")
+            .with("left", expr_field(left))
+            .with("index", expr_field(right)),
+        ExprKind::Infix {
+            left,
+            operator,
+            right,
+        } => node("Infix")
+            .with("operator", Field::Text(operator.to_string()))
+            .with("left", expr_field(left))
+            .with("right", expr_field(right)),
+        ExprKind::Range {
+            start,
+            end,
+            inclusive,
+        } => node("Range")
+            .with("inclusive", Field::Bool(*inclusive))
+            .with(
+                "start",
+                start
+                    .as_ref()
+                    .map_or(Field::Nothing, |start| expr_field(start)),
+            )
+            .with(
+                "end",
+                end.as_ref().map_or(Field::Nothing, |end| expr_field(end)),
+            ),
+        ExprKind::Assign {
+            target,
+            operator,
+            value,
+        } => node("Assign")
+            .with("operator", Field::Text(operator.to_string()))
+            .with("target", expr_field(target))
+            .with("value", expr_field(value)),
+        ExprKind::IfExpr {
+            condition,
+            consequence,
+            alternative,
+        } => node("If")
+            .with("condition", expr_field(condition))
+            .with("consequence", block_field(consequence))
+            .with(
+                "alternative",
+                alternative
+                    .as_ref()
+                    .map_or(Field::Nothing, |block| block_field(block)),
+            ),
+        ExprKind::Function {
+            parameters,
+            body,
+            shorthand,
+        } => node("Function")
+            .with("parameters", Field::Names(parameters.names.clone()))
+            .with("defaults", exprs_field(&parameters.defaults))
+            .with(
+                "rest",
+                parameters.rest.clone().map_or(Field::Nothing, Field::Text),
+            )
+            .with("shorthand", Field::Bool(*shorthand))
+            .with("body", block_field(body)),
+        ExprKind::Call {
+            function,
+            arguments,
+            keywords,
+            piped,
+        } => node("Call")
+            .with("keywords", Field::Names(keywords.clone()))
+            .with("piped", Field::Bool(*piped))
+            .with("function", expr_field(function))
+            .with("arguments", exprs_field(arguments)),
+        ExprKind::Match { subject, arms } => node("Match")
+            .with("subject", expr_field(subject))
+            .with("arms", Field::List(arms.iter().map(arm_node).collect())),
+    }
+}
+
+fn arm_node(arm: &MatchArm) -> Node {
+    Node::new("Arm", arm.pattern.span.to(arm.body.span))
+        .with("pattern", Field::Node(pattern_node(&arm.pattern)))
+        .with(
+            "guard",
+            arm.guard.as_ref().map_or(Field::Nothing, expr_field),
+        )
+        .with("body", expr_field(&arm.body))
+}
+
+fn pattern_node(pattern: &Pattern) -> Node {
+    let node = |kind| Node::new(kind, pattern.span);
+    match &pattern.kind {
+        PatternKind::Wildcard => node("WildcardPattern"),
+        PatternKind::Binding(name) => {
+            node("BindingPattern").with("name", Field::Text(name.clone()))
+        }
+        PatternKind::Literal(literal) => node("LiteralPattern").with("value", expr_field(literal)),
+        PatternKind::Array { elements, rest } => node("ArrayPattern")
+            .with("rest", Field::Bool(rest.is_some()))
+            .with(
+                "rest_name",
+                rest.clone().flatten().map_or(Field::Nothing, Field::Text),
+            )
+            .with(
+                "elements",
+                Field::List(elements.iter().map(pattern_node).collect()),
+            ),
+        PatternKind::Hash { keys, values } => {
+            node("HashPattern").with("keys", exprs_field(keys)).with(
+                "values",
+                Field::List(values.iter().map(pattern_node).collect()),
+            )
+        }
+    }
+}
+
+// Scalar fields stay on the line of their node, nodes go below it
+fn write_tree(
+    out: &mut String,
+    node: &Node,
+    label: Option<&str>,
+    depth: usize,
+) -> error::Result<()> {
+    let indent = "  ".repeat(depth);
+    write!(out, "{}", indent)?;
+    if let Some(label) = label {
+        write!(out, "{}: ", label)?;
+    }
+    write!(out, "{}", node.kind)?;
+    if let Some(span) = node.span {
+        write!(out, " {}", span)?;
+    }
+    for (name, field) in &node.fields {
+        match field {
+            Field::Text(s) => write!(out, " {}={:?}", name, s)?,
+            Field::Int(n) => write!(out, " {}={}", name, n)?,
+            Field::Float(n) => write!(out, " {}={}", name, token::format_float(*n))?,
+            Field::Bool(b) => write!(out, " {}={}", name, b)?,
+            Field::Names(names) => write!(out, " {}=[{}]", name, names.join(", "))?,
+            _ => {}
+        }
+    }
+    writeln!(out)?;
+
+    for (name, field) in &node.fields {
+        match field {
+            Field::Node(child) => write_tree(out, child, Some(name), depth + 1)?,
+            Field::List(children) if children.is_empty() => {
+                writeln!(out, "{}  {}: []", indent, name)?
+            }
+            Field::List(children) => {
+                writeln!(out, "{}  {}:", indent, name)?;
+                for child in children {
+                    write_tree(out, child, None, depth + 2)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+fn write_json(out: &mut String, node: &Node) -> error::Result<()> {
+    write!(out, r#"{{"type":"{}","span":"#, node.kind)?;
+    match node.span {
+        Some(span) => write!(
+            out,
+            r#"{{"start":{},"end":{},"line":{},"column":{}}}"#,
+            span.start, span.end, span.line, span.column
+        )?,
+        None => out.push_str("null"),
+    }
+    for (name, field) in &node.fields {
+        write!(out, r#","{}":"#, name)?;
+        match field {
+            Field::Text(s) => write_json_string(out, s)?,
+            Field::Int(n) => write!(out, "{}", n)?,
+            Field::Float(n) => write!(out, "{}", token::format_float(*n))?,
+            Field::Bool(b) => write!(out, "{}", b)?,
+            Field::Names(names) => {
+                out.push('[');
+                for (i, name) in names.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    write_json_string(out, name)?;
+                }
+                out.push(']');
+            }
+            Field::Node(child) => write_json(out, child)?,
+            Field::List(children) => {
+                out.push('[');
+                for (i, child) in children.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    write_json(out, child)?;
+                }
+                out.push(']');
+            }
+            Field::Nothing => out.push_str("null"),
+        }
+    }
+    out.push('}');
+    Ok(())
+}
+
+fn write_json_string(out: &mut String, s: &str) -> error::Result<()> {
+    out.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            _ if ch.is_control() => write!(out, "\\u{:04x}", ch as u32)?,
+            _ => out.push(ch),
+        }
+    }
+    out.push('"');
+    Ok(())
+}
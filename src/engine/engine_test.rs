@@ -0,0 +1,175 @@
+use super::*;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use std::thread;
+
+// Runs input on both engines, which must give the same value
+fn run_both(input: &str) -> error::Result<Object> {
+    let program = Parser::new(Lexer::new(input)).parse_program()?;
+    let evaluated = Engine::new_eval().run(&program, false)?;
+    let executed = Engine::new_vm().run(&program, false)?;
+    assert_eq!(evaluated, executed, "input: {}", input);
+    Ok(evaluated)
+}
+
+#[test]
+fn closures_share_captured_variables() -> error::Result<()> {
+    let cases = &[
+        (
+            "let make = fn() { let n = 0; fn() { n += 1; n } }; let c = make(); c(); c(); c()",
+            3,
+        ),
+        ("let x = 0; let f = fn() { x }; x = 3; f()", 3),
+        ("fn() { let x = 0; let f = fn() { x }; x = 3; f() }()", 3),
+        (
+            "fn() { let n = 0; let inc = fn() { fn() { n += 1 } }; inc()(); inc()(); n }()",
+            2,
+        ),
+        (
+            "let p = fn() { let n = 0; [fn() { n += 1 }, fn() { n }] }(); p[0](); p[0](); p[1]()",
+            2,
+        ),
+        (
+            "let make = fn() { let n = 0; fn() { n += 1 } }; let a = make(); let b = make(); a(); a(); b()",
+            1,
+        ),
+        (
+            "fn() { let fs = []; for (i in 0..3) { fs = push(fs, fn() { i }) } fs[0]() }()",
+            2,
+        ),
+        (
+            "let make = fn() { let n = 0; fn() { n } }; let a = make(); let g = fn() { let m = 5; m }; g(); a()",
+            0,
+        ),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(
+            Object::Integer(*expected),
+            run_both(input)?,
+            "input: {}",
+            input
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn match_arms_bind_their_own_variables() -> error::Result<()> {
+    let cases = &[
+        ("let x = 10; match (5) { x if x > 100 => 1, _ => 2 }; x", 10),
+        ("fn() { let x = 10; match (5) { x if x > 100 => 1, _ => 2 }; x }()", 10),
+        ("let x = 10; match (5) { x => x } + x", 15),
+        ("let t = 0; match (3) { n => t += n }; t", 3),
+        ("fn() { let y = 1; match (2) { n => fn() { y + n } }() }()", 3),
+        (
+            "fn() { let fs = []; for (i in 0..3) { fs = push(fs, match (i) { n => fn() { n } }) } fs[0]() }()",
+            0,
+        ),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(
+            Object::Integer(*expected),
+            run_both(input)?,
+            "input: {}",
+            input
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn errors_know_where_they_happened() -> error::Result<()> {
+    let cases = &[
+        ("let a = 1;\nlet f = fn(x) {\n  x + true\n};\nf(a)", (3, 3)),
+        ("let xs = [1];\nlen(xs, xs)", (2, 1)),
+        ("let x = 1;\nx + y", (2, 5)),
+    ];
+    for (input, (line, column)) in cases {
+        let program = Parser::new(Lexer::new(input)).parse_program()?;
+        for mut engine in [Engine::new_eval(), Engine::new_vm()] {
+            match engine.run(&program, false) {
+                Err(err) => {
+                    let span = err.span().expect("the error has a span");
+                    assert_eq!(
+                        (*line, *column),
+                        (span.line, span.column),
+                        "input: {}",
+                        input
+                    );
+                }
+                Ok(value) => panic!("expected an error for {}, got {}", input, value),
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn deep_recursion_exceeds_the_call_depth() {
+    let cases = [
+        "let f = fn(n) { 1 + f(n + 1) };\nf(0)",
+        "let f = fn(n) {\n  let a = n; let b = a; f(b + 1)\n};\nf(0)",
+    ];
+    for input in cases {
+        // The evaluator recurses on the native stack, which needs room for it
+        let reports = thread::Builder::new()
+            .stack_size(256 << 20)
+            .spawn(move || {
+                let program = Parser::new(Lexer::new(input)).parse_program().unwrap();
+                [Engine::new_eval(), Engine::new_vm()].map(|mut engine| {
+                    let err = engine.run(&program, false).unwrap_err();
+                    let span = err.span().expect("the error has a span");
+                    (err.message(), span.line, span.column)
+                })
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        let [evaluated, executed] = reports;
+        assert_eq!(
+            "Eval error: maximum call depth exceeded", evaluated.0,
+            "input: {}",
+            input
+        );
+        assert_eq!(evaluated, executed, "input: {}", input);
+    }
+}
+
+#[test]
+fn operands_run_from_left_to_right() -> error::Result<()> {
+    let cases = &[
+        "let i = 0; [(i += 1) <= (i *= 10), i]",
+        "let i = 1; [(i *= 10) < (i += 1), i]",
+        "let i = 0; [(i += 1) >= (i *= 10), i]",
+        "let s = \"\"; let f = fn(x) { s = s + x; 0 }; f(\"a\") < f(\"b\"); s",
+    ];
+    for input in cases {
+        run_both(input)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn undeclared_names() -> error::Result<()> {
+    let cases = &[
+        ("foobar", "foobar", false),
+        ("let x = 1; x + y", "y", false),
+        ("y = 1", "y", true),
+        ("let f = fn() { z += 1 }; f()", "z", true),
+    ];
+    for (input, expected_name, expected_assigned) in cases {
+        let program = Parser::new(Lexer::new(input)).parse_program()?;
+        for mut engine in [Engine::new_eval(), Engine::new_vm()] {
+            match engine.run(&program, false) {
+                Err(error::MonkeyErr::UndeclaredNameErr { name, assigned, .. }) => assert_eq!(
+                    (*expected_name, *expected_assigned),
+                    (name.as_str(), assigned),
+                    "input: {}",
+                    input
+                ),
+                other => panic!("expected an undeclared name for {}, got {:?}", input, other),
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,87 @@
+#[cfg(test)]
+mod engine_test;
+
+use std::mem;
+
+use crate::code;
+use crate::compiler::symbol_table::SymbolTable;
+use crate::compiler::Compiler;
+use crate::error;
+use crate::eval;
+use crate::object::environment::{Env, Environment};
+use crate::object::Object;
+use crate::parser::ast::Program;
+use crate::vm::{self, VM};
+
+// Both engines keep their state so that later programs can use earlier bindings
+pub enum Engine {
+    Eval(Env),
+    Vm {
+        symbol_table: SymbolTable,
+        constants: Vec<Object>,
+        globals: Vec<Object>,
+    },
+}
+
+impl Engine {
+    pub fn new_eval() -> Self {
+        Engine::Eval(Environment::new())
+    }
+
+    pub fn new_vm() -> Self {
+        let (symbol_table, constants) = Compiler::new().into_state();
+        Engine::Vm {
+            symbol_table,
+            constants,
+            globals: vec![Object::Null; vm::GLOBALS_SIZE],
+        }
+    }
+
+    // Binds a global name before any program is run
+    pub fn define(&mut self, name: &str, value: Object) {
+        match self {
+            Engine::Eval(env) => env.borrow_mut().set(name, value),
+            Engine::Vm {
+                symbol_table,
+                globals,
+                ..
+            } => {
+                let symbol = symbol_table.define(name);
+                globals[symbol.index] = value;
+            }
+        }
+    }
+
+    pub fn run(&mut self, program: &Program, show_bytecode: bool) -> error::Result<Object> {
+        match self {
+            Engine::Eval(env) => eval::eval_program(program, env),
+            Engine::Vm {
+                symbol_table,
+                constants,
+                globals,
+            } => {
+                let mut compiler =
+                    Compiler::new_with_state(mem::take(symbol_table), mem::take(constants));
+                let compiled = compiler.compile(program);
+                let bytecode = compiler.bytecode();
+                let (table, consts) = compiler.into_state();
+                *symbol_table = table;
+                *constants = consts;
+                compiled?;
+                if show_bytecode {
+                    print!("{}", code::disassemble(&bytecode.instructions)?);
+                }
+
+                let mut machine = VM::new_with_globals(bytecode, mem::take(globals));
+                let result = machine.run().map(|_| {
+                    machine
+                        .last_popped_stack_elem()
+                        .cloned()
+                        .unwrap_or(Object::Null)
+                });
+                *globals = machine.into_globals();
+                result
+            }
+        }
+    }
+}
@@ -15,6 +15,18 @@ macro_rules! impl_partialeq {
 
 #[macro_export]
 macro_rules! handle_error {
+    ($handle: expr, $file_name: expr, $source: expr => $result: stmt) => {{
+        if let Err(error) = $handle {
+            if error.is_critical_err() {
+                panic!("{}", error);
+            } else if let Err(render_error) = $crate::diagnostic::emit(&error, $file_name, $source)
+            {
+                panic!("{}", render_error);
+            }
+        } else {
+            $result
+        }
+    }};
     ($handle: expr => $result: stmt) => {{
         if let Err(error) = $handle {
             if error.is_critical_err() {
@@ -3,93 +3,259 @@ mod macros;
 
 use std::fmt::{self, Debug, Display};
 
+use crate::lexer::span::Span;
 use crate::lexer::token::Token;
 
-#[allow(clippy::upper_case_acronyms)]
+#[allow(clippy::upper_case_acronyms, clippy::enum_variant_names)]
 pub enum MonkeyErr {
     // These two errors are critical errors so that the program panics
     IOErr(std::io::Error),
     FmtErr(fmt::Error),
     // These errors are pure monkey lang errors
-    CannotConvertStringErr { got: Token },
-    CannotConvertSymbolErr { got: Token },
-    PrefixParseNoneErr { got: Token },
-    InfixParseNoneErr { got: Token },
-    ParseExprErr { expected: String, got: Token },
-    ParseTokDiffErr { expected: Token, got: Token },
-    EvalErr { msg: String },
+    LexErr {
+        kind: LexErrKind,
+        span: Span,
+    },
+    CannotConvertStringErr {
+        got: Token,
+        span: Span,
+    },
+    CannotConvertSymbolErr {
+        got: Token,
+        span: Span,
+    },
+    PrefixParseNoneErr {
+        got: Token,
+        span: Span,
+    },
+    InfixParseNoneErr {
+        got: Token,
+        span: Span,
+    },
+    ParseExprErr {
+        expected: String,
+        got: Token,
+        span: Span,
+    },
+    ParseTokDiffErr {
+        expected: Token,
+        got: Token,
+        span: Span,
+    },
+    UnclosedDelimErr {
+        open: Token,
+        open_span: Span,
+        got: Token,
+        span: Span,
+    },
+    AssignTargetErr {
+        span: Span,
+    },
+    LoopControlErr {
+        keyword: Token,
+        span: Span,
+    },
+    ParameterOrderErr {
+        name: String,
+        span: Span,
+    },
+    ArgumentOrderErr {
+        span: Span,
+    },
+    PipelineErr {
+        span: Span,
+    },
+    // A line which starts with next could go on with the line before it
+    AmbiguousLineBreakErr {
+        next: Token,
+        span: Span,
+    },
+    // Something other than `;` or a line break follows a statement
+    StatementEndErr {
+        got: Token,
+        span: Span,
+    },
+    // Runtime errors know where they happened once the node which failed
+    // has passed them on
+    EvalErr {
+        msg: String,
+        span: Option<Span>,
+    },
+    // A name which is bound nowhere, found by either engine. assigned tells
+    // an assignment from a read.
+    UndeclaredNameErr {
+        name: String,
+        assigned: bool,
+        span: Option<Span>,
+    },
     IlligalOpcodeFoundErr,
-    FmtOperandsInputLenErr { expected: usize, got: usize },
-    UnhandledOperandCountErr { name: String },
-    CompileErr { msg: String },
+    FmtOperandsInputLenErr {
+        expected: usize,
+        got: usize,
+    },
+    UnhandledOperandCountErr {
+        name: String,
+    },
+    CompileErr {
+        msg: String,
+        span: Option<Span>,
+    },
+    UsageErr {
+        msg: String,
+    },
 }
 
 impl MonkeyErr {
     pub fn is_critical_err(&self) -> bool {
         matches!(self, Self::IOErr(_) | Self::FmtErr(_))
     }
+
+    // Where in the source the error happened, if it is known
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::LexErr { span, .. }
+            | Self::CannotConvertStringErr { span, .. }
+            | Self::CannotConvertSymbolErr { span, .. }
+            | Self::PrefixParseNoneErr { span, .. }
+            | Self::InfixParseNoneErr { span, .. }
+            | Self::ParseExprErr { span, .. }
+            | Self::ParseTokDiffErr { span, .. }
+            | Self::UnclosedDelimErr { span, .. }
+            | Self::AssignTargetErr { span }
+            | Self::LoopControlErr { span, .. }
+            | Self::ParameterOrderErr { span, .. }
+            | Self::ArgumentOrderErr { span }
+            | Self::PipelineErr { span }
+            | Self::AmbiguousLineBreakErr { span, .. }
+            | Self::StatementEndErr { span, .. } => Some(*span),
+            Self::EvalErr { span, .. }
+            | Self::CompileErr { span, .. }
+            | Self::UndeclaredNameErr { span, .. } => *span,
+            _ => None,
+        }
+    }
+
+    // Runtime and compile errors are made without a span. The evaluator and
+    // the compiler pass each one up through the nodes they are in, and the
+    // innermost of them gives it its span. The vm gives it the span of the
+    // node which the failing instruction was compiled from.
+    pub fn at(mut self, at: Span) -> Self {
+        if let Self::EvalErr { span, .. }
+        | Self::CompileErr { span, .. }
+        | Self::UndeclaredNameErr { span, .. } = &mut self
+        {
+            span.get_or_insert(at);
+        }
+        self
+    }
+
+    // The error message without its location
+    pub fn message(&self) -> String {
+        match self {
+            Self::IOErr(ref e) => e.to_string(),
+            Self::FmtErr(ref e) => e.to_string(),
+            Self::LexErr { kind, .. } => kind.to_string(),
+            Self::CannotConvertStringErr { got, .. } => {
+                format!("Cannot take string from {}", got.take_tok_name())
+            }
+            Self::CannotConvertSymbolErr { got, .. } => {
+                format!("Cannot convery symbol from {}", got.take_tok_name())
+            }
+            Self::PrefixParseNoneErr { got, .. } => format!(
+                "Cannot take prefix function for {} found",
+                got.take_tok_name()
+            ),
+            Self::InfixParseNoneErr { got, .. } => format!(
+                "Cannot take infix function for {} found",
+                got.take_tok_name()
+            ),
+            Self::ParseExprErr { expected, got, .. } => {
+                format!("Cannot parse {0} with {1}", expected, got.take_tok_name())
+            }
+            Self::ParseTokDiffErr { expected, got, .. } => format!(
+                "Expected next token to be {0}, got {1} instead",
+                expected.take_tok_name(),
+                got.take_tok_name()
+            ),
+            Self::UnclosedDelimErr { open, got, .. } => format!(
+                "Expected next token to be {0} to close {1}, got {2} instead",
+                open.closing_delimiter().take_tok_name(),
+                open.take_tok_name(),
+                got.take_tok_name()
+            ),
+            Self::AssignTargetErr { .. } => "Cannot assign to this expression".to_string(),
+            Self::LoopControlErr { keyword, .. } => {
+                format!("Cannot use {} outside of a loop", keyword)
+            }
+            Self::ParameterOrderErr { name, .. } => format!(
+                "Parameter {} needs a default, as it follows a parameter with one",
+                name
+            ),
+            Self::ArgumentOrderErr { .. } => {
+                "Cannot give an argument by position after one by name".to_string()
+            }
+            Self::PipelineErr { .. } => "Expected a call after |>".to_string(),
+            Self::AmbiguousLineBreakErr { next, .. } => {
+                format!("Ambiguous line break before `{}`", next)
+            }
+            Self::StatementEndErr { got, .. } => format!(
+                "Expected `;` or a line break after the statement, got {} instead",
+                got.take_tok_name()
+            ),
+            Self::EvalErr { msg, .. } => format!("Eval error: {}", msg),
+            Self::UndeclaredNameErr {
+                name,
+                assigned: false,
+                ..
+            } => format!("identifier not found: {}", name),
+            Self::UndeclaredNameErr { name, .. } => {
+                format!("cannot assign to undeclared variable {}", name)
+            }
+            Self::IlligalOpcodeFoundErr => "Illigal Opcode found".to_string(),
+            Self::FmtOperandsInputLenErr { expected, got } => {
+                format!("operand len {} does not match defined {}", expected, got)
+            }
+            Self::UnhandledOperandCountErr { name } => {
+                format!("unhandled operator count for {}", name)
+            }
+            Self::CompileErr { msg, .. } => format!("Compile error: {}", msg),
+            Self::UsageErr { msg } => msg.clone(),
+        }
+    }
 }
 
 impl_partialeq!(
     MonkeyErr: IOErr(_),
     FmtErr(_),
+    LexErr { .. },
     CannotConvertStringErr { .. },
     CannotConvertSymbolErr { .. },
     PrefixParseNoneErr { .. },
     InfixParseNoneErr { .. },
     ParseExprErr { .. },
     ParseTokDiffErr { .. },
+    UnclosedDelimErr { .. },
+    AssignTargetErr { .. },
+    LoopControlErr { .. },
+    ParameterOrderErr { .. },
+    ArgumentOrderErr { .. },
+    PipelineErr { .. },
+    AmbiguousLineBreakErr { .. },
+    StatementEndErr { .. },
     EvalErr { .. },
+    UndeclaredNameErr { .. },
     IlligalOpcodeFoundErr,
     FmtOperandsInputLenErr { .. },
     UnhandledOperandCountErr { .. },
-    CompileErr { .. }
+    CompileErr { .. },
+    UsageErr { .. }
 );
 
 impl Display for MonkeyErr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Self::IOErr(ref e) => Display::fmt(e, f),
-            Self::FmtErr(ref e) => Display::fmt(e, f),
-            Self::CannotConvertStringErr { got } => {
-                write!(f, "Cannot take string from {}", got.take_tok_name())
-            }
-            Self::CannotConvertSymbolErr { got } => {
-                write!(f, "Cannot convery symbol from {}", got.take_tok_name())
-            }
-            Self::PrefixParseNoneErr { got } => write!(
-                f,
-                "Cannot take prefix function for {} found",
-                got.take_tok_name()
-            ),
-            Self::InfixParseNoneErr { got } => write!(
-                f,
-                "Cannot take infix function for {} found",
-                got.take_tok_name()
-            ),
-            Self::ParseExprErr { expected, got } => write!(
-                f,
-                "Cannot parse {0} with {1}",
-                expected,
-                got.take_tok_name()
-            ),
-            Self::ParseTokDiffErr { expected, got } => write!(
-                f,
-                "Expected next token to be {0}, got {1} instead",
-                expected.take_tok_name(),
-                got.take_tok_name()
-            ),
-            Self::EvalErr { msg } => write!(f, "Eval error: {}", msg),
-            Self::IlligalOpcodeFoundErr => write!(f, "Illigal Opcode found"),
-            Self::FmtOperandsInputLenErr { expected, got } => write!(
-                f,
-                "operand len {} does not match defined {}\n",
-                expected, got
-            ),
-            Self::UnhandledOperandCountErr { name } => {
-                write!(f, "unhandled operator count for {}\n", name)
-            }
-            Self::CompileErr { msg } => write!(f, "Compile error: {}", msg),
+        match self.span() {
+            Some(span) => write!(f, "{}: {}", span, self.message()),
+            None => write!(f, "{}", self.message()),
         }
     }
 }
@@ -113,3 +279,72 @@ impl From<fmt::Error> for MonkeyErr {
 }
 
 pub type Result<T> = std::result::Result<T, MonkeyErr>;
+
+// What is wrong with the characters of a token
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexErrKind {
+    UnterminatedBlockComment,
+    UnterminatedString,
+    UnknownEscape(char),
+    UnicodeEscapeWithoutBrace,
+    UnterminatedUnicodeEscape,
+    UnicodeEscapeLength,
+    // The hex digits of an escape which is not a character
+    InvalidUnicode(String),
+    // A float like .5, without a digit before the point
+    LeadingPoint,
+    // radix is the name of the base, like "binary"
+    InvalidDigit { digit: char, radix: &'static str },
+    NoDigits { radix: &'static str },
+    IntegerTooLarge,
+    FloatTooLarge,
+}
+
+impl Display for LexErrKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnterminatedBlockComment => write!(f, "unterminated block comment"),
+            Self::UnterminatedString => write!(f, "unterminated string"),
+            Self::UnknownEscape(ch) => write!(f, "unknown escape sequence `\\{}`", ch),
+            Self::UnicodeEscapeWithoutBrace => write!(f, "expected `{{` after `\\u`"),
+            Self::UnterminatedUnicodeEscape => write!(f, "unterminated unicode escape"),
+            Self::UnicodeEscapeLength => write!(f, "unicode escape must have 1 to 6 hex digits"),
+            Self::InvalidUnicode(digits) => {
+                write!(f, "invalid unicode character `\\u{{{}}}`", digits)
+            }
+            Self::LeadingPoint => write!(f, "a float literal needs a digit before the `.`"),
+            Self::InvalidDigit { digit, radix } => {
+                write!(f, "invalid digit `{}` in {} literal", digit, radix)
+            }
+            Self::NoDigits { radix } => write!(f, "{} literal has no digits", radix),
+            Self::IntegerTooLarge => write!(f, "integer literal is too large"),
+            Self::FloatTooLarge => write!(f, "float literal is too large"),
+        }
+    }
+}
+
+// Problems which are reported, but do not stop the program
+#[derive(Debug, PartialEq)]
+pub enum MonkeyWarn {
+    UnreachableArm { span: Span, covered_by: Span },
+}
+
+impl MonkeyWarn {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::UnreachableArm { span, .. } => *span,
+        }
+    }
+
+    pub fn message(&self) -> String {
+        match self {
+            Self::UnreachableArm { .. } => "unreachable match arm".to_string(),
+        }
+    }
+}
+
+impl Display for MonkeyWarn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.span(), self.message())
+    }
+}
@@ -0,0 +1,505 @@
+use super::*;
+use crate::error;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use std::thread;
+
+fn run(input: &str) -> error::Result<Object> {
+    let program = Parser::new(Lexer::new(input)).parse_program()?;
+    eval_program(&program, &Environment::new())
+}
+
+macro_rules! test_eval {
+    ($test: ident => $($input: expr => $expected: expr),+ $(,)?) => {
+        #[test]
+        fn $test() -> error::Result<()> {
+            $(assert_eq!($expected, run($input)?, "input: {}", $input);)+
+            Ok(())
+        }
+    };
+}
+
+macro_rules! test_eval_err {
+    ($test: ident => $($input: expr => $msg: expr),+ $(,)?) => {
+        #[test]
+        fn $test() {
+            $(match run($input) {
+                Err(Error::EvalErr { msg, .. }) => assert_eq!($msg, msg, "input: {}", $input),
+                other => panic!("expected an eval error for {}, got {:?}", $input, other),
+            })+
+        }
+    };
+}
+
+test_eval!(
+    eval_integer =>
+    "5" => Object::Integer(5),
+    "-10" => Object::Integer(-10),
+    "5 + 5 + 5 + 5 - 10" => Object::Integer(10),
+    "2 * 2 * 2 * 2 * 2" => Object::Integer(32),
+    "-50 + 100 + -50" => Object::Integer(0),
+    "20 + 2 * -10" => Object::Integer(0),
+    "2 * (5 + 10)" => Object::Integer(30),
+    "(5 + 10 * 2 + 15 / 3) * 2 + -10" => Object::Integer(50),
+    "2 ** 10" => Object::Integer(1024),
+);
+
+test_eval!(
+    eval_boolean =>
+    "true" => Object::Boolean(true),
+    "1 < 2" => Object::Boolean(true),
+    "1 > 2" => Object::Boolean(false),
+    "1 == 1" => Object::Boolean(true),
+    "1 != 1" => Object::Boolean(false),
+    "true == false" => Object::Boolean(false),
+    "(1 < 2) == true" => Object::Boolean(true),
+    "!true" => Object::Boolean(false),
+    "!!5" => Object::Boolean(true),
+    "1 == true" => Object::Boolean(false),
+    "1 <= 1" => Object::Boolean(true),
+    "2 >= 3" => Object::Boolean(false),
+    "1.5 >= 1" => Object::Boolean(true),
+    "true && 1 < 2" => Object::Boolean(true),
+    "1 > 2 || 0 && false" => Object::Boolean(false),
+    "5 || false" => Object::Boolean(true),
+);
+
+test_eval!(
+    eval_short_circuit =>
+    "false && undefined_name" => Object::Boolean(false),
+    "true || undefined_name" => Object::Boolean(true),
+    "let n = 0; let f = fn(x) { x != 0 && 10 / x > 1 }; f(n)" => Object::Boolean(false),
+);
+
+test_eval!(
+    eval_bitwise =>
+    "0xf0 | 0x0f" => Object::Integer(0xff),
+    "6 & 3" => Object::Integer(2),
+    "6 ^ 3" => Object::Integer(5),
+    "~0" => Object::Integer(-1),
+    "1 << 62" => Object::Integer(1 << 62),
+    "-16 >> 2" => Object::Integer(-4),
+    "1 + 1 << 2" => Object::Integer(8),
+    "1 | 2 ^ 3 & 4" => Object::Integer(3),
+    "(0b1010 & 0b10) == 2" => Object::Boolean(true),
+);
+
+test_eval!(
+    eval_assignment =>
+    "let x = 1; x = x + 1; x" => Object::Integer(2),
+    "let x = 1; x += 10; x *= 2; x -= 1; x /= 3; x" => Object::Integer(7),
+    "let x = 1; x = 5" => Object::Integer(5),
+    "let a = 0; let b = 0; a = b = 7; a + b" => Object::Integer(14),
+    "let a = [1, [2, 3]]; a[0] = 5; a[1][1] += 100; a" => Object::Array(vec![
+        Object::Integer(5),
+        Object::Array(vec![Object::Integer(2), Object::Integer(103)]),
+    ]),
+    r#"let h = {"k": 1}; h["k"] -= 3; h["new"] = 2; h["k"] + h["new"]"# => Object::Integer(0),
+    "let a = [1]; a[0] += 1" => Object::Integer(2),
+    "let n = 0; let inc = fn() { n += 1 }; inc(); inc(); n" => Object::Integer(2),
+    "let n = 0; let f = fn() { let n = 5; n = 6; n }; f() + n" => Object::Integer(6),
+    "let i = 0; let a = [0, 0]; let next = fn() { i += 1; i - 1 }; a[next()] = 9; [a, i]"
+        => Object::Array(vec![
+            Object::Array(vec![Object::Integer(9), Object::Integer(0)]),
+            Object::Integer(1),
+        ]),
+);
+
+test_eval!(
+    eval_loops =>
+    "let i = 0; while (i < 5) { i += 1; } i" => Object::Integer(5),
+    "let s = 0; let i = 0;
+     while (true) { i += 1; if (i % 2 == 0) { continue; } if (i > 9) { break; } s += i; }
+     s" => Object::Integer(25),
+    "let s = 0; for (x in [1, 2, 3]) { s += x; } s" => Object::Integer(6),
+    r#"let s = ""; for (k in {"b": 1, "a": 2, "c": 3}) { s += k; } s"#
+        => Object::String("abc".to_string()),
+    r#"let s = []; for (ch in "héllo") { if (ch == "l") { continue } s = push(s, ch); } s"#
+        => Object::Array(vec![
+            Object::String("h".to_string()),
+            Object::String("é".to_string()),
+            Object::String("o".to_string()),
+        ]),
+    "let n = 0; for (x in [1, 2]) { for (y in [1, 2, 3]) { if (y > x) { break } n += 1; } } n"
+        => Object::Integer(3),
+    "let f = fn(xs) { for (x in xs) { if (x > 1) { return x; } } 0 }; f([1, 5, 7]) + f([])"
+        => Object::Integer(5),
+    "let x = 7; for (x in []) { } x" => Object::Integer(7),
+    "while (false) { }" => Object::Null,
+    "let i = 0; while (i < 100000) { i += 1 } i" => Object::Integer(100000),
+);
+
+test_eval!(
+    eval_ranges =>
+    "let s = 0; for (i in 1..=4) { s += i; } s" => Object::Integer(10),
+    "let n = 0; for (i in 3..1) { n += 1; } n" => Object::Integer(0),
+    "let r = 2..5; [len(r), r[0], r[2], r[3]]" => Object::Array(vec![
+        Object::Integer(3),
+        Object::Integer(2),
+        Object::Integer(4),
+        Object::Null,
+    ]),
+    "(0..10)[2..=4]" => Object::Range { start: 2, end: 5, inclusive: false },
+    "(0..3) == (0..=2)" => Object::Boolean(true),
+    "(4..1) == (7..7)" => Object::Boolean(true),
+    "(0..3) == (1..4)" => Object::Boolean(false),
+    "len(0..9223372036854775807)" => Object::Integer(i64::MAX),
+    "(5..=9223372036854775807)[0..2]" => Object::Range { start: 5, end: 7, inclusive: false },
+    "let s = 0; for (i in 0..1000000000000) { if (i == 3) { break } s += i; } s"
+        => Object::Integer(3),
+);
+
+test_eval!(
+    eval_slices =>
+    "let a = [0, 1, 2, 3]; [a[1..3], a[2..], a[..1], a[1..=1]]" => Object::Array(vec![
+        Object::Array(vec![Object::Integer(1), Object::Integer(2)]),
+        Object::Array(vec![Object::Integer(2), Object::Integer(3)]),
+        Object::Array(vec![Object::Integer(0)]),
+        Object::Array(vec![Object::Integer(1)]),
+    ]),
+    "[1, 2][2..]" => Object::Array(vec![]),
+    "let r = 1..3; [1, 2, 3, 4][r]" => Object::Array(vec![Object::Integer(2), Object::Integer(3)]),
+    r#""héllo"[1..4]"# => Object::String("éll".to_string()),
+    r#""héllo"[..]"# => Object::String("héllo".to_string()),
+    "let i = 1; (10..20)[i..i + 2]" => Object::Range { start: 11, end: 13, inclusive: false },
+);
+
+test_eval!(
+    eval_remainder =>
+    "7 % 3" => Object::Integer(1),
+    "-7 % 3" => Object::Integer(-1),
+    "7.5 % 2" => Object::Float(1.5),
+    "1 + 10 % 4 * 2" => Object::Integer(5),
+);
+
+test_eval!(
+    eval_complex =>
+    "1 + 2i" => Object::Complex { re: 1.0, im: 2.0 },
+    "(1 + 2i) + (3 - 1i)" => Object::Complex { re: 4.0, im: 1.0 },
+    "(1 + 2i) * (3 + 4i)" => Object::Complex { re: -5.0, im: 10.0 },
+    "2 * 3i" => Object::Complex { re: 0.0, im: 6.0 },
+    "-(1 + 2i)" => Object::Complex { re: -1.0, im: -2.0 },
+    "(1 + 2i) == (1 + 2i)" => Object::Boolean(true),
+    "2 * 3 + 4i" => Object::Complex { re: 6.0, im: 4.0 },
+    "let x = 3; x + 4i" => Object::Complex { re: 3.0, im: 4.0 },
+    "(3 + 4i) * 2" => Object::Complex { re: 6.0, im: 8.0 },
+    "(1 + 2i) / (3 - 4i)" => Object::Complex { re: -0.2, im: 0.4 },
+    "(1 + 2i) ** 2" => Object::Complex { re: -3.0, im: 4.0 },
+    "2i ** -1" => Object::Complex { re: 0.0, im: -0.5 },
+    "(3 + 4i) != (3 - 4i)" => Object::Boolean(true),
+);
+
+test_eval!(
+    eval_complex_builtins =>
+    "re(3 + 4i)" => Object::Float(3.0),
+    "im(3 + 4i)" => Object::Float(4.0),
+    "im(2.5)" => Object::Float(0.0),
+    "conj(3 + 4i)" => Object::Complex { re: 3.0, im: -4.0 },
+    "conj(7)" => Object::Integer(7),
+    "abs(3 + 4i)" => Object::Float(5.0),
+    "abs(-7)" => Object::Integer(7),
+    "abs(-0.5)" => Object::Float(0.5),
+    "arg(1i)" => Object::Float(std::f64::consts::FRAC_PI_2),
+);
+
+test_eval!(
+    eval_float =>
+    "2.75" => Object::Float(2.75),
+    "1.5 * 2.0 - 1e-1" => Object::Float(2.9),
+    "-2.5 ** 2.0" => Object::Float(6.25),
+    "7 / 2" => Object::Integer(3),
+    "7 / 2.0" => Object::Float(3.5),
+    "1 + 0.5" => Object::Float(1.5),
+    "1 == 1.0" => Object::Boolean(true),
+    "0.1 < 1" => Object::Boolean(true),
+    "2.5i * 2" => Object::Complex { re: 0.0, im: 5.0 },
+    "1.5 + 2i" => Object::Complex { re: 1.5, im: 2.0 },
+    "(1 + 2i) == 1" => Object::Boolean(false),
+);
+
+test_eval!(
+    eval_if_else =>
+    "if (true) { 10 }" => Object::Integer(10),
+    "if (false) { 10 }" => Object::Null,
+    "if (1) { 10 }" => Object::Integer(10),
+    "if (1 > 2) { 10 } else { 20 }" => Object::Integer(20),
+);
+
+test_eval!(
+    eval_return =>
+    "return 10; 9;" => Object::Integer(10),
+    "9; return 2 * 5; 9;" => Object::Integer(10),
+    "if (10 > 1) { if (10 > 1) { return 10; } return 1; }" => Object::Integer(10),
+);
+
+test_eval!(
+    eval_let =>
+    "let a = 5; a;" => Object::Integer(5),
+    "let a = 5 * 5; a;" => Object::Integer(25),
+    "let a = 5; let b = a; let c = a + b + 5; c;" => Object::Integer(15),
+);
+
+test_eval!(
+    eval_function_and_closure =>
+    "let identity = fn(x) { x; }; identity(5);" => Object::Integer(5),
+    "let add = fn(x, y) { return x + y; }; add(5, add(5, 5));" => Object::Integer(15),
+    "fn(x) { x; }(5)" => Object::Integer(5),
+    "let adder = fn(x) { fn(y) { x + y } }; let add_two = adder(2); add_two(3);"
+        => Object::Integer(5),
+    "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15);"
+        => Object::Integer(610),
+);
+
+test_eval!(
+    eval_string =>
+    r#""Hello World!""# => Object::String("Hello World!".to_string()),
+    r#""Hello" + " " + "World!""# => Object::String("Hello World!".to_string()),
+    r#""a" == "a""# => Object::Boolean(true),
+    r#"let 인사 = "안녕"; 인사 + ", 世界 😀""# => Object::String("안녕, 世界 😀".to_string()),
+);
+
+test_eval!(
+    eval_array_and_index =>
+    "[1, 2 * 2, 3 + 3]" => Object::Array(vec![
+        Object::Integer(1),
+        Object::Integer(4),
+        Object::Integer(6),
+    ]),
+    "[1, 2, 3][0]" => Object::Integer(1),
+    "let i = 0; [1][i];" => Object::Integer(1),
+    "let myArray = [1, 2, 3]; myArray[0] + myArray[1] + myArray[2];" => Object::Integer(6),
+    "[1, 2, 3][3]" => Object::Null,
+    "[1, 2, 3][-1]" => Object::Null,
+);
+
+test_eval!(
+    eval_hash =>
+    r#"{"one": 10 - 9, true: 2, 3: 3}["one"]"# => Object::Integer(1),
+    r#"let key = "foo"; {"foo": 5}[key]"# => Object::Integer(5),
+    r#"{"foo": 5}["bar"]"# => Object::Null,
+    "{true: 5}[true]" => Object::Integer(5),
+    "{5: 5}[5]" => Object::Integer(5),
+);
+
+test_eval!(
+    eval_builtins =>
+    r#"len("")"# => Object::Integer(0),
+    r#"len("four")"# => Object::Integer(4),
+    r#"len("안녕 😀")"# => Object::Integer(4),
+    "len([1, 2, 3])" => Object::Integer(3),
+    "first([1, 2, 3])" => Object::Integer(1),
+    "last([1, 2, 3])" => Object::Integer(3),
+    "rest([1, 2, 3])" => Object::Array(vec![Object::Integer(2), Object::Integer(3)]),
+    "rest([])" => Object::Null,
+    "push([], 1)" => Object::Array(vec![Object::Integer(1)]),
+);
+
+test_eval!(
+    eval_match =>
+    r#"let f = fn(v) {
+        match (v) {
+            0 => "zero",
+            -1.5 => "negative",
+            "s" => "string",
+            [] => "empty",
+            [x] => "one ${x}",
+            [x, ..rest] if x > 0 => "many ${rest}",
+            [_, ..] => "many",
+            {"x": x, "y": [y, _]} => "point ${x} ${y}",
+            _ => "other",
+        }
+    };
+    [f(0.0), f(-1.5), f("s"), f([]), f([1]), f([1, 2, 3]), f([0, 1]),
+     f({"x": 1, "y": [2, 3], "z": 4}), f({"x": 1, "y": [2]}), f(true)]"#
+        => Object::Array(
+            [
+                "zero", "negative", "string", "empty", "one 1", "many [2, 3]", "many",
+                "point 1 2", "other", "other",
+            ]
+            .iter()
+            .map(|s| Object::String(s.to_string()))
+            .collect()
+        ),
+    "match (3) { 1 => 1 }" => Object::Null,
+    "match ([1, 2]) { [a, b] if a > b => 1, [a, b] => a + b }" => Object::Integer(3),
+    "let x = 1; match (5) { x if x < 0 => 1, _ => x }" => Object::Integer(1),
+    "match ({1: 2}) { {1: n, 2: _} => 0, {1: n} => n }" => Object::Integer(2),
+);
+
+test_eval!(
+    eval_destructuring =>
+    r#"let [a, [b, c], {"x": x}] = [1, [2, 3], {"x": 4, "y": 5}]; a + b + c + x"#
+        => Object::Integer(10),
+    "let [first, ..rest] = [1, 2, 3]; rest" => Object::Array(vec![
+        Object::Integer(2),
+        Object::Integer(3),
+    ]),
+    "let [_, ..] = [0]; let [0, n] = [0.0, 1]; n" => Object::Integer(1),
+    "let f = fn(p) { let [x, y] = p; x * y }; f([6, 7])" => Object::Integer(42),
+    "let [a, a] = [1, 2]; a" => Object::Integer(2),
+);
+
+test_eval_err!(
+    eval_destructuring_errors =>
+    "let [a, b] = [1]" => "cannot destructure value with [a, b]: expected 2 elements, got 1",
+    "let [a, ..r] = []" => "cannot destructure value with [a, ..r]: expected at least 1 element, got 0",
+    "let [a, [b, c]] = [1, 2]"
+        => "cannot destructure value[1] with [b, c]: expected ARRAY, got INTEGER",
+    r#"let {"x": x} = {"y": 1}"# => r#"cannot destructure value with {"x": x}: missing key "x""#,
+    r#"let {"p": [0, {"q": 1}]} = {"p": [0, {"q": "s"}]}"#
+        => r#"cannot destructure value["p"][1]["q"] with 1: got "s""#,
+    "let {1: a} = [1]" => "cannot destructure value with {1: a}: expected HASH, got ARRAY",
+);
+
+test_eval_err!(
+    eval_errors =>
+    "5 + true;" => "type mismatch: INTEGER + BOOLEAN",
+    "5 + true; 5;" => "type mismatch: INTEGER + BOOLEAN",
+    "-true" => "unknown operator: -BOOLEAN",
+    "true + false;" => "unknown operator: BOOLEAN + BOOLEAN",
+    "if (10 > 1) { true + false; }" => "unknown operator: BOOLEAN + BOOLEAN",
+    r#""Hello" - "World""# => "unknown operator: STRING - STRING",
+    r#"{"name": "Monkey"}[fn(x) { x }];"# => "unusable as hash key: FUNCTION",
+    "10 / 0" => "division by zero",
+    "9223372036854775807 + 1" => "integer overflow",
+    "fn(x) { x }(1, 2)" => "wrong number of arguments to fn(x): want=1, got=2",
+    "1.0 / 0" => "division by zero",
+    "1i / 0" => "division by zero",
+    "1 % 0" => "division by zero",
+    "for (x in 5) { }" => "not iterable: INTEGER",
+    "1..2.5" => "range bounds must be INTEGER, got INTEGER..FLOAT",
+    "[1, 2][1..3]" => "slice out of bounds: 1..3 for ARRAY of length 2",
+    r#""ab"[-1..]"# => "slice out of bounds: -1.. for STRING of length 2",
+    "[1, 2][2..=0]" => "slice starts after its end: 2..=0",
+    "[1][..true]" => "slice bounds must be INTEGER, got BOOLEAN",
+    "{}[0..1]" => "slicing not supported: HASH",
+    "(5..=9223372036854775807)[0..]" => "slice does not fit in a range: 0..",
+    "(0..=9223372036854775807)[1..]" => "slice does not fit in a range: 1..",
+    "len(0..=9223372036854775807)"
+        => "length of 0..=9223372036854775807 does not fit in an INTEGER",
+    "len(-9223372036854775807..=9223372036854775807)"
+        => "length of -9223372036854775807..=9223372036854775807 does not fit in an INTEGER",
+    "len = 1" => "cannot assign to builtin function len",
+    "let a = [1]; a[1] = 2" => "index out of bounds: 1 for array of length 1",
+    r#"let a = [1]; a["0"] = 2"# => "array index must be an INTEGER, got STRING",
+    r#"let h = {}; h["x"] += 1"# => r#"key not found: "x""#,
+    r#"let s = "ab"; s[0] = "c""# => "index assignment not supported: STRING",
+    "1 << 64" => "shift amount out of range: 1 << 64",
+    "1 >> -1" => "shift amount out of range: 1 >> -1",
+    "~1.5" => "unknown operator: ~FLOAT",
+    "1 & 2 == 2" => "type mismatch: INTEGER & BOOLEAN",
+    "false || 1 % 0 == 0" => "division by zero",
+    "0i ** -1" => "division by zero",
+    r#"abs("x")"# => "argument to `abs` not supported, got STRING",
+    "len(1)" => "argument to `len` not supported, got INTEGER",
+    r#"match ("a") { x if x > 1 => 1 }"# => "type mismatch: STRING > INTEGER",
+    r#"len("one", "two")"# => "wrong number of arguments to `len`. got=2, want=1",
+);
+
+test_eval!(
+    eval_string_literals =>
+    r#""a\tb" + "\u{1F600}""# => Object::String("a\tb😀".to_string()),
+    r#"len(r"\n")"# => Object::Integer(2),
+    "len(\"\"\"a\nb\"\"\")" => Object::Integer(3),
+);
+
+test_eval!(
+    eval_interpolated_strings =>
+    r#"let name = "world"; "hello ${name}!""# => Object::String("hello world!".to_string()),
+    r#""${1 + 2} ${true} ${[1, "a"]}""# => Object::String("3 true [1, \"a\"]".to_string()),
+    r#"let f = fn(x) { "<${x}>" }; "${f("${f(1)}")}""# => Object::String("<<1>>".to_string()),
+    r#""\${x}""# => Object::String("${x}".to_string()),
+);
+
+test_eval!(
+    eval_parameters =>
+    "let f = fn(x, y = x * 10) { [x, y] }; f(1)" => Object::Array(vec![Object::Integer(1), Object::Integer(10)]),
+    "let f = fn(x, y = 10) { x - y }; f(1, 2)" => Object::Integer(-1),
+    "let f = fn(x, y) { x - y }; f(y: 1, x: 5)" => Object::Integer(4),
+    "let f = fn(x, y = 2, z = 3) { x * y + z }; f(1, z: 0)" => Object::Integer(2),
+    "let f = fn(first, ..rest) { rest }; f(1, 2, 3)" => Object::Array(vec![Object::Integer(2), Object::Integer(3)]),
+    "fn(..xs) { len(xs) }()" => Object::Integer(0),
+    "let x = 1; fn(y = x) { y }()" => Object::Integer(1),
+);
+
+test_eval_err!(
+    eval_argument_errors =>
+    "let f = fn(x, y = 1) { x }; f(1, 2, 3)" => "wrong number of arguments to f(x, y = 1): want=1 to 2, got=3",
+    "let f = fn(x, ..r) { x }; f()" => "wrong number of arguments to f(x, ..r): want=at least 1, got=0",
+    "let f = fn(x, y) { x }; f(y: 1)" => "missing argument for x in call to f(x, y)",
+    "let f = fn(x) { x }; f(1, x: 2)" => "argument x given twice in call to f(x)",
+    "fn(x) { x }(z: 2)" => "unknown parameter z in call to fn(x)",
+    "len(x: [])" => "builtin function len takes no arguments by name",
+);
+
+test_eval!(
+    eval_lambdas_and_pipelines =>
+    "let double = |x| x * 2; double(4)" => Object::Integer(8),
+    "(x => x + 1)(1)" => Object::Integer(2),
+    "(|| 3)()" => Object::Integer(3),
+    "let add = |a, b = 10| a + b; [add(1), add(1, 2)]" => Object::Array(vec![Object::Integer(11), Object::Integer(3)]),
+    "let y = 5; let f = |x| x + y; f(1)" => Object::Integer(6),
+    "let sub = |a, b| a - b; 10 |> sub(3) |> sub(2)" => Object::Integer(5),
+    "[1, 2, 3] |> len()" => Object::Integer(3),
+    "let double = |x| x * 2; 1 + 2 |> double()" => Object::Integer(6),
+    "match (2) { n if n > 1 => x => x * n, _ => 0 }(3)" => Object::Integer(6),
+);
+
+test_eval!(
+    eval_without_semicolons =>
+    "let a = 1\nlet b = a +\n  2\nb * 2" => Object::Integer(6),
+    "let f = fn(x) {\n  let y = x * 2\n  return y\n}\nf(3)" => Object::Integer(6),
+    "let i = 0\nwhile (i < 3) { i += 1 }\ni" => Object::Integer(3),
+    "let xs = [1,\n  2]\nxs\n  |> len()" => Object::Integer(2),
+);
+
+test_eval!(
+    eval_loop_control_in_expressions =>
+    "let s = 0; for (i in 0..5) { let y = if (i == 3) { break } else { i }; s += y } s"
+        => Object::Integer(3),
+    "let s = 0; for (i in 0..4) { s += 1 + if (i % 2 == 0) { continue } else { 0 } } s"
+        => Object::Integer(2),
+    "let n = 0; let f = fn(a, b) { a }; for (i in 0..10) { n += 1; f(1, if (true) { continue } else { 2 }) } n"
+        => Object::Integer(10),
+    "let i = 0; while (i < 5) { i += 1; let [a] = [if (i > 2) { break } else { i }] } i"
+        => Object::Integer(3),
+    r#"let s = ""; for (x in [1, 2]) { s += "${if (x == 2) { continue } else { x }}" } s"#
+        => Object::String("1".to_string()),
+    "let f = fn(x) { 1 + if (x) { return 10 } else { 0 } }; [f(true), f(false)]"
+        => Object::Array(vec![Object::Integer(10), Object::Integer(1)]),
+    "let n = 0; let f = fn(a, b) { a };
+     for (x in [1, 2, 3]) {
+         f(1, if (true) { while (if (x == 2) { continue } else { false }) { } 0 } else { 0 });
+         n += x
+     }
+     n"
+        => Object::Integer(4),
+);
+
+// Deep recursion takes more than the native stack of a test thread
+#[test]
+fn eval_deep_recursion() {
+    let cases = &[
+        ("let f = fn(n) { f(n + 1) }; f(0)", None),
+        ("let f = fn(n) { 1 + f(n + 1) }; f(0)", None),
+        (
+            "let f = fn(n) { if (n == 0) { 0 } else { 1 + f(n - 1) } }; f(1022)",
+            Some("1022"),
+        ),
+    ];
+    for (input, expected) in cases {
+        let result = thread::Builder::new()
+            .stack_size(256 << 20)
+            .spawn(move || run(input).map(|value| value.to_string()))
+            .expect("cannot start the test thread")
+            .join()
+            .expect("the evaluator does not panic");
+        match (expected, result) {
+            (Some(expected), Ok(value)) => assert_eq!(*expected, value, "input: {}", input),
+            (None, Err(Error::EvalErr { msg, .. })) => {
+                assert_eq!("maximum call depth exceeded", msg)
+            }
+            (_, other) => panic!("unexpected result for {}: {:?}", input, other),
+        }
+    }
+}
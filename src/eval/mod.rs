@@ -0,0 +1,1130 @@
+#[cfg(test)]
+mod eval_test;
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::mem;
+use std::ops::ControlFlow;
+
+use crate::error;
+use crate::lexer::token::Token;
+use crate::object::builtins;
+use crate::object::environment::{Env, Environment};
+use crate::object::{self, Object};
+use crate::parser::ast::{
+    BlockStmt, ExprKind, Expression, MatchArm, Parameters, Pattern, PatternKind, Program,
+    Statement, StmtKind, StringPart,
+};
+use crate::vm;
+
+type Error = error::MonkeyErr;
+
+// break, continue and return may come in the middle of an expression. They
+// stop it, and the object which stands for them is passed up like the value
+// of a block to the loop or function they belong to.
+macro_rules! value {
+    ($flow: expr) => {
+        match $flow {
+            ControlFlow::Continue(value) => value,
+            ControlFlow::Break(signal) => return Ok(signal),
+        }
+    };
+}
+
+fn control_flow(obj: Object) -> ControlFlow<Object, Object> {
+    match obj {
+        Object::ReturnValue(_) | Object::Break | Object::Continue => ControlFlow::Break(obj),
+        _ => ControlFlow::Continue(obj),
+    }
+}
+
+fn eval_value(expr: &Expression, env: &Env) -> error::Result<ControlFlow<Object, Object>> {
+    Ok(control_flow(eval_expression(expr, env)?))
+}
+
+pub fn eval_program(program: &Program, env: &Env) -> error::Result<Object> {
+    let mut result = Object::Null;
+    for stmt in program.get_stmts() {
+        result = eval_statement(stmt, env)?;
+        if let Object::ReturnValue(value) = result {
+            return Ok(*value);
+        }
+    }
+    Ok(result)
+}
+
+fn eval_block_statement(block: &BlockStmt, env: &Env) -> error::Result<Object> {
+    let mut result = Object::Null;
+    for stmt in block {
+        result = eval_statement(stmt, env)?;
+        // Return value is not unwrapped here so that it stops outer blocks too
+        if let Object::ReturnValue(_) | Object::Break | Object::Continue = result {
+            return Ok(result);
+        }
+    }
+    Ok(result)
+}
+
+fn eval_statement(stmt: &Statement, env: &Env) -> error::Result<Object> {
+    eval_statement_kind(stmt, env).map_err(|err| err.at(stmt.span))
+}
+
+fn eval_statement_kind(stmt: &Statement, env: &Env) -> error::Result<Object> {
+    match &stmt.kind {
+        StmtKind::LetStmt { name, value } => {
+            let is_literal = matches!(value.kind, ExprKind::Function { .. });
+            let mut value = value!(eval_value(value, env)?);
+            if let Object::Function { name: fn_name, .. } = &mut value {
+                if is_literal {
+                    *fn_name = Some(name.clone());
+                }
+            }
+            env.borrow_mut().set(name, value);
+            Ok(Object::Null)
+        }
+        StmtKind::LetPatternStmt { pattern, value } => {
+            let value = value!(eval_value(value, env)?);
+            let mut bound = Vec::new();
+            destructure(pattern, &value, &mut bound)?;
+            for (name, value) in pattern.bindings().into_iter().zip(bound) {
+                env.borrow_mut().set(name, value);
+            }
+            Ok(Object::Null)
+        }
+        StmtKind::ReturnStmt { value } => Ok(Object::ReturnValue(Box::new(value!(eval_value(
+            value, env
+        )?)))),
+        StmtKind::ExpressionStmt { expression } => eval_expression(expression, env),
+        StmtKind::WhileStmt { condition, body } => {
+            while value!(eval_value(condition, env)?).is_truthy() {
+                match eval_block_statement(body, env)? {
+                    Object::Break => break,
+                    result @ Object::ReturnValue(_) => return Ok(result),
+                    _ => {}
+                }
+            }
+            Ok(Object::Null)
+        }
+        StmtKind::ForStmt {
+            variable,
+            iterable,
+            body,
+        } => {
+            let sequence = iteration_sequence(value!(eval_value(iterable, env)?))?;
+            let mut i = 0;
+            while let Some(value) = iteration_nth(&sequence, i) {
+                env.borrow_mut().set(variable, value);
+                match eval_block_statement(body, env)? {
+                    Object::Break => break,
+                    result @ Object::ReturnValue(_) => return Ok(result),
+                    _ => {}
+                }
+                i += 1;
+            }
+            Ok(Object::Null)
+        }
+        StmtKind::BreakStmt => Ok(Object::Break),
+        StmtKind::ContinueStmt => Ok(Object::Continue),
+    }
+}
+
+// What a for loop goes through: the elements of an array, the keys of a hash
+// in order, the chars of a string, or the integers of a range. Only the
+// sequence is kept while looping, and each value is taken from it by its
+// position.
+pub fn iteration_sequence(iterable: Object) -> error::Result<Object> {
+    match iterable {
+        Object::Array(_) | Object::Range { .. } => Ok(iterable),
+        Object::Hash(pairs) => Ok(Object::Array(pairs.into_keys().map(Object::from).collect())),
+        Object::String(s) => Ok(Object::Array(
+            s.chars().map(|ch| Object::String(ch.to_string())).collect(),
+        )),
+        other => Err(Error::EvalErr {
+            msg: format!("not iterable: {}", other.type_name()),
+            span: None,
+        }),
+    }
+}
+
+pub fn iteration_nth(sequence: &Object, i: usize) -> Option<Object> {
+    match sequence {
+        Object::Array(elements) => elements.get(i).cloned(),
+        Object::Range {
+            start,
+            end,
+            inclusive,
+        } if (i as u64) < object::range_len(*start, *end, *inclusive) => {
+            Some(Object::Integer((i128::from(*start) + i as i128) as i64))
+        }
+        _ => None,
+    }
+}
+
+fn eval_expression(expr: &Expression, env: &Env) -> error::Result<Object> {
+    eval_expression_kind(expr, env).map_err(|err| err.at(expr.span))
+}
+
+fn eval_expression_kind(expr: &Expression, env: &Env) -> error::Result<Object> {
+    match &expr.kind {
+        ExprKind::Ident(name) => eval_identifier(name, env),
+        ExprKind::String(s) => Ok(Object::String(s.clone())),
+        ExprKind::Interpolated(parts) => {
+            let mut s = String::new();
+            for part in parts {
+                match part {
+                    StringPart::Literal(literal) => s.push_str(literal),
+                    StringPart::Expr(expr) => {
+                        s.push_str(&value!(eval_value(expr, env)?).to_string())
+                    }
+                }
+            }
+            Ok(Object::String(s))
+        }
+        ExprKind::Boolean(b) => Ok(Object::Boolean(*b)),
+        ExprKind::Integer(n) => Ok(Object::Integer(*n)),
+        ExprKind::Float(n) => Ok(Object::Float(*n)),
+        ExprKind::Imaginary(im) => Ok(Object::Complex { re: 0.0, im: *im }),
+        ExprKind::Array(elements) => Ok(Object::Array(value!(eval_expressions(elements, env)?))),
+        ExprKind::Hash { key, value } => eval_hash_expr(key, value, env),
+        ExprKind::Prefix { operator, right } => {
+            let right = value!(eval_value(right, env)?);
+            eval_prefix_expr(operator, right)
+        }
+        ExprKind::Infix {
+            left,
+            operator,
+            right,
+        } => {
+            let left = value!(eval_value(left, env)?);
+            if let (
+                Token::LBRACKET,
+                ExprKind::Range {
+                    start,
+                    end,
+                    inclusive,
+                },
+            ) = (operator, &right.kind)
+            {
+                let (start, end) = value!(eval_range_bounds(start, end, env)?);
+                return eval_slice(left, start, end, *inclusive);
+            }
+            // The right operand is evaluated only when it decides the value
+            match operator {
+                Token::AND if !left.is_truthy() => return Ok(Object::Boolean(false)),
+                Token::OR if left.is_truthy() => return Ok(Object::Boolean(true)),
+                Token::AND | Token::OR => {
+                    return Ok(Object::Boolean(value!(eval_value(right, env)?).is_truthy()))
+                }
+                _ => {}
+            }
+            let right = value!(eval_value(right, env)?);
+            if operator == &Token::LBRACKET {
+                eval_index_expr(left, right)
+            } else {
+                eval_infix_expr(operator, left, right)
+            }
+        }
+        ExprKind::Range {
+            start,
+            end,
+            inclusive,
+        } => {
+            let (start, end) = value!(eval_range_bounds(start, end, env)?);
+            make_range(start, end, *inclusive)
+        }
+        ExprKind::Assign {
+            target,
+            operator,
+            value,
+        } => eval_assign_expr(target, operator, value, env),
+        ExprKind::IfExpr {
+            condition,
+            consequence,
+            alternative,
+        } => {
+            if value!(eval_value(condition, env)?).is_truthy() {
+                eval_block_statement(consequence, env)
+            } else if let Some(alternative) = alternative {
+                eval_block_statement(alternative, env)
+            } else {
+                Ok(Object::Null)
+            }
+        }
+        ExprKind::Function {
+            parameters, body, ..
+        } => Ok(Object::Function {
+            name: None,
+            parameters: parameters.clone(),
+            body: body.clone(),
+            env: env.clone(),
+        }),
+        ExprKind::Call {
+            function,
+            arguments,
+            keywords,
+            ..
+        } => {
+            let function = value!(eval_value(function, env)?);
+            let arguments = value!(eval_expressions(arguments, env)?);
+            apply_function(function, arguments, keywords)
+        }
+        ExprKind::Match { subject, arms } => {
+            let subject = value!(eval_value(subject, env)?);
+            eval_match_expr(&subject, arms, env)
+        }
+    }
+}
+
+// Like the variable of a for loop, the bindings of the arm which matches
+// are set in the current environment
+fn eval_match_expr(subject: &Object, arms: &[MatchArm], env: &Env) -> error::Result<Object> {
+    let mut bound = Vec::new();
+    for arm in arms {
+        bound.clear();
+        if !match_pattern(&arm.pattern, subject, &mut bound)? {
+            continue;
+        }
+        // The variables of an arm are seen only by its guard and body
+        let arm_env = Environment::new_enclosed(env);
+        for (name, value) in arm.pattern.bindings().into_iter().zip(bound.drain(..)) {
+            arm_env.borrow_mut().set(name, value);
+        }
+        if let Some(guard) = &arm.guard {
+            if !value!(eval_value(guard, &arm_env)?).is_truthy() {
+                continue;
+            }
+        }
+        return eval_expression(&arm.body, &arm_env);
+    }
+    Ok(Object::Null)
+}
+
+// Whether value has the shape of pattern. The values of its bindings are
+// pushed onto bound in the order of Pattern::bindings.
+pub fn match_pattern(
+    pattern: &Pattern,
+    value: &Object,
+    bound: &mut Vec<Object>,
+) -> error::Result<bool> {
+    Ok(check_pattern(pattern, value, bound)?.is_none())
+}
+
+// Like match_pattern, but a value which does not match is an error
+pub fn destructure(
+    pattern: &Pattern,
+    value: &Object,
+    bound: &mut Vec<Object>,
+) -> error::Result<()> {
+    match check_pattern(pattern, value, bound)? {
+        Some(mismatch) => Err(mismatch.into_error()),
+        None => Ok(()),
+    }
+}
+
+// The part of a value which does not have the shape of its pattern
+struct Mismatch {
+    // The indexes and keys which lead to the part, the innermost first
+    path: Vec<String>,
+    pattern: String,
+    reason: String,
+}
+
+impl Mismatch {
+    fn into_error(self) -> Error {
+        let path: String = self.path.into_iter().rev().collect();
+        Error::EvalErr {
+            msg: format!(
+                "cannot destructure value{} with {}: {}",
+                path, self.pattern, self.reason
+            ),
+            span: None,
+        }
+    }
+}
+
+fn check_pattern(
+    pattern: &Pattern,
+    value: &Object,
+    bound: &mut Vec<Object>,
+) -> error::Result<Option<Mismatch>> {
+    let mismatch = |reason: String| {
+        Ok(Some(Mismatch {
+            path: Vec::new(),
+            pattern: pattern.to_string(),
+            reason,
+        }))
+    };
+    match &pattern.kind {
+        PatternKind::Wildcard => Ok(None),
+        PatternKind::Binding(_) => {
+            bound.push(value.clone());
+            Ok(None)
+        }
+        // Compared as by ==, so that 1 matches 1.0 too
+        PatternKind::Literal(literal) => {
+            if eval_infix_expr(&Token::EQ, literal_value(literal)?, value.clone())?.is_truthy() {
+                Ok(None)
+            } else {
+                mismatch(format!("got {}", object::inspect(value)))
+            }
+        }
+        PatternKind::Array { elements, rest } => {
+            let values = match value {
+                Object::Array(values) => values,
+                other => return mismatch(format!("expected ARRAY, got {}", other.type_name())),
+            };
+            match rest {
+                Some(_) if values.len() < elements.len() => {
+                    return mismatch(format!(
+                        "expected at least {}, got {}",
+                        count_elements(elements.len()),
+                        values.len()
+                    ))
+                }
+                None if values.len() != elements.len() => {
+                    return mismatch(format!(
+                        "expected {}, got {}",
+                        count_elements(elements.len()),
+                        values.len()
+                    ))
+                }
+                _ => {}
+            }
+            for (i, (element, value)) in elements.iter().zip(values).enumerate() {
+                if let Some(mut mismatch) = check_pattern(element, value, bound)? {
+                    mismatch.path.push(format!("[{}]", i));
+                    return Ok(Some(mismatch));
+                }
+            }
+            if let Some(Some(_)) = rest {
+                bound.push(Object::Array(values[elements.len()..].to_vec()));
+            }
+            Ok(None)
+        }
+        PatternKind::Hash { keys, values } => {
+            let pairs = match value {
+                Object::Hash(pairs) => pairs,
+                other => return mismatch(format!("expected HASH, got {}", other.type_name())),
+            };
+            for (key, pattern) in keys.iter().zip(values) {
+                let key = literal_value(key)?;
+                let value = match pairs.get(&key.to_hash_key()?) {
+                    Some(value) => value,
+                    None => return mismatch(format!("missing key {}", object::inspect(&key))),
+                };
+                if let Some(mut mismatch) = check_pattern(pattern, value, bound)? {
+                    mismatch.path.push(format!("[{}]", object::inspect(&key)));
+                    return Ok(Some(mismatch));
+                }
+            }
+            Ok(None)
+        }
+    }
+}
+
+fn count_elements(n: usize) -> String {
+    match n {
+        1 => "1 element".to_string(),
+        _ => format!("{} elements", n),
+    }
+}
+
+// The parser only gives literals here, which need no environment
+fn literal_value(literal: &Expression) -> error::Result<Object> {
+    eval_expression(literal, &Environment::new())
+}
+
+// The values of exprs, or what stopped one of them
+fn eval_expressions(
+    exprs: &[Expression],
+    env: &Env,
+) -> error::Result<ControlFlow<Object, Vec<Object>>> {
+    let mut values = Vec::with_capacity(exprs.len());
+    for expr in exprs {
+        match eval_value(expr, env)? {
+            ControlFlow::Continue(value) => values.push(value),
+            ControlFlow::Break(signal) => return Ok(ControlFlow::Break(signal)),
+        }
+    }
+    Ok(ControlFlow::Continue(values))
+}
+
+fn eval_identifier(name: &str, env: &Env) -> error::Result<Object> {
+    if let Some(obj) = env.borrow().get(name) {
+        return Ok(obj);
+    }
+    builtins::lookup(name).ok_or_else(|| Error::UndeclaredNameErr {
+        name: name.to_string(),
+        assigned: false,
+        span: None,
+    })
+}
+
+// A bound which is left out gives null
+fn eval_range_bounds(
+    start: &Option<Box<Expression>>,
+    end: &Option<Box<Expression>>,
+    env: &Env,
+) -> error::Result<ControlFlow<Object, (Object, Object)>> {
+    let mut bounds = [Object::Null, Object::Null];
+    for (bound, expr) in bounds.iter_mut().zip([start, end]) {
+        if let Some(expr) = expr {
+            match eval_value(expr, env)? {
+                ControlFlow::Continue(value) => *bound = value,
+                ControlFlow::Break(signal) => return Ok(ControlFlow::Break(signal)),
+            }
+        }
+    }
+    let [start, end] = bounds;
+    Ok(ControlFlow::Continue((start, end)))
+}
+
+pub fn make_range(start: Object, end: Object, inclusive: bool) -> error::Result<Object> {
+    match (start, end) {
+        (Object::Integer(start), Object::Integer(end)) => Ok(Object::Range {
+            start,
+            end,
+            inclusive,
+        }),
+        (start, end) => Err(Error::EvalErr {
+            msg: format!(
+                "range bounds must be INTEGER, got {}..{}",
+                start.type_name(),
+                end.type_name()
+            ),
+            span: None,
+        }),
+    }
+}
+
+// Takes the part of an array, a string or a range which the bounds cover.
+// A null bound was left out, and goes to the start or the end.
+pub fn eval_slice(
+    container: Object,
+    start: Object,
+    end: Object,
+    inclusive: bool,
+) -> error::Result<Object> {
+    let len = match &container {
+        Object::Array(elements) => elements.len() as i128,
+        Object::String(s) => s.chars().count() as i128,
+        Object::Range {
+            start,
+            end,
+            inclusive,
+        } => i128::from(object::range_len(*start, *end, *inclusive)),
+        other => {
+            return Err(Error::EvalErr {
+                msg: format!("slicing not supported: {}", other.type_name()),
+                span: None,
+            })
+        }
+    };
+    let bound = |bound: &Object| match bound {
+        Object::Null => Ok(None),
+        Object::Integer(n) => Ok(Some(i128::from(*n))),
+        other => Err(Error::EvalErr {
+            msg: format!("slice bounds must be INTEGER, got {}", other.type_name()),
+            span: None,
+        }),
+    };
+    let (from, to) = (bound(&start)?, bound(&end)?);
+    let range = format!(
+        "{}{}{}",
+        from.map_or(String::new(), |n| n.to_string()),
+        if inclusive { "..=" } else { ".." },
+        to.map_or(String::new(), |n| n.to_string())
+    );
+    let from = from.unwrap_or(0);
+    let to = to.map_or(len, |to| to + i128::from(inclusive));
+    if from > to {
+        return Err(Error::EvalErr {
+            msg: format!("slice starts after its end: {}", range),
+            span: None,
+        });
+    }
+    if from < 0 || to > len {
+        return Err(Error::EvalErr {
+            msg: format!(
+                "slice out of bounds: {} for {} of length {}",
+                range,
+                container.type_name(),
+                len
+            ),
+            span: None,
+        });
+    }
+    // Both are within 0..=len now
+    let (from, to) = (from as usize, to as usize);
+    Ok(match container {
+        Object::Array(elements) => Object::Array(elements[from..to].to_vec()),
+        Object::String(s) => Object::String(s.chars().skip(from).take(to - from).collect()),
+        Object::Range { start, .. } => {
+            let bound = |offset: usize| {
+                i64::try_from(i128::from(start) + offset as i128).map_err(|_| Error::EvalErr {
+                    msg: format!("slice does not fit in a range: {}", range),
+                    span: None,
+                })
+            };
+            Object::Range {
+                start: bound(from)?,
+                end: bound(to)?,
+                inclusive: false,
+            }
+        }
+        _ => unreachable!("only arrays, strings and ranges have a length here"),
+    })
+}
+
+fn eval_hash_expr(key: &[Expression], value: &[Expression], env: &Env) -> error::Result<Object> {
+    let mut pairs = BTreeMap::new();
+    for (k, v) in key.iter().zip(value) {
+        let k = value!(eval_value(k, env)?).to_hash_key()?;
+        let v = value!(eval_value(v, env)?);
+        pairs.insert(k, v);
+    }
+    Ok(Object::Hash(pairs))
+}
+
+// The indexes of the target are evaluated once, from left to right, and
+// before the value
+fn eval_assign_expr(
+    target: &Expression,
+    operator: &Token,
+    value: &Expression,
+    env: &Env,
+) -> error::Result<Object> {
+    let mut place = target;
+    let mut indexes = Vec::new();
+    while let ExprKind::Infix {
+        left,
+        operator: Token::LBRACKET,
+        right,
+    } = &place.kind
+    {
+        indexes.push(right);
+        place = left;
+    }
+    let name = match &place.kind {
+        ExprKind::Ident(name) => name,
+        _ => {
+            return Err(Error::EvalErr {
+                msg: format!("cannot assign to {}", target),
+                span: None,
+            })
+        }
+    };
+    let mut index_values = Vec::with_capacity(indexes.len());
+    for index in indexes.into_iter().rev() {
+        index_values.push(value!(eval_value(index, env)?));
+    }
+    let value = value!(eval_value(value, env)?);
+
+    let current = env
+        .borrow()
+        .get(name)
+        .ok_or_else(|| assign_undeclared_err(name))?;
+    let (updated, assigned) = assign_index(current, &index_values, operator, value)?;
+    env.borrow_mut().assign(name, updated);
+    Ok(assigned)
+}
+
+fn assign_undeclared_err(name: &str) -> Error {
+    if builtins::lookup(name).is_some() {
+        return Error::EvalErr {
+            msg: format!("cannot assign to builtin function {}", name),
+            span: None,
+        };
+    }
+    Error::UndeclaredNameErr {
+        name: name.to_string(),
+        assigned: true,
+        span: None,
+    }
+}
+
+// Puts value at the end of the index path in container, combined with the
+// old value by a compound operator. Gives back the updated container and the
+// value which was put.
+pub fn assign_index(
+    container: Object,
+    indexes: &[Object],
+    operator: &Token,
+    value: Object,
+) -> error::Result<(Object, Object)> {
+    let (index, rest) = match indexes.split_first() {
+        Some(split) => split,
+        None => {
+            let value = match operator.compound_operator() {
+                Some(operator) => eval_infix_expr(&operator, container, value)?,
+                None => value,
+            };
+            return Ok((value.clone(), value));
+        }
+    };
+    match (container, index) {
+        (Object::Array(mut elements), Object::Integer(idx)) => {
+            let len = elements.len();
+            let slot = usize::try_from(*idx)
+                .ok()
+                .and_then(|idx| elements.get_mut(idx))
+                .ok_or_else(|| Error::EvalErr {
+                    msg: format!("index out of bounds: {} for array of length {}", idx, len),
+                    span: None,
+                })?;
+            let (updated, assigned) =
+                assign_index(mem::replace(slot, Object::Null), rest, operator, value)?;
+            *slot = updated;
+            Ok((Object::Array(elements), assigned))
+        }
+        (Object::Array(_), index) => Err(Error::EvalErr {
+            msg: format!("array index must be an INTEGER, got {}", index.type_name()),
+            span: None,
+        }),
+        // A new key can be added, but not changed by a compound operator
+        (Object::Hash(mut pairs), key) => {
+            let hash_key = key.to_hash_key()?;
+            let old = match pairs.remove(&hash_key) {
+                Some(old) => old,
+                None if rest.is_empty() && operator == &Token::ASSIGN => Object::Null,
+                None => {
+                    return Err(Error::EvalErr {
+                        msg: format!("key not found: {}", object::inspect(key)),
+                        span: None,
+                    })
+                }
+            };
+            let (updated, assigned) = assign_index(old, rest, operator, value)?;
+            pairs.insert(hash_key, updated);
+            Ok((Object::Hash(pairs), assigned))
+        }
+        (container, _) => Err(Error::EvalErr {
+            msg: format!("index assignment not supported: {}", container.type_name()),
+            span: None,
+        }),
+    }
+}
+
+pub fn eval_prefix_expr(operator: &Token, right: Object) -> error::Result<Object> {
+    match (operator, right) {
+        (Token::BANG, right) => Ok(Object::Boolean(!right.is_truthy())),
+        (Token::MINUS, Object::Integer(n)) => n
+            .checked_neg()
+            .map(Object::Integer)
+            .ok_or_else(overflow_err),
+        (Token::MINUS, Object::Float(n)) => Ok(Object::Float(-n)),
+        (Token::BITNOT, Object::Integer(n)) => Ok(Object::Integer(!n)),
+        (Token::MINUS, Object::Complex { re, im }) => Ok(Object::Complex { re: -re, im: -im }),
+        (operator, right) => Err(Error::EvalErr {
+            msg: format!("unknown operator: {}{}", operator, right.type_name()),
+            span: None,
+        }),
+    }
+}
+
+// Numbers of different kinds are promoted along the tower integer, float,
+// complex. Integers stay integers when both operands are integers.
+pub fn eval_infix_expr(operator: &Token, left: Object, right: Object) -> error::Result<Object> {
+    if let (Object::Integer(l), Object::Integer(r)) = (&left, &right) {
+        return eval_integer_infix_expr(operator, *l, *r);
+    }
+    if let (Some(l), Some(r)) = (left.to_real(), right.to_real()) {
+        return eval_float_infix_expr(operator, l, r);
+    }
+    if let (Some(l), Some(r)) = (left.to_complex(), right.to_complex()) {
+        return eval_complex_infix_expr(operator, l, r);
+    }
+    match (left, right) {
+        (Object::String(l), Object::String(r)) => eval_string_infix_expr(operator, l, r),
+        (left, right) => match operator {
+            Token::EQ => Ok(Object::Boolean(left == right)),
+            Token::NOTEQ => Ok(Object::Boolean(left != right)),
+            _ if left.type_name() != right.type_name() => Err(Error::EvalErr {
+                msg: format!(
+                    "type mismatch: {} {} {}",
+                    left.type_name(),
+                    operator,
+                    right.type_name()
+                ),
+                span: None,
+            }),
+            _ => Err(unknown_infix_operator(operator, &left, &right)),
+        },
+    }
+}
+
+fn eval_integer_infix_expr(operator: &Token, l: i64, r: i64) -> error::Result<Object> {
+    let result = match operator {
+        Token::PLUS => l.checked_add(r),
+        Token::MINUS => l.checked_sub(r),
+        Token::ASTERISK => l.checked_mul(r),
+        Token::SLASH => {
+            if r == 0 {
+                return Err(division_by_zero_err());
+            }
+            l.checked_div(r)
+        }
+        // The remainder takes the sign of the dividend, as in C
+        Token::PERCENT => {
+            if r == 0 {
+                return Err(division_by_zero_err());
+            }
+            l.checked_rem(r)
+        }
+        Token::POWER => {
+            if r < 0 {
+                return Err(Error::EvalErr {
+                    msg: format!("negative exponent: {} ** {}", l, r),
+                    span: None,
+                });
+            }
+            u32::try_from(r).ok().and_then(|r| l.checked_pow(r))
+        }
+        Token::BITAND => Some(l & r),
+        Token::BITOR => Some(l | r),
+        Token::BITXOR => Some(l ^ r),
+        // Bits shifted out are dropped, and >> keeps the sign
+        Token::SHL | Token::SHR => {
+            let shift = u32::try_from(r)
+                .ok()
+                .filter(|shift| *shift < i64::BITS)
+                .ok_or_else(|| Error::EvalErr {
+                    msg: format!("shift amount out of range: {} {} {}", l, operator, r),
+                    span: None,
+                })?;
+            if operator == &Token::SHL {
+                Some(l << shift)
+            } else {
+                Some(l >> shift)
+            }
+        }
+        Token::LT => return Ok(Object::Boolean(l < r)),
+        Token::GT => return Ok(Object::Boolean(l > r)),
+        Token::LTEQ => return Ok(Object::Boolean(l <= r)),
+        Token::GTEQ => return Ok(Object::Boolean(l >= r)),
+        Token::EQ => return Ok(Object::Boolean(l == r)),
+        Token::NOTEQ => return Ok(Object::Boolean(l != r)),
+        _ => {
+            return Err(unknown_infix_operator(
+                operator,
+                &Object::Integer(l),
+                &Object::Integer(r),
+            ))
+        }
+    };
+    result.map(Object::Integer).ok_or_else(overflow_err)
+}
+
+fn eval_float_infix_expr(operator: &Token, l: f64, r: f64) -> error::Result<Object> {
+    let result = match operator {
+        Token::PLUS => l + r,
+        Token::MINUS => l - r,
+        Token::ASTERISK => l * r,
+        Token::SLASH => {
+            if r == 0.0 {
+                return Err(division_by_zero_err());
+            }
+            l / r
+        }
+        Token::PERCENT => {
+            if r == 0.0 {
+                return Err(division_by_zero_err());
+            }
+            l % r
+        }
+        Token::POWER => l.powf(r),
+        Token::LT => return Ok(Object::Boolean(l < r)),
+        Token::GT => return Ok(Object::Boolean(l > r)),
+        Token::LTEQ => return Ok(Object::Boolean(l <= r)),
+        Token::GTEQ => return Ok(Object::Boolean(l >= r)),
+        Token::EQ => return Ok(Object::Boolean(l == r)),
+        Token::NOTEQ => return Ok(Object::Boolean(l != r)),
+        _ => {
+            return Err(unknown_infix_operator(
+                operator,
+                &Object::Float(l),
+                &Object::Float(r),
+            ))
+        }
+    };
+    Ok(Object::Float(result))
+}
+
+fn eval_complex_infix_expr(
+    operator: &Token,
+    (r1, i1): (f64, f64),
+    (r2, i2): (f64, f64),
+) -> error::Result<Object> {
+    let (re, im) = match operator {
+        Token::PLUS => (r1 + r2, i1 + i2),
+        Token::MINUS => (r1 - r2, i1 - i2),
+        Token::ASTERISK => complex_mul((r1, i1), (r2, i2)),
+        Token::SLASH => complex_div((r1, i1), (r2, i2))?,
+        Token::POWER => complex_pow((r1, i1), (r2, i2))?,
+        Token::EQ => return Ok(Object::Boolean(r1 == r2 && i1 == i2)),
+        Token::NOTEQ => return Ok(Object::Boolean(r1 != r2 || i1 != i2)),
+        _ => {
+            return Err(unknown_infix_operator(
+                operator,
+                &Object::Complex { re: r1, im: i1 },
+                &Object::Complex { re: r2, im: i2 },
+            ))
+        }
+    };
+    Ok(Object::Complex { re, im })
+}
+
+fn complex_mul((r1, i1): (f64, f64), (r2, i2): (f64, f64)) -> (f64, f64) {
+    (r1 * r2 - i1 * i2, r1 * i2 + i1 * r2)
+}
+
+fn complex_div((r1, i1): (f64, f64), (r2, i2): (f64, f64)) -> error::Result<(f64, f64)> {
+    let norm = r2 * r2 + i2 * i2;
+    if norm == 0.0 {
+        return Err(division_by_zero_err());
+    }
+    Ok(((r1 * r2 + i1 * i2) / norm, (i1 * r2 - r1 * i2) / norm))
+}
+
+// Whole exponents are multiplied out, so that (1 + 2i) ** 2 is exactly
+// -3 + 4i. Others go by the principal value of exp(w ln z).
+fn complex_pow(base: (f64, f64), (r, i): (f64, f64)) -> error::Result<(f64, f64)> {
+    if i == 0.0 && r.fract() == 0.0 && r.abs() <= f64::from(u16::MAX) {
+        let (mut result, mut square, mut n) = ((1.0, 0.0), base, r.abs() as u32);
+        while n > 0 {
+            if n & 1 == 1 {
+                result = complex_mul(result, square);
+            }
+            square = complex_mul(square, square);
+            n >>= 1;
+        }
+        return if r < 0.0 {
+            complex_div((1.0, 0.0), result)
+        } else {
+            Ok(result)
+        };
+    }
+    if base == (0.0, 0.0) {
+        return if r > 0.0 {
+            Ok((0.0, 0.0))
+        } else {
+            Err(division_by_zero_err())
+        };
+    }
+    let ln_abs = base.0.hypot(base.1).ln();
+    let arg = base.1.atan2(base.0);
+    let (re, im) = (r * ln_abs - i * arg, i * ln_abs + r * arg);
+    Ok((re.exp() * im.cos(), re.exp() * im.sin()))
+}
+
+fn eval_string_infix_expr(operator: &Token, l: String, r: String) -> error::Result<Object> {
+    match operator {
+        Token::PLUS => Ok(Object::String(l + &r)),
+        Token::EQ => Ok(Object::Boolean(l == r)),
+        Token::NOTEQ => Ok(Object::Boolean(l != r)),
+        _ => Err(unknown_infix_operator(
+            operator,
+            &Object::String(l),
+            &Object::String(r),
+        )),
+    }
+}
+
+pub fn eval_index_expr(left: Object, index: Object) -> error::Result<Object> {
+    match (left, index) {
+        (Object::Array(elements), Object::Integer(idx)) => Ok(usize::try_from(idx)
+            .ok()
+            .and_then(|idx| elements.get(idx).cloned())
+            .unwrap_or(Object::Null)),
+        (range @ Object::Range { .. }, Object::Integer(idx)) => Ok(usize::try_from(idx)
+            .ok()
+            .and_then(|idx| iteration_nth(&range, idx))
+            .unwrap_or(Object::Null)),
+        (
+            container,
+            Object::Range {
+                start,
+                end,
+                inclusive,
+            },
+        ) => eval_slice(
+            container,
+            Object::Integer(start),
+            Object::Integer(end),
+            inclusive,
+        ),
+        (Object::Hash(pairs), key) => Ok(pairs
+            .get(&key.to_hash_key()?)
+            .cloned()
+            .unwrap_or(Object::Null)),
+        (left, _) => Err(Error::EvalErr {
+            msg: format!("index operator not supported: {}", left.type_name()),
+            span: None,
+        }),
+    }
+}
+
+thread_local! {
+    // How many function calls are being evaluated
+    static CALL_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+// Counts a call for as long as it is alive. As in the vm, where the program
+// takes a frame too, the number of nested calls is bounded, so that deep
+// recursion is an error rather than an overflow of the native stack.
+struct CallDepth;
+
+impl CallDepth {
+    fn enter() -> error::Result<Self> {
+        CALL_DEPTH.with(|depth| {
+            if depth.get() + 1 >= vm::MAX_FRAMES {
+                return Err(vm::call_depth_err());
+            }
+            depth.set(depth.get() + 1);
+            Ok(CallDepth)
+        })
+    }
+}
+
+impl Drop for CallDepth {
+    fn drop(&mut self) {
+        CALL_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+fn apply_function(
+    function: Object,
+    arguments: Vec<Object>,
+    keywords: &[String],
+) -> error::Result<Object> {
+    match function {
+        Object::Function {
+            name,
+            parameters,
+            body,
+            env,
+        } => {
+            let _depth = CallDepth::enter()?;
+            let slots = bind_arguments(name.as_deref(), &parameters, arguments, keywords)?;
+            let extended_env = Environment::new_enclosed(&env);
+            // Parameters without an argument are null until their default
+            // is evaluated, which may use the parameters before it
+            let mut missing = Vec::new();
+            for (i, (param, slot)) in parameters.bound_names().zip(slots).enumerate() {
+                if slot.is_none() {
+                    missing.push(i);
+                }
+                extended_env
+                    .borrow_mut()
+                    .set(param, slot.unwrap_or(Object::Null));
+            }
+            for i in missing {
+                let default = &parameters.defaults[i - parameters.required()];
+                let value = match eval_expression(default, &extended_env)? {
+                    Object::ReturnValue(value) => return Ok(*value),
+                    value => value,
+                };
+                extended_env.borrow_mut().set(&parameters.names[i], value);
+            }
+            match eval_block_statement(&body, &extended_env)? {
+                Object::ReturnValue(value) => Ok(*value),
+                obj => Ok(obj),
+            }
+        }
+        Object::Builtin { name, .. } if !keywords.is_empty() => Err(Error::EvalErr {
+            msg: format!("builtin function {} takes no arguments by name", name),
+            span: None,
+        }),
+        Object::Builtin { func, .. } => func(arguments),
+        other => Err(Error::EvalErr {
+            msg: format!("not a function: {}", other.type_name()),
+            span: None,
+        }),
+    }
+}
+
+// The value of each parameter in the order of Parameters::bound_names, and
+// None for those which take their default. The last keywords.len()
+// arguments are given by name.
+pub fn bind_arguments(
+    name: Option<&str>,
+    parameters: &Parameters,
+    mut arguments: Vec<Object>,
+    keywords: &[String],
+) -> error::Result<Vec<Option<Object>>> {
+    let call_err = |msg: String| Error::EvalErr {
+        msg: format!("{} in call to {}", msg, object::signature(name, parameters)),
+        span: None,
+    };
+    let named = arguments.split_off(arguments.len() - keywords.len());
+    let (count, required) = (parameters.names.len(), parameters.required());
+    let too_many = arguments.len() > count && parameters.rest.is_none();
+    if too_many || (keywords.is_empty() && arguments.len() < required) {
+        let want = match (&parameters.rest, required == count) {
+            (Some(_), _) => format!("at least {}", required),
+            (None, true) => count.to_string(),
+            (None, false) => format!("{} to {}", required, count),
+        };
+        return Err(Error::EvalErr {
+            msg: format!(
+                "wrong number of arguments to {}: want={}, got={}",
+                object::signature(name, parameters),
+                want,
+                arguments.len() + named.len()
+            ),
+            span: None,
+        });
+    }
+
+    let rest = arguments.split_off(arguments.len().min(count));
+    let mut slots: Vec<Option<Object>> = arguments.into_iter().map(Some).collect();
+    slots.resize(count, None);
+    for (keyword, argument) in keywords.iter().zip(named) {
+        let i = parameters
+            .names
+            .iter()
+            .position(|name| name == keyword)
+            .ok_or_else(|| call_err(format!("unknown parameter {}", keyword)))?;
+        if slots[i].is_some() {
+            return Err(call_err(format!("argument {} given twice", keyword)));
+        }
+        slots[i] = Some(argument);
+    }
+    if let Some(i) = slots[..required].iter().position(Option::is_none) {
+        return Err(call_err(format!(
+            "missing argument for {}",
+            parameters.names[i]
+        )));
+    }
+    if parameters.rest.is_some() {
+        slots.push(Some(Object::Array(rest)));
+    }
+    Ok(slots)
+}
+
+fn unknown_infix_operator(operator: &Token, left: &Object, right: &Object) -> Error {
+    Error::EvalErr {
+        msg: format!(
+            "unknown operator: {} {} {}",
+            left.type_name(),
+            operator,
+            right.type_name()
+        ),
+        span: None,
+    }
+}
+
+fn division_by_zero_err() -> Error {
+    Error::EvalErr {
+        msg: "division by zero".to_string(),
+        span: None,
+    }
+}
+
+fn overflow_err() -> Error {
+    Error::EvalErr {
+        msg: "integer overflow".to_string(),
+        span: None,
+    }
+}
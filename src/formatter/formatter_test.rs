@@ -0,0 +1,237 @@
+use super::*;
+use crate::error;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+fn parse(input: &str) -> error::Result<Program> {
+    Parser::new(Lexer::new(input)).parse_program()
+}
+
+fn format(input: &str) -> error::Result<String> {
+    Ok(format_program(&parse(input)?, input))
+}
+
+#[test]
+fn format_canonical_layout() -> error::Result<()> {
+    let input = r#"let   add=fn(x,y){x+y;};
+let h = {"a":[1,2], "b" : fn(){}};
+
+
+if(add(1,2)>2){ puts("big") } else {let z = 1; z}
+return add(1,2)"#;
+    let expected = r#"let add = fn(x, y) {
+    x + y
+};
+let h = {"a": [1, 2], "b": fn() {}};
+
+if (add(1, 2) > 2) {
+    puts("big")
+} else {
+    let z = 1;
+    z
+}
+return add(1, 2);
+"#;
+    assert_eq!(expected, format(input)?);
+    Ok(())
+}
+
+#[test]
+fn format_minimal_parentheses() -> error::Result<()> {
+    let cases = &[
+        ("((1 + 2)) * 3", "(1 + 2) * 3"),
+        ("1 + (2 * 3)", "1 + 2 * 3"),
+        ("(1 - 2) - 3", "1 - 2 - 3"),
+        ("1 - (2 - 3)", "1 - (2 - 3)"),
+        ("(2 ** 3) ** 2", "2 ** 3 ** 2"),
+        ("-(a + b)", "-(a + b)"),
+        ("(-a) * b", "-a * b"),
+        ("!(-a)", "!(-a)"),
+        ("(a + b)(1)", "(a + b)(1)"),
+        ("(f(1))[0]", "f(1)[0]"),
+        ("(-a)[0]", "(-a)[0]"),
+        ("(a < b) == (b > c)", "a < b == b > c"),
+        ("1 + (2i)", "1 + 2i"),
+        ("a || (b && c)", "a || b && c"),
+        ("(a || b) && c", "(a || b) && c"),
+        ("(a <= b) && ((c % 2) >= 0)", "a <= b && c % 2 >= 0"),
+        ("(a + b) % c", "(a + b) % c"),
+        ("a | (b ^ (c & d))", "a | b ^ c & d"),
+        ("(a & b) == c", "(a & b) == c"),
+        ("a & (b == c)", "a & b == c"),
+        ("(a << 1) + (2 >> b)", "(a << 1) + (2 >> b)"),
+        ("a << (1 + 2) < b", "a << 1 + 2 < b"),
+        ("~(a & b)", "~(a & b)"),
+        ("a = (b += 1 + 2)", "a = b += 1 + 2"),
+        ("(a = 1) + 2", "(a = 1) + 2"),
+        ("0 .. (n + 1)", "0..n + 1"),
+        ("(0..n) == r", "(0..n) == r"),
+        ("((1..2))..=3", "(1..2)..=3"),
+        ("a[(i)..][..=(j)]", "a[i..][..=j]"),
+        ("x * (3 - 4i)", "x * (3 - 4i)"),
+        ("fn(x) { x }(1)", "fn(x) {\n    x\n}(1)"),
+        ("f(1,y:(2),z : 3)", "f(1, y: 2, z: 3)"),
+        ("fn(x=(1 + 2),..r) {}", "fn(x = 1 + 2, ..r) {}"),
+        ("x=>(x + 1)", "|x| x + 1"),
+        ("(|x| x)(1) + (|| 2)()", "(|x| x)(1) + (|| 2)()"),
+        ("(a + b) |> f(|x| (x |> g()))", "a + b |> f(|x| x |> g())"),
+        ("((a |> f()) |> g())", "a |> f() |> g()"),
+        ("a |> (b |> f())()", "a |> (b |> f())()"),
+        ("(a |> f()) * 2", "(a |> f()) * 2"),
+        ("(a = 1) |> f()", "(a = 1) |> f()"),
+    ];
+    for (input, expected) in cases {
+        let program = parse(input)?;
+        assert_eq!(
+            *expected,
+            program.get_stmts()[0].to_string(),
+            "input: {}",
+            input
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn format_round_trip() -> error::Result<()> {
+    let inputs = &[
+        "let x = 1 + 2 * 3 - -4 / (5 - 6);",
+        "let f = fn(a, b) { let c = a[b]; return c ** 2; }; f([1, 2], 0)",
+        "if (x) { 1 } (2 + 3)",
+        "if (x) { 1 }; -3",
+        r#"{"key": {1: true, false: "v"}}["key"][1]"#,
+        "let c = 1 + 2i; c * (3 - 4i) + 5i - (1 - 1i)",
+        "let f = 2.75 * 2.0 - 1e-9 + 2.5i * 1e20",
+        "fn() { if (a) { if (b) { c } else { d } } }()",
+        r#"puts("a ${ x+1 } \${b} ${ {"k": "${y}"}["k"] }")"#,
+        "for (i in 0..=n - 1) { a[i..][..2] }",
+        "while (i < 3) { i += 1; if (i == 2) { continue } } for (x in [1]) { break; };",
+        r#"let [a, {"k": [b, ..]}, ..rest] = f(x); let {1: _, true: [-1, ..]} = y"#,
+        r#"match (f(x)) { [a, ..] if a => -1, {"k": [_, ..r]} => r, 2.5 => match (y) {}, _ => 0 }"#,
+        "let f = fn(x, y = [1, 2], ..rest) { g(0..2, y: x + 1) }; f(1, y: 2); fn(..r) {}",
+        "let f = |x, y = 1| x |> g(y) |> h(); (|| 1)(); a |> (x => x)(); (1 |> f())[0] + 1",
+        "let a = 1\nlet f = fn(x) {\n  x +\n    a\n}\nwhile (a) { break }\nf(a)\n  |> g()",
+    ];
+    for input in inputs {
+        let formatted = format(input)?;
+        assert_eq!(
+            parse(input)?.get_stmts(),
+            parse(&formatted)?.get_stmts(),
+            "formatted: {}",
+            formatted
+        );
+        assert_eq!(formatted, format(&formatted)?, "input: {}", input);
+    }
+    Ok(())
+}
+
+#[test]
+fn format_loops() -> error::Result<()> {
+    let input = "for(x in xs){if(x){break;}}\nwhile(true){continue};x";
+    let expected = "for (x in xs) {
+    if (x) {
+        break;
+    }
+}
+while (true) {
+    continue;
+}
+x;
+";
+    assert_eq!(expected, format(input)?);
+    Ok(())
+}
+
+#[test]
+fn format_match() -> error::Result<()> {
+    let input = r#"let f=fn(v){match(v){[a,..rest] if a>1=>a , {"k":[_ , ..]} => 2,-3=>3, _=>0}};"#;
+    let expected = r#"let f = fn(v) {
+    match (v) {
+        [a, ..rest] if a > 1 => a,
+        {"k": [_, ..]} => 2,
+        -3 => 3,
+        _ => 0,
+    }
+};
+"#;
+    assert_eq!(expected, format(input)?);
+    assert_eq!("match (x) {};\n", format("match(x){}")?);
+    Ok(())
+}
+
+#[test]
+fn format_keeps_comments() -> error::Result<()> {
+    let input = "// header
+let add = fn(x, y) { // trailing brace
+  // inside
+  x + /* odd place */ y // value
+  /* before the end */ };
+
+
+/* own line */ add(1, 2) // call
+// at the end";
+    let expected = "// header
+let add = fn(x, y) { // trailing brace
+    // inside
+    x + y /* odd place */ // value
+    /* before the end */
+};
+
+/* own line */
+add(1, 2); // call
+// at the end
+";
+    let formatted = format(input)?;
+    assert_eq!(expected, formatted);
+    assert_eq!(formatted, format(&formatted)?);
+    Ok(())
+}
+
+#[test]
+fn format_keeps_literals() -> error::Result<()> {
+    let input = "let n = 0xff + 0b1010 * 1_000_000 - 2.50;\n";
+    assert_eq!(input, format(input)?);
+    assert_eq!("1_000 * \"a\";\n", format("((1_000)) * (\"a\")")?);
+
+    let input = "let a = r\"\\d+\";\nlet b = \"\"\"two\nlines\"\"\";\nlet c = \"tab\\t\\u{41}\";\n";
+    assert_eq!(input, format(input)?);
+
+    let program = parse(r#"puts("a\"b\n")"#)?;
+    assert_eq!(r#"puts("a\"b\n")"#, program.get_stmts()[0].to_string());
+
+    let input = "let s = \"\"\"say \"${x}\"\n\\${y}\\\"\"\"\";\n";
+    assert_eq!(input, format(input)?);
+    Ok(())
+}
+
+#[test]
+fn format_without_semicolons() -> error::Result<()> {
+    let input = "let a = [1,\n  2]\nlet n = a\n  |> len()\nputs(n)\n";
+    let expected = "let a = [1, 2];\nlet n = a |> len();\nputs(n);\n";
+    assert_eq!(expected, format(input)?);
+    Ok(())
+}
+
+// The sugar is told by the flags of the nodes, not by where they are
+#[test]
+fn format_sugar_without_spans() {
+    let ident = |name: &str| Expression::from(ExprKind::Ident(name.to_string()));
+    let program = Program::new(vec![Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Call {
+            function: Box::new(ident("map")),
+            arguments: vec![
+                ident("xs"),
+                Expression::from(ExprKind::Function {
+                    parameters: Parameters::new(vec!["x".to_string()]),
+                    body: vec![Statement::from(StmtKind::ExpressionStmt {
+                        expression: ident("x"),
+                    })],
+                    shorthand: true,
+                }),
+            ],
+            keywords: vec![],
+            piped: true,
+        }),
+    })]);
+    assert_eq!("xs |> map(|x| x);\n", format_program(&program, ""));
+}
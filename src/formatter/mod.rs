@@ -0,0 +1,522 @@
+#[cfg(test)]
+mod formatter_test;
+
+use std::collections::BTreeMap;
+use std::mem;
+
+use crate::lexer::token::{self, Token};
+use crate::lexer::{Comment, Lexer};
+use crate::parser::ast::{
+    ExprKind, Expression, Parameters, Pattern, PatternKind, Precedence, Program, Statement,
+    StmtKind, StringPart,
+};
+
+const INDENT: &str = "    ";
+
+// Formats the program as canonical Monkey source. The source the program was
+// parsed from is used to keep its comments and the blank lines between
+// statements.
+pub fn format_program(program: &Program, source: &str) -> String {
+    let mut lexer = Lexer::new(source).with_comments();
+    lexer.by_ref().for_each(drop);
+
+    let mut printer = Printer::new(Some(source));
+    printer.comments = lexer.comments;
+    printer.stmts(program.get_stmts(), None);
+    printer.out
+}
+
+pub struct Printer<'a> {
+    pub out: String,
+    source: Option<&'a str>,
+    // Comments not printed yet, keyed by the start of the token they are
+    // attached to
+    comments: BTreeMap<usize, Vec<Comment>>,
+    indent: usize,
+}
+
+impl<'a> Printer<'a> {
+    pub fn new(source: Option<&'a str>) -> Self {
+        Self {
+            out: String::new(),
+            source,
+            comments: BTreeMap::new(),
+            indent: 0,
+        }
+    }
+
+    fn newline(&mut self) {
+        if !self.out.is_empty() {
+            self.out.push('\n');
+            self.out.push_str(&INDENT.repeat(self.indent));
+        }
+    }
+
+    // Comments inside an expression are attached to a token which starts no
+    // statement. They are printed along with the next statement instead, so
+    // that no comment is lost.
+    fn take_comments(&mut self, pos: usize) -> Vec<Comment> {
+        let rest = match pos.checked_add(1) {
+            Some(after) => self.comments.split_off(&after),
+            None => BTreeMap::new(),
+        };
+        mem::replace(&mut self.comments, rest)
+            .into_values()
+            .flatten()
+            .collect()
+    }
+
+    // Trailing comments stay at the end of the current line, the others get
+    // their own lines. Returns where the last printed comment ends.
+    fn comments_before(&mut self, pos: usize, mut prev_end: Option<usize>) -> Option<usize> {
+        for comment in self.take_comments(pos) {
+            if comment.trailing && !self.out.is_empty() {
+                self.out.push(' ');
+            } else {
+                if let Some(end) = prev_end {
+                    if self.blank_line_between(end, comment.span.start) {
+                        self.out.push('\n');
+                    }
+                }
+                self.newline();
+            }
+            self.out.push_str(&comment.text);
+            prev_end = Some(comment.span.end);
+        }
+        prev_end
+    }
+
+    // Every statement goes on its own line. In a block the last statement
+    // gives its value, so it goes without a semicolon. end is where the
+    // closing brace of the block is, if it is known.
+    fn stmts(&mut self, stmts: &[Statement], end: Option<usize>) {
+        let in_block = self.indent > 0;
+        let mut prev_end = None;
+        for (i, stmt) in stmts.iter().enumerate() {
+            prev_end = self.comments_before(stmt.span.start, prev_end);
+            if prev_end.is_some_and(|end| self.blank_line_between(end, stmt.span.start)) {
+                self.out.push('\n');
+            }
+            self.newline();
+            self.stmt(stmt);
+            prev_end = Some(stmt.span.end);
+
+            let next = stmts.get(i + 1);
+            let semicolon = match &stmt.kind {
+                StmtKind::ExpressionStmt { .. } if in_block && next.is_none() => false,
+                // Nothing follows a block, unless the next statement would
+                // continue the if expression as a call, an index or a minus
+                StmtKind::ExpressionStmt {
+                    expression:
+                        Expression {
+                            kind: ExprKind::IfExpr { .. },
+                            ..
+                        },
+                } => next.is_some_and(|next| next.to_string().starts_with(&['(', '[', '-'][..])),
+                StmtKind::WhileStmt { .. } | StmtKind::ForStmt { .. } => false,
+                _ => true,
+            };
+            if semicolon {
+                self.out.push(';');
+            }
+        }
+        match end {
+            Some(end) => {
+                self.comments_before(end, prev_end);
+            }
+            None if !in_block => {
+                self.comments_before(usize::MAX, prev_end);
+                if !self.out.is_empty() {
+                    self.out.push('\n');
+                }
+            }
+            None => {}
+        }
+    }
+
+    fn blank_line_between(&self, start: usize, end: usize) -> bool {
+        let between = match self.source.and_then(|source| source.get(start..end)) {
+            Some(between) => between,
+            None => return false,
+        };
+        let lines: Vec<&str> = between.split('\n').collect();
+        lines.len() > 2
+            && lines[1..lines.len() - 1]
+                .iter()
+                .any(|line| line.trim().is_empty())
+    }
+
+    pub fn stmt(&mut self, stmt: &Statement) {
+        match &stmt.kind {
+            StmtKind::LetStmt { name, value } => {
+                self.out.push_str(&format!("let {} = ", name));
+                self.expr(value);
+            }
+            StmtKind::LetPatternStmt { pattern, value } => {
+                self.out.push_str("let ");
+                self.pattern(pattern);
+                self.out.push_str(" = ");
+                self.expr(value);
+            }
+            StmtKind::ReturnStmt { value } => {
+                self.out.push_str("return ");
+                self.expr(value);
+            }
+            StmtKind::ExpressionStmt { expression } => self.expr(expression),
+            StmtKind::WhileStmt { condition, body } => {
+                self.out.push_str("while (");
+                self.expr(condition);
+                self.out.push_str(") ");
+                self.block(body, stmt.span.end.checked_sub(1));
+            }
+            StmtKind::ForStmt {
+                variable,
+                iterable,
+                body,
+            } => {
+                self.out.push_str(&format!("for ({} in ", variable));
+                self.expr(iterable);
+                self.out.push_str(") ");
+                self.block(body, stmt.span.end.checked_sub(1));
+            }
+            StmtKind::BreakStmt => self.out.push_str("break"),
+            StmtKind::ContinueStmt => self.out.push_str("continue"),
+        }
+    }
+
+    fn block(&mut self, block: &[Statement], end: Option<usize>) {
+        let has_comments = end.is_some_and(|end| self.comments.range(..=end).next().is_some());
+        if block.is_empty() && !has_comments {
+            self.out.push_str("{}");
+            return;
+        }
+        self.out.push('{');
+        self.indent += 1;
+        self.stmts(block, end);
+        self.indent -= 1;
+        self.newline();
+        self.out.push('}');
+    }
+
+    fn list(&mut self, exprs: &[Expression]) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expr(expr);
+        }
+    }
+
+    fn operand(&mut self, expr: &Expression, parens: bool) {
+        if parens {
+            self.out.push('(');
+            self.expr(expr);
+            self.out.push(')');
+        } else {
+            self.expr(expr);
+        }
+    }
+
+    // The source text of a literal. The span of a grouped expression takes
+    // in its parentheses, which are left out.
+    fn literal(&self, expr: &Expression) -> Option<&'a str> {
+        self.source
+            .and_then(|source| source.get(expr.span.start..expr.span.end))
+            .map(|text| text.trim_matches(|ch: char| ch == '(' || ch == ')' || ch.is_whitespace()))
+    }
+
+    pub fn expr(&mut self, expr: &Expression) {
+        match &expr.kind {
+            ExprKind::Ident(name) => self.out.push_str(name),
+            // Raw and multi-line strings, and numbers like 0xff or 1_000 are
+            // kept as they were written
+            ExprKind::String(s) => match self.literal(expr) {
+                Some(literal) => self.out.push_str(literal),
+                None => self.out.push_str(&format!("\"{}\"", token::escape_str(s))),
+            },
+            ExprKind::Interpolated(parts) => {
+                let triple = self
+                    .literal(expr)
+                    .is_some_and(|literal| literal.starts_with("\"\"\""));
+                let quote = if triple { "\"\"\"" } else { "\"" };
+                self.out.push_str(quote);
+                for (i, part) in parts.iter().enumerate() {
+                    match part {
+                        StringPart::Literal(s) if triple => {
+                            self.out.push_str(&escape_triple(s, i + 1 == parts.len()))
+                        }
+                        StringPart::Literal(s) => self.out.push_str(&token::escape_str(s)),
+                        StringPart::Expr(expr) => {
+                            self.out.push_str("${");
+                            self.expr(expr);
+                            self.out.push('}');
+                        }
+                    }
+                }
+                self.out.push_str(quote);
+            }
+            ExprKind::Boolean(b) => self.out.push_str(&b.to_string()),
+            ExprKind::Integer(n) => match self.literal(expr) {
+                Some(literal) => self.out.push_str(literal),
+                None => self.out.push_str(&n.to_string()),
+            },
+            ExprKind::Float(n) => match self.literal(expr) {
+                Some(literal) => self.out.push_str(literal),
+                None => self.out.push_str(&token::format_float(*n)),
+            },
+            ExprKind::Imaginary(im) => match self.literal(expr) {
+                Some(literal) => self.out.push_str(literal),
+                None => self.out.push_str(&format!("{}i", im)),
+            },
+            ExprKind::Array(elements) => {
+                self.out.push('[');
+                self.list(elements);
+                self.out.push(']');
+            }
+            ExprKind::Hash { key, value } => {
+                self.out.push('{');
+                for (i, (key, value)) in key.iter().zip(value).enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expr(key);
+                    self.out.push_str(": ");
+                    self.expr(value);
+                }
+                self.out.push('}');
+            }
+            ExprKind::Prefix { operator, right } => {
+                self.out.push_str(&operator.to_string());
+                self.operand(right, precedence(right) <= Precedence::PREFIX);
+            }
+            ExprKind::Infix {
+                left,
+                operator: Token::LBRACKET,
+                right,
+            } => {
+                self.operand(left, precedence(left) < Precedence::CALL);
+                self.out.push('[');
+                self.expr(right);
+                self.out.push(']');
+            }
+            ExprKind::Infix {
+                left,
+                operator,
+                right,
+            } => {
+                // Operators are left associative, so only the right operand
+                // needs parentheses on the same precedence
+                let prece = Precedence::take_precedence(operator);
+                self.operand(left, precedence(left) < prece);
+                self.out.push_str(&format!(" {} ", operator));
+                self.operand(right, precedence(right) <= prece);
+            }
+            // A range does not chain, so both bounds need parentheses on the
+            // same precedence
+            ExprKind::Range {
+                start,
+                end,
+                inclusive,
+            } => {
+                if let Some(start) = start {
+                    self.operand(start, precedence(start) <= Precedence::RANGE);
+                }
+                self.out.push_str(if *inclusive { "..=" } else { ".." });
+                if let Some(end) = end {
+                    self.operand(end, precedence(end) <= Precedence::RANGE);
+                }
+            }
+            ExprKind::Assign {
+                target,
+                operator,
+                value,
+            } => {
+                self.expr(target);
+                self.out.push_str(&format!(" {} ", operator));
+                self.expr(value);
+            }
+            ExprKind::IfExpr {
+                condition,
+                consequence,
+                alternative,
+            } => {
+                self.out.push_str("if (");
+                self.expr(condition);
+                self.out.push_str(") ");
+                let end = expr.span.end.checked_sub(1);
+                match alternative {
+                    Some(alternative) => {
+                        self.block(consequence, None);
+                        self.out.push_str(" else ");
+                        self.block(alternative, end);
+                    }
+                    None => self.block(consequence, end),
+                }
+            }
+            ExprKind::Function {
+                parameters,
+                body,
+                shorthand: true,
+            } => {
+                self.out.push('|');
+                self.parameters(parameters);
+                self.out.push_str("| ");
+                if let StmtKind::ExpressionStmt { expression } = &body[0].kind {
+                    self.expr(expression);
+                }
+            }
+            ExprKind::Function {
+                parameters, body, ..
+            } => {
+                self.out.push_str("fn(");
+                self.parameters(parameters);
+                self.out.push_str(") ");
+                self.block(body, expr.span.end.checked_sub(1));
+            }
+            ExprKind::Call {
+                function,
+                arguments,
+                keywords,
+                piped,
+            } => {
+                let piped = usize::from(*piped);
+                if piped == 1 {
+                    let left = &arguments[0];
+                    self.operand(left, precedence(left) < Precedence::PIPELINE);
+                    self.out.push_str(" |> ");
+                }
+                self.operand(function, precedence(function) < Precedence::CALL);
+                self.out.push('(');
+                let positional = arguments.len() - keywords.len();
+                self.list(&arguments[piped..positional]);
+                for (i, (keyword, argument)) in
+                    keywords.iter().zip(&arguments[positional..]).enumerate()
+                {
+                    if i + positional > piped {
+                        self.out.push_str(", ");
+                    }
+                    self.out.push_str(&format!("{}: ", keyword));
+                    self.expr(argument);
+                }
+                self.out.push(')');
+            }
+            // One arm per line, each with a trailing comma
+            ExprKind::Match { subject, arms } => {
+                self.out.push_str("match (");
+                self.expr(subject);
+                self.out.push_str(") {");
+                if !arms.is_empty() {
+                    self.indent += 1;
+                    for arm in arms {
+                        self.newline();
+                        self.pattern(&arm.pattern);
+                        if let Some(guard) = &arm.guard {
+                            self.out.push_str(" if ");
+                            self.expr(guard);
+                        }
+                        self.out.push_str(" => ");
+                        self.expr(&arm.body);
+                        self.out.push(',');
+                    }
+                    self.indent -= 1;
+                    self.newline();
+                }
+                self.out.push('}');
+            }
+        }
+    }
+
+    pub fn parameters(&mut self, parameters: &Parameters) {
+        let required = parameters.required();
+        for (i, name) in parameters.names.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.out.push_str(name);
+            if let Some(default) = i.checked_sub(required) {
+                self.out.push_str(" = ");
+                self.expr(&parameters.defaults[default]);
+            }
+        }
+        if let Some(rest) = &parameters.rest {
+            if !parameters.names.is_empty() {
+                self.out.push_str(", ");
+            }
+            self.out.push_str(&format!("..{}", rest));
+        }
+    }
+
+    pub fn pattern(&mut self, pattern: &Pattern) {
+        match &pattern.kind {
+            PatternKind::Wildcard => self.out.push('_'),
+            PatternKind::Binding(name) => self.out.push_str(name),
+            PatternKind::Literal(literal) => self.expr(literal),
+            PatternKind::Array { elements, rest } => {
+                self.out.push('[');
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.pattern(element);
+                }
+                if let Some(rest) = rest {
+                    if !elements.is_empty() {
+                        self.out.push_str(", ");
+                    }
+                    self.out.push_str("..");
+                    if let Some(name) = rest {
+                        self.out.push_str(name);
+                    }
+                }
+                self.out.push(']');
+            }
+            PatternKind::Hash { keys, values } => {
+                self.out.push('{');
+                for (i, (key, value)) in keys.iter().zip(values).enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expr(key);
+                    self.out.push_str(": ");
+                    self.pattern(value);
+                }
+                self.out.push('}');
+            }
+        }
+    }
+}
+
+// Line breaks and tabs stay as they are in a triple-quoted string. A quote
+// needs escaping only where it would close the string.
+fn escape_triple(s: &str, last: bool) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\n' | '\t' => escaped.push(ch),
+            '"' if chars.peek().map_or(last, |next| *next == '"') => escaped.push_str("\\\""),
+            '"' => escaped.push('"'),
+            '$' if chars.peek() == Some(&'{') => escaped.push_str("\\$"),
+            _ => escaped.push_str(&token::escape_str(&ch.to_string())),
+        }
+    }
+    escaped
+}
+
+// How tightly the printed expression holds together. Literals never need
+// parentheses.
+fn precedence(expr: &Expression) -> Precedence {
+    match &expr.kind {
+        // The body of a lambda takes in everything after it
+        ExprKind::Function {
+            shorthand: true, ..
+        } => Precedence::LOWEST,
+        ExprKind::Call { piped: true, .. } => Precedence::PIPELINE,
+        ExprKind::Infix { operator, .. } => Precedence::take_precedence(operator),
+        ExprKind::Prefix { .. } => Precedence::PREFIX,
+        ExprKind::Range { .. } => Precedence::RANGE,
+        ExprKind::Assign { .. } => Precedence::ASSIGN,
+        ExprKind::Call { .. } => Precedence::CALL,
+        _ => Precedence::INDEX,
+    }
+}
@@ -3,7 +3,9 @@ use super::*;
 #[test]
 fn test_next_token() {
     let input = "=+(){},;";
-    let lex = Lexer::new(&input).collect::<Vec<Token>>();
+    let lex = Lexer::new(input)
+        .map(|tok| tok.kind)
+        .collect::<Vec<Token>>();
     let expected = vec![
         Token::ASSIGN,
         Token::PLUS,
@@ -26,7 +28,7 @@ fn more_complex_lex() {
         x + y;
     };
     let result = add(five, ten);
-    !-/*5;
+    !-/ *5;
     5 < 10 > 5;
     10 ** 10;
     
@@ -44,7 +46,9 @@ fn more_complex_lex() {
     [1,2];
     { "foo": "bar" };
     "#;
-    let lex = Lexer::new(&input).collect::<Vec<Token>>();
+    let lex = Lexer::new(input)
+        .map(|tok| tok.kind)
+        .collect::<Vec<Token>>();
     let expected = vec![
         Token::LET,
         Token::IDENT("five".to_string()),
@@ -115,6 +119,7 @@ fn more_complex_lex() {
         Token::FALSE,
         Token::SEMICOLON,
         Token::RBRACE,
+        Token::NEWLINE,
         Token::INT(10),
         Token::EQ,
         Token::INT(10),
@@ -125,7 +130,7 @@ fn more_complex_lex() {
         Token::SEMICOLON,
         Token::INT(1),
         Token::PLUS,
-        Token::IMEGINARY(2),
+        Token::IMEGINARY(2.0),
         Token::SEMICOLON,
         Token::STRING("foobar".to_string()),
         Token::SEMICOLON,
@@ -147,3 +152,520 @@ fn more_complex_lex() {
     ];
     assert_eq!(lex, expected);
 }
+
+#[test]
+fn token_spans() {
+    let input = "let five = 5;\n  five ** 10;\n\"str\"";
+    let spans = Lexer::new(input)
+        .map(|tok| {
+            let span = tok.span;
+            (tok.kind, span.start, span.end, span.line, span.column)
+        })
+        .collect::<Vec<_>>();
+    let expected = vec![
+        (Token::LET, 0, 3, 1, 1),
+        (Token::IDENT("five".to_string()), 4, 8, 1, 5),
+        (Token::ASSIGN, 9, 10, 1, 10),
+        (Token::INT(5), 11, 12, 1, 12),
+        (Token::SEMICOLON, 12, 13, 1, 13),
+        (Token::IDENT("five".to_string()), 16, 20, 2, 3),
+        (Token::POWER, 21, 23, 2, 8),
+        (Token::INT(10), 24, 26, 2, 11),
+        (Token::SEMICOLON, 26, 27, 2, 13),
+        (Token::STRING("str".to_string()), 28, 33, 3, 1),
+        (Token::EOF, 33, 33, 3, 6),
+    ];
+    assert_eq!(spans, expected);
+}
+
+#[test]
+fn unicode_input() {
+    let input = "let 변수 = \"안녕, 世界 😀\";\n변수 + π_2";
+    let spans = Lexer::new(input)
+        .map(|tok| {
+            let span = tok.span;
+            (tok.kind, span.start, span.end, span.line, span.column)
+        })
+        .collect::<Vec<_>>();
+    let expected = vec![
+        (Token::LET, 0, 3, 1, 1),
+        (Token::IDENT("변수".to_string()), 4, 10, 1, 5),
+        (Token::ASSIGN, 11, 12, 1, 8),
+        (Token::STRING("안녕, 世界 😀".to_string()), 13, 34, 1, 10),
+        (Token::SEMICOLON, 34, 35, 1, 20),
+        (Token::IDENT("변수".to_string()), 36, 42, 2, 1),
+        (Token::PLUS, 43, 44, 2, 4),
+        (Token::IDENT("π_2".to_string()), 45, 49, 2, 6),
+        (Token::EOF, 49, 49, 2, 9),
+    ];
+    assert_eq!(spans, expected);
+
+    let lex = Lexer::new("x😀").map(|tok| tok.kind).collect::<Vec<_>>();
+    let expected = vec![Token::IDENT("x".to_string()), Token::ILLIGAL, Token::EOF];
+    assert_eq!(lex, expected);
+}
+
+#[test]
+fn skip_comments() {
+    let input = "let // a line comment
+    x = /* a /* nested */ block */ 1; // end
+    /**/ x";
+    let lex = Lexer::new(input)
+        .map(|tok| tok.kind)
+        .collect::<Vec<Token>>();
+    let expected = vec![
+        Token::LET,
+        Token::IDENT("x".to_string()),
+        Token::ASSIGN,
+        Token::INT(1),
+        Token::SEMICOLON,
+        Token::IDENT("x".to_string()),
+        Token::EOF,
+    ];
+    assert_eq!(expected, lex);
+}
+
+#[test]
+fn keep_comments() {
+    let input = "// head\nlet x = 1; // tail\n/* a */ /* b */ x";
+    let mut lex = Lexer::new(input).with_comments();
+    let toks: Vec<_> = lex.by_ref().collect();
+    let comments: Vec<_> = lex
+        .comments
+        .iter()
+        .map(|(start, comments)| {
+            let texts: Vec<_> = comments
+                .iter()
+                .map(|comment| (comment.text.as_str(), comment.trailing))
+                .collect();
+            (*start, texts)
+        })
+        .collect();
+    let expected = vec![
+        (toks[0].span.start, vec![("// head", false)]),
+        (
+            toks[5].span.start,
+            vec![("// tail", true), ("/* a */", false), ("/* b */", false)],
+        ),
+    ];
+    assert_eq!(expected, comments);
+
+    let mut lex = Lexer::new(input);
+    lex.by_ref().for_each(drop);
+    assert!(lex.comments.is_empty());
+}
+
+#[test]
+fn unterminated_block_comment() {
+    let mut lex = Lexer::new("1 /* a /* b */\n2");
+    let toks: Vec<_> = lex.by_ref().map(|tok| tok.kind).collect();
+    assert_eq!(vec![Token::INT(1), Token::EOF], toks);
+    match lex.errors.as_slice() {
+        [MonkeyErr::LexErr { kind, span }] => {
+            assert_eq!(&LexErrKind::UnterminatedBlockComment, kind);
+            assert_eq!((1, 3), (span.line, span.column));
+        }
+        other => panic!("expected one lexing error, got {:?}", other),
+    }
+}
+
+#[test]
+fn string_escapes() {
+    let cases = &[
+        (r#""a\"b""#, "a\"b"),
+        (r#""tab\tnew\nline\r\0""#, "tab\tnew\nline\r\0"),
+        (r#""back\\slash""#, "back\\slash"),
+        (r#""\u{48}\u{c548}\u{1F600}""#, "H안😀"),
+        (r#"r"C:\dir\n""#, "C:\\dir\\n"),
+        ("\"\"\"a \"quoted\"\n\\tline\"\"\"", "a \"quoted\"\n\tline"),
+        ("r\"\"\"\\d+\n\"\"\"", "\\d+\n"),
+        (r#""""#, ""),
+    ];
+    for (input, expected) in cases {
+        let mut lex = Lexer::new(input);
+        let toks: Vec<_> = lex.by_ref().map(|tok| tok.kind).collect();
+        assert_eq!(
+            vec![Token::STRING(expected.to_string()), Token::EOF],
+            toks,
+            "input: {}",
+            input
+        );
+        assert!(lex.errors.is_empty(), "input: {}", input);
+    }
+}
+
+#[test]
+fn string_errors() {
+    let cases = &[
+        (r#""a\qb""#, "ab", "unknown escape sequence `\\q`", 3),
+        (r#""\u41""#, "41", "expected `{` after `\\u`", 2),
+        (r#""\u{41""#, "", "unterminated unicode escape", 2),
+        (
+            r#""\u{}""#,
+            "",
+            "unicode escape must have 1 to 6 hex digits",
+            2,
+        ),
+        (
+            r#""\u{d800}""#,
+            "",
+            "invalid unicode character `\\u{d800}`",
+            2,
+        ),
+        ("x + \"abc", "abc", "unterminated string", 5),
+        ("\"\"\"abc\"\"", "abc\"\"", "unterminated string", 1),
+    ];
+    for (input, value, msg, column) in cases {
+        let mut lex = Lexer::new(input);
+        let toks: Vec<_> = lex.by_ref().map(|tok| tok.kind).collect();
+        assert!(
+            toks.contains(&Token::STRING(value.to_string())),
+            "input: {}, got {:?}",
+            input,
+            toks
+        );
+        assert_eq!(Some(&Token::EOF), toks.last(), "input: {}", input);
+        match lex.errors.as_slice() {
+            [MonkeyErr::LexErr { kind, span }] => {
+                assert_eq!(msg, &kind.to_string(), "input: {}", input);
+                assert_eq!(*column, span.column, "input: {}", input);
+            }
+            other => panic!("expected one lexing error for {}, got {:?}", input, other),
+        }
+    }
+}
+
+#[test]
+fn string_interpolation() {
+    let input = r#""a${x}b${ {"k": "v${y}"}["k"] }" "\${no} $" r"${raw}""#;
+    let toks: Vec<_> = Lexer::new(input).map(|tok| tok.kind).collect();
+    assert_eq!(
+        vec![
+            Token::INTERP_START("a".to_string()),
+            Token::IDENT("x".to_string()),
+            Token::INTERP_MID("b".to_string()),
+            Token::LBRACE,
+            Token::STRING("k".to_string()),
+            Token::COLON,
+            Token::INTERP_START("v".to_string()),
+            Token::IDENT("y".to_string()),
+            Token::INTERP_END("".to_string()),
+            Token::RBRACE,
+            Token::LBRACKET,
+            Token::STRING("k".to_string()),
+            Token::RBRACKET,
+            Token::INTERP_END("".to_string()),
+            Token::STRING("${no} $".to_string()),
+            Token::STRING("${raw}".to_string()),
+            Token::EOF,
+        ],
+        toks
+    );
+
+    let mut lex = Lexer::new("\"a${x");
+    lex.by_ref().for_each(drop);
+    match lex.errors.as_slice() {
+        [MonkeyErr::LexErr { kind, span }] => {
+            assert_eq!(&LexErrKind::UnterminatedString, kind);
+            assert_eq!(1, span.column);
+        }
+        other => panic!("expected one lexing error, got {:?}", other),
+    }
+}
+
+#[test]
+fn number_literals() {
+    let cases: &[(&str, &[Token])] = &[
+        ("2.75", &[Token::FLOAT(2.75)]),
+        (
+            "1e-9 2E+3 1e5",
+            &[Token::FLOAT(1e-9), Token::FLOAT(2e3), Token::FLOAT(1e5)],
+        ),
+        ("2.5e3", &[Token::FLOAT(2500.0)]),
+        (
+            "0xff 0XA_b 0o17 0b1010_1010",
+            &[
+                Token::INT(255),
+                Token::INT(171),
+                Token::INT(15),
+                Token::INT(170),
+            ],
+        ),
+        (
+            "1_000_000 1_0.2_5",
+            &[Token::INT(1_000_000), Token::FLOAT(10.25)],
+        ),
+        ("9223372036854775807", &[Token::INT(i64::MAX)]),
+        ("2.5i 3i", &[Token::IMEGINARY(2.5), Token::IMEGINARY(3.0)]),
+        ("1e", &[Token::INT(1), Token::IDENT("e".to_string())]),
+        (
+            "1.x",
+            &[Token::INT(1), Token::ILLIGAL, Token::IDENT("x".to_string())],
+        ),
+    ];
+    for (input, expected) in cases {
+        let mut lex = Lexer::new(input);
+        let toks: Vec<_> = lex.by_ref().map(|tok| tok.kind).collect();
+        assert_eq!(expected, &&toks[..toks.len() - 1], "input: {}", input);
+        assert!(lex.errors.is_empty(), "input: {}", input);
+    }
+
+    let mut lex = Lexer::new("x + .5");
+    let toks: Vec<_> = lex.by_ref().map(|tok| tok.kind).collect();
+    assert_eq!(Token::FLOAT(0.5), toks[2]);
+    match lex.errors.as_slice() {
+        [MonkeyErr::LexErr { kind, span }] => {
+            assert_eq!(&LexErrKind::LeadingPoint, kind);
+            assert_eq!((5, 2), (span.column, span.end - span.start));
+        }
+        other => panic!("expected one lexing error, got {:?}", other),
+    }
+}
+
+#[test]
+fn number_errors() {
+    let cases = &[
+        ("99999999999999999999", "integer literal is too large"),
+        ("0x8000000000000000", "integer literal is too large"),
+        ("1e999", "float literal is too large"),
+        ("2e400i", "float literal is too large"),
+        ("0b102", "invalid digit `2` in binary literal"),
+        ("0o8", "invalid digit `8` in octal literal"),
+        ("0x_", "hexadecimal literal has no digits"),
+    ];
+    for (input, msg) in cases {
+        let mut lex = Lexer::new(input);
+        let toks: Vec<_> = lex.by_ref().map(|tok| tok.kind).collect();
+        assert_eq!(2, toks.len(), "input: {}, got {:?}", input, toks);
+        match lex.errors.as_slice() {
+            [MonkeyErr::LexErr { kind, span }] => {
+                assert_eq!(msg, &kind.to_string(), "input: {}", input);
+                assert_eq!(input.len(), span.end - span.start, "input: {}", input);
+            }
+            other => panic!("expected one lexing error for {}, got {:?}", input, other),
+        }
+    }
+}
+
+#[test]
+fn comparison_and_logical_operators() {
+    let toks: Vec<_> = Lexer::new("a <= b >= c && d || e % f < g > h")
+        .map(|tok| tok.kind)
+        .collect();
+    let ident = |name: &str| Token::IDENT(name.to_string());
+    assert_eq!(
+        vec![
+            ident("a"),
+            Token::LTEQ,
+            ident("b"),
+            Token::GTEQ,
+            ident("c"),
+            Token::AND,
+            ident("d"),
+            Token::OR,
+            ident("e"),
+            Token::PERCENT,
+            ident("f"),
+            Token::LT,
+            ident("g"),
+            Token::GT,
+            ident("h"),
+            Token::EOF,
+        ],
+        toks
+    );
+}
+
+#[test]
+fn bitwise_operators() {
+    let toks: Vec<_> = Lexer::new("<< < <= >> > >= & && | || |> ^ ~")
+        .map(|tok| tok.kind)
+        .collect();
+    assert_eq!(
+        vec![
+            Token::SHL,
+            Token::LT,
+            Token::LTEQ,
+            Token::SHR,
+            Token::GT,
+            Token::GTEQ,
+            Token::BITAND,
+            Token::AND,
+            Token::BITOR,
+            Token::OR,
+            Token::PIPELINE,
+            Token::BITXOR,
+            Token::BITNOT,
+            Token::EOF,
+        ],
+        toks
+    );
+}
+
+#[test]
+fn assignment_operators() {
+    let toks: Vec<_> = Lexer::new("= == += -= *= /= ** -")
+        .map(|tok| tok.kind)
+        .collect();
+    assert_eq!(
+        vec![
+            Token::ASSIGN,
+            Token::EQ,
+            Token::PLUSEQ,
+            Token::MINUSEQ,
+            Token::ASTERISKEQ,
+            Token::SLASHEQ,
+            Token::POWER,
+            Token::MINUS,
+            Token::EOF,
+        ],
+        toks
+    );
+}
+
+#[test]
+fn loop_keywords() {
+    let toks: Vec<_> = Lexer::new("while for in break continue forever input")
+        .map(|tok| tok.kind)
+        .collect();
+    assert_eq!(
+        vec![
+            Token::WHILE,
+            Token::FOR,
+            Token::IN,
+            Token::BREAK,
+            Token::CONTINUE,
+            Token::IDENT("forever".to_string()),
+            Token::IDENT("input".to_string()),
+            Token::EOF,
+        ],
+        toks
+    );
+}
+
+#[test]
+fn range_operators() {
+    let toks: Vec<_> = Lexer::new("1..5 0..=n 1.5..2 a[..]")
+        .map(|tok| tok.kind)
+        .collect();
+    assert_eq!(
+        vec![
+            Token::INT(1),
+            Token::DOTDOT,
+            Token::INT(5),
+            Token::INT(0),
+            Token::DOTDOTEQ,
+            Token::IDENT("n".to_string()),
+            Token::FLOAT(1.5),
+            Token::DOTDOT,
+            Token::INT(2),
+            Token::IDENT("a".to_string()),
+            Token::LBRACKET,
+            Token::DOTDOT,
+            Token::RBRACKET,
+            Token::EOF,
+        ],
+        toks
+    );
+}
+
+#[test]
+fn match_tokens() {
+    let toks: Vec<_> = Lexer::new("match (x) { _ => a == b, }")
+        .map(|tok| tok.kind)
+        .collect();
+    assert_eq!(
+        vec![
+            Token::MATCH,
+            Token::LPAREN,
+            Token::IDENT("x".to_string()),
+            Token::RPAREN,
+            Token::LBRACE,
+            Token::IDENT("_".to_string()),
+            Token::FATARROW,
+            Token::IDENT("a".to_string()),
+            Token::EQ,
+            Token::IDENT("b".to_string()),
+            Token::COMMA,
+            Token::RBRACE,
+            Token::EOF,
+        ],
+        toks
+    );
+}
+
+#[test]
+fn line_breaks_end_statements() {
+    let input = "let x = 1
+let y = x +
+  2
+f(1,
+  2)
+return [x,
+  y]
+  |> g()
+";
+    let toks: Vec<_> = Lexer::new(input).map(|tok| tok.kind).collect();
+    assert_eq!(
+        vec![
+            Token::LET,
+            Token::IDENT("x".to_string()),
+            Token::ASSIGN,
+            Token::INT(1),
+            Token::NEWLINE,
+            Token::LET,
+            Token::IDENT("y".to_string()),
+            Token::ASSIGN,
+            Token::IDENT("x".to_string()),
+            Token::PLUS,
+            Token::INT(2),
+            Token::NEWLINE,
+            Token::IDENT("f".to_string()),
+            Token::LPAREN,
+            Token::INT(1),
+            Token::COMMA,
+            Token::INT(2),
+            Token::RPAREN,
+            Token::NEWLINE,
+            Token::RETURN,
+            Token::LBRACKET,
+            Token::IDENT("x".to_string()),
+            Token::COMMA,
+            Token::IDENT("y".to_string()),
+            Token::RBRACKET,
+            Token::PIPELINE,
+            Token::IDENT("g".to_string()),
+            Token::LPAREN,
+            Token::RPAREN,
+            Token::EOF,
+        ],
+        toks
+    );
+}
+
+#[test]
+fn line_breaks_in_blocks_and_strings() {
+    let input = "if (a) {\n  b\n} else {\n  c }\n\"${x\n}\"";
+    let toks: Vec<_> = Lexer::new(input).map(|tok| tok.kind).collect();
+    assert_eq!(
+        vec![
+            Token::IF,
+            Token::LPAREN,
+            Token::IDENT("a".to_string()),
+            Token::RPAREN,
+            Token::LBRACE,
+            Token::IDENT("b".to_string()),
+            Token::RBRACE,
+            Token::ELSE,
+            Token::LBRACE,
+            Token::IDENT("c".to_string()),
+            Token::RBRACE,
+            Token::NEWLINE,
+            Token::INTERP_START(String::new()),
+            Token::IDENT("x".to_string()),
+            Token::INTERP_END(String::new()),
+            Token::EOF,
+        ],
+        toks
+    );
+}
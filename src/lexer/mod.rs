@@ -1,15 +1,67 @@
 #[cfg(test)]
 mod lexer_test;
+pub mod span;
 pub mod token;
 
+use std::collections::BTreeMap;
+
+use crate::error::{LexErrKind, MonkeyErr};
+use crate::lexer::span::{Span, Spanned};
 use crate::lexer::token::Token;
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    // The whole comment including `//` or `/* */`
+    pub text: String,
+    pub span: Span,
+    // Whether the comment follows a token on the same line
+    pub trailing: bool,
+}
+
+// Where the literal part of a string stops
+enum StringEnd {
+    Quote,
+    Interpolation,
+    Eof,
+}
+
+// A string whose literal part is stopped at `${`. The braces opened inside
+// the interpolation are counted to find the `}` which closes it.
+#[derive(Debug)]
+struct Interpolation {
+    triple: bool,
+    depth: usize,
+    open: Span,
+}
+
 #[derive(Debug)]
 pub struct Lexer<'a> {
     pub input: &'a str,
     pub position: usize,
     pub read_position: usize,
     pub ch: char,
+    // Line and column of ch
+    pub line: usize,
+    pub column: usize,
+    // Errors are collected so that lexing can go on to the end
+    pub errors: Vec<MonkeyErr>,
+    // Comments are kept only with with_comments. They are attached to the
+    // token which follows them, keyed by the start of that token.
+    pub comments: BTreeMap<usize, Vec<Comment>>,
+    keep_comments: bool,
+    pending_comments: Vec<Comment>,
+    last_token_line: usize,
+    interpolations: Vec<Interpolation>,
+    // The token read after a line break, which is given out after the
+    // NEWLINE
+    ahead: Option<(Spanned<Token>, Span)>,
+    // The last token given out, and the empty span right after it
+    last: Option<(Token, Span)>,
+    // The brackets open around the current token, innermost last. An
+    // interpolation counts as one.
+    brackets: Vec<Token>,
+    // The column of the first token on the line of the last token
+    line_indent: usize,
 }
 
 impl<'a> Lexer<'a> {
@@ -19,53 +71,209 @@ impl<'a> Lexer<'a> {
             position: 0,
             read_position: 0,
             ch: '\x00',
+            line: 1,
+            column: 0,
+            errors: Vec::new(),
+            comments: BTreeMap::new(),
+            keep_comments: false,
+            pending_comments: Vec::new(),
+            last_token_line: 0,
+            interpolations: Vec::new(),
+            ahead: None,
+            last: None,
+            brackets: Vec::new(),
+            line_indent: 0,
         };
         lex.read_char();
         lex
     }
 
+    pub fn with_comments(mut self) -> Self {
+        self.keep_comments = true;
+        self
+    }
+
+    // Positions are byte offsets which always lie on char boundaries
     fn read_char(&mut self) {
-        if self.read_position >= self.input.len() {
-            self.ch = '\x00'
+        if self.ch == '\n' {
+            self.line += 1;
+            self.column = 1;
         } else {
-            self.ch = self.input.as_bytes()[self.read_position] as char;
+            self.column += 1;
         }
+        self.ch = self.peek_char();
         self.position = self.read_position;
-        self.read_position += 1;
+        self.read_position += self.ch.len_utf8();
     }
 
-    fn peek_char(&mut self) -> char {
-        if self.read_position >= self.input.len() {
-            '\x00'
-        } else {
-            self.input.as_bytes()[self.read_position] as char
+    fn peek_char(&self) -> char {
+        self.peek_nth(0)
+    }
+
+    // The char n places after the peeked one
+    fn peek_nth(&self, n: usize) -> char {
+        self.input
+            .get(self.read_position..)
+            .and_then(|rest| rest.chars().nth(n))
+            .unwrap_or('\x00')
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        loop {
+            while self.ch.is_whitespace() {
+                self.read_char()
+            }
+            match (self.ch, self.peek_char()) {
+                ('/', '/') => self.skip_line_comment(),
+                ('/', '*') => self.skip_block_comment(),
+                _ => break,
+            }
+        }
+    }
+
+    // Leaves ch at the newline which ends the comment
+    fn skip_line_comment(&mut self) {
+        let (start, line, column) = (self.position, self.line, self.column);
+        while self.ch != '\n' && self.ch != '\x00' {
+            self.read_char();
+        }
+        self.push_comment(Span::new(start, self.position, line, column));
+    }
+
+    // Block comments nest, so that code with comments can be commented out
+    fn skip_block_comment(&mut self) {
+        let (start, line, column) = (self.position, self.line, self.column);
+        let mut depth = 0;
+        loop {
+            match (self.ch, self.peek_char()) {
+                ('\x00', _) => {
+                    self.errors.push(MonkeyErr::LexErr {
+                        kind: LexErrKind::UnterminatedBlockComment,
+                        span: Span::new(start, start + 2, line, column),
+                    });
+                    return;
+                }
+                ('/', '*') => {
+                    depth += 1;
+                    self.read_char();
+                }
+                ('*', '/') => {
+                    depth -= 1;
+                    self.read_char();
+                    if depth == 0 {
+                        self.read_char();
+                        break;
+                    }
+                }
+                _ => {}
+            }
+            self.read_char();
+        }
+        self.push_comment(Span::new(start, self.position, line, column));
+    }
+
+    fn push_comment(&mut self, span: Span) {
+        if self.keep_comments {
+            self.pending_comments.push(Comment {
+                text: self.input[span.start..span.end].to_string(),
+                span,
+                trailing: span.line == self.last_token_line,
+            });
+        }
+    }
+
+    pub fn next_token(&mut self) -> Spanned<Token> {
+        let (tok, after) = match self.ahead.take() {
+            Some(ahead) => ahead,
+            None => {
+                let tok = self.read_token();
+                let after = Span::new(self.position, self.position, self.line, self.column);
+                if let Some(newline) = self.line_break(&tok) {
+                    self.ahead = Some((tok, after));
+                    return newline;
+                }
+                (tok, after)
+            }
+        };
+
+        match tok.kind {
+            Token::LPAREN | Token::LBRACKET | Token::LBRACE | Token::INTERP_START(_) => {
+                self.brackets.push(tok.kind.clone())
+            }
+            Token::RPAREN | Token::RBRACKET | Token::RBRACE | Token::INTERP_END(_) => {
+                self.brackets.pop();
+            }
+            _ => {}
+        }
+        if self
+            .last
+            .as_ref()
+            .is_none_or(|(_, last_after)| tok.span.line > last_after.line)
+        {
+            self.line_indent = tok.span.column;
+        }
+        self.last = Some((tok.kind.clone(), after));
+        tok
+    }
+
+    // The NEWLINE which ends a statement at the line break before next, if
+    // there is one. A line break ends a statement when the token before it
+    // can end one, the token after it can start one, and it is not inside
+    // of parentheses or brackets. A line which starts with a token like `-`
+    // or `(` could also go on with the line before, so it is an error when
+    // the line is indented further than the one before, as if it did.
+    fn line_break(&mut self, next: &Spanned<Token>) -> Option<Spanned<Token>> {
+        let (last, after) = self.last.as_ref()?;
+        let in_block = matches!(self.brackets.last(), None | Some(Token::LBRACE));
+        if next.span.line <= after.line
+            || !last.ends_statement()
+            || !in_block
+            || next.kind.continues_line()
+        {
+            return None;
         }
+        let newline = Spanned::new(Token::NEWLINE, *after);
+        if next.kind.is_ambiguous_start() && next.span.column > self.line_indent {
+            self.errors.push(MonkeyErr::AmbiguousLineBreakErr {
+                next: next.kind.clone(),
+                span: next.span,
+            });
+        }
+        Some(newline)
     }
 
-    pub fn next_token(&mut self) -> Token {
-        while self.ch.is_whitespace() {
-            self.read_char()
+    fn read_token(&mut self) -> Spanned<Token> {
+        self.skip_whitespace_and_comments();
+        let (start, line, column) = (self.position, self.line, self.column);
+        if !self.pending_comments.is_empty() {
+            let comments = std::mem::take(&mut self.pending_comments);
+            self.comments.insert(start, comments);
         }
+        self.last_token_line = line;
         let tok = match self.ch {
-            '=' => {
-                if self.peek_char() == '=' {
+            '=' => match self.peek_char() {
+                '=' => {
                     self.read_char();
                     Token::EQ
-                } else {
-                    Token::ASSIGN
                 }
-            }
-            '+' => Token::PLUS,
-            '-' => Token::MINUS,
+                '>' => {
+                    self.read_char();
+                    Token::FATARROW
+                }
+                _ => Token::ASSIGN,
+            },
+            '+' => self.with_assign(Token::PLUS, Token::PLUSEQ),
+            '-' => self.with_assign(Token::MINUS, Token::MINUSEQ),
             '*' => {
                 if self.peek_char() == '*' {
                     self.read_char();
                     Token::POWER
                 } else {
-                    Token::ASTERISK
+                    self.with_assign(Token::ASTERISK, Token::ASTERISKEQ)
                 }
             }
-            '/' => Token::SLASH,
+            '/' => self.with_assign(Token::SLASH, Token::SLASHEQ),
+            '%' => Token::PERCENT,
             '!' => {
                 if self.peek_char() == '=' {
                     self.read_char();
@@ -74,22 +282,95 @@ impl<'a> Lexer<'a> {
                     Token::BANG
                 }
             }
-            '<' => Token::LT,
-            '>' => Token::GT,
+            '<' => match self.peek_char() {
+                '=' => {
+                    self.read_char();
+                    Token::LTEQ
+                }
+                '<' => {
+                    self.read_char();
+                    Token::SHL
+                }
+                _ => Token::LT,
+            },
+            '>' => match self.peek_char() {
+                '=' => {
+                    self.read_char();
+                    Token::GTEQ
+                }
+                '>' => {
+                    self.read_char();
+                    Token::SHR
+                }
+                _ => Token::GT,
+            },
+            '&' => {
+                if self.peek_char() == '&' {
+                    self.read_char();
+                    Token::AND
+                } else {
+                    Token::BITAND
+                }
+            }
+            '|' => match self.peek_char() {
+                '|' => {
+                    self.read_char();
+                    Token::OR
+                }
+                '>' => {
+                    self.read_char();
+                    Token::PIPELINE
+                }
+                _ => Token::BITOR,
+            },
+            '.' if self.peek_char() == '.' => {
+                self.read_char();
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    Token::DOTDOTEQ
+                } else {
+                    Token::DOTDOT
+                }
+            }
+            '^' => Token::BITXOR,
+            '~' => Token::BITNOT,
             ',' => Token::COMMA,
             ';' => Token::SEMICOLON,
             ':' => Token::COLON,
             '(' => Token::LPAREN,
             ')' => Token::RPAREN,
-            '{' => Token::LBRACE,
-            '}' => Token::RBRACE,
+            '{' => {
+                if let Some(interp) = self.interpolations.last_mut() {
+                    interp.depth += 1;
+                }
+                Token::LBRACE
+            }
+            '}' => match self.interpolations.last_mut() {
+                Some(interp) if interp.depth == 0 => self.continue_string(),
+                Some(interp) => {
+                    interp.depth -= 1;
+                    Token::RBRACE
+                }
+                None => Token::RBRACE,
+            },
             '[' => Token::LBRACKET,
             ']' => Token::RBRACKET,
-            '"' => self.read_string(),
-            '\x00' => Token::EOF,
-            _ if token::is_letter(self.ch) => {
+            '"' => self.read_string(false),
+            'r' if self.peek_char() == '"' => {
+                self.read_char();
+                self.read_string(true)
+            }
+            '\x00' => {
+                if let Some(interp) = self.interpolations.first() {
+                    let open = interp.open;
+                    self.interpolations.clear();
+                    self.unterminated_string(open);
+                }
+                Token::EOF
+            }
+            _ if token::is_ident_start(self.ch) => {
                 let read_str = self.read_identifier();
-                let semi_tok = Token::is_str_keywords(&read_str);
+                let semi_tok = Token::is_str_keywords(read_str);
                 if let Some(tmp) = semi_tok {
                     tmp
                 } else {
@@ -97,53 +378,303 @@ impl<'a> Lexer<'a> {
                 }
             }
             _ if self.ch.is_ascii_digit() => self.read_number(),
+            '.' if self.peek_char().is_ascii_digit() => {
+                let tok = self.read_number();
+                self.lex_error(LexErrKind::LeadingPoint, start, line, column);
+                tok
+            }
             _ => Token::ILLIGAL,
         };
+        // Every arm leaves ch at the last character of the token
+        let end = self.read_position.min(self.input.len());
         self.read_char();
-        tok
+        Spanned::new(tok, Span::new(start, end, line, column))
+    }
+
+    // The compound assignment if `=` follows the operator
+    fn with_assign(&mut self, operator: Token, compound: Token) -> Token {
+        if self.peek_char() == '=' {
+            self.read_char();
+            compound
+        } else {
+            operator
+        }
     }
 
     fn read_identifier(&mut self) -> &str {
         let position = self.position;
-        while token::is_letter(self.ch) {
+        while token::is_ident_continue(self.peek_char()) {
+            self.read_char();
+        }
+        &self.input[position..self.read_position]
+    }
+
+    fn at_end(&self) -> bool {
+        self.read_position >= self.input.len()
+    }
+
+    fn lex_error(&mut self, kind: LexErrKind, start: usize, line: usize, column: usize) {
+        let span = Span::new(
+            start,
+            self.read_position.min(self.input.len()),
+            line,
+            column,
+        );
+        self.errors.push(MonkeyErr::LexErr { kind, span });
+    }
+
+    fn unterminated_string(&mut self, span: Span) {
+        self.errors.push(MonkeyErr::LexErr {
+            kind: LexErrKind::UnterminatedString,
+            span,
+        });
+    }
+
+    // Reads "...", or """...""" which may span lines. Escapes and
+    // interpolations are not processed in raw strings, which start with r.
+    fn read_string(&mut self, raw: bool) -> Token {
+        let open = Span::new(self.position, self.position + 1, self.line, self.column);
+        let triple = self.input[self.read_position..].starts_with("\"\"");
+        if triple {
             self.read_char();
+            self.read_char();
+        }
+
+        let (value, end) = self.read_string_part(raw, triple);
+        match end {
+            StringEnd::Quote => Token::STRING(value),
+            StringEnd::Interpolation => {
+                self.interpolations.push(Interpolation {
+                    triple,
+                    depth: 0,
+                    open,
+                });
+                Token::INTERP_START(value)
+            }
+            StringEnd::Eof => {
+                self.unterminated_string(open);
+                Token::STRING(value)
+            }
+        }
+    }
+
+    // Goes on with the string after the `}` which closes an interpolation
+    fn continue_string(&mut self) -> Token {
+        let (triple, open) = match self.interpolations.last() {
+            Some(interp) => (interp.triple, interp.open),
+            None => return Token::RBRACE,
+        };
+        let (value, end) = self.read_string_part(false, triple);
+        match end {
+            StringEnd::Interpolation => Token::INTERP_MID(value),
+            StringEnd::Quote => {
+                self.interpolations.pop();
+                Token::INTERP_END(value)
+            }
+            StringEnd::Eof => {
+                self.interpolations.pop();
+                self.unterminated_string(open);
+                Token::INTERP_END(value)
+            }
         }
-        self.position -= 1;
-        self.read_position -= 1;
-        &self.input[position..=self.position]
     }
 
-    fn read_string(&mut self) -> Token {
-        let position = self.position + 1;
+    // Reads the literal part of a string after ch, up to the closing quote
+    // or the `${` of an interpolation
+    fn read_string_part(&mut self, raw: bool, triple: bool) -> (String, StringEnd) {
+        let mut value = String::new();
         loop {
+            if self.at_end() {
+                return (value, StringEnd::Eof);
+            }
             self.read_char();
-            if self.ch == '"' || self.ch == '\x00' {
-                break;
+            match self.ch {
+                '"' if !triple => return (value, StringEnd::Quote),
+                '"' if self.input[self.read_position..].starts_with("\"\"") => {
+                    self.read_char();
+                    self.read_char();
+                    return (value, StringEnd::Quote);
+                }
+                '$' if !raw && self.peek_char() == '{' => {
+                    self.read_char();
+                    return (value, StringEnd::Interpolation);
+                }
+                '\\' if !raw => {
+                    if let Some(ch) = self.read_escape() {
+                        value.push(ch);
+                    }
+                }
+                ch => value.push(ch),
+            }
+        }
+    }
+
+    // Leaves ch at the last character of the escape. Invalid escapes are
+    // reported and give nothing.
+    fn read_escape(&mut self) -> Option<char> {
+        let (start, line, column) = (self.position, self.line, self.column);
+        if self.at_end() {
+            return None;
+        }
+        self.read_char();
+        let escaped = match self.ch {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '$' => '$',
+            'u' => return self.read_unicode_escape(start, line, column),
+            ch => {
+                self.lex_error(LexErrKind::UnknownEscape(ch), start, line, column);
+                return None;
             }
+        };
+        Some(escaped)
+    }
+
+    // \u{...} with one to six hex digits
+    fn read_unicode_escape(&mut self, start: usize, line: usize, column: usize) -> Option<char> {
+        if self.peek_char() != '{' {
+            self.lex_error(LexErrKind::UnicodeEscapeWithoutBrace, start, line, column);
+            return None;
+        }
+        self.read_char();
+        let digits_start = self.read_position;
+        while self.peek_char().is_ascii_hexdigit() {
+            self.read_char();
         }
-        Token::STRING(self.input[position..self.position].to_string())
+        let digits = &self.input[digits_start..self.read_position];
+        if self.peek_char() != '}' {
+            self.lex_error(LexErrKind::UnterminatedUnicodeEscape, start, line, column);
+            return None;
+        }
+        self.read_char();
+
+        if digits.is_empty() || digits.len() > 6 {
+            self.lex_error(LexErrKind::UnicodeEscapeLength, start, line, column);
+            return None;
+        }
+        let escaped = u32::from_str_radix(digits, 16)
+            .ok()
+            .and_then(char::from_u32);
+        if escaped.is_none() {
+            let kind = LexErrKind::InvalidUnicode(digits.to_string());
+            self.lex_error(kind, start, line, column);
+        }
+        escaped
     }
 
-    // Add lexing an imeginary part of complex number
+    // Reads 12, 3.14, 1e-9 or 2.5e3, and a trailing i makes it imaginary.
+    // The point needs a digit after it, so that `1..5` is not read as 1.
+    // Digits may be separated by `_`, like 1_000_000.
     fn read_number(&mut self) -> Token {
-        let position = self.position;
-        while self.ch.is_ascii_digit() {
+        let (position, line, column) = (self.position, self.line, self.column);
+        if self.ch == '0' {
+            let radix = match self.peek_char() {
+                'x' | 'X' => Some((16, "hexadecimal")),
+                'o' | 'O' => Some((8, "octal")),
+                'b' | 'B' => Some((2, "binary")),
+                _ => None,
+            };
+            if let Some((radix, name)) = radix {
+                self.read_char();
+                return self.read_radix_number(radix, name, (position, line, column));
+            }
+        }
+
+        let mut is_float = self.ch == '.';
+        self.read_digits();
+        if self.peek_char() == '.' && self.peek_nth(1).is_ascii_digit() {
+            self.read_char();
+            self.read_digits();
+            is_float = true;
+        }
+        let exponent_digit = match self.peek_nth(1) {
+            '+' | '-' => self.peek_nth(2),
+            ch => ch,
+        };
+        if matches!(self.peek_char(), 'e' | 'E') && exponent_digit.is_ascii_digit() {
             self.read_char();
+            if matches!(self.peek_char(), '+' | '-') {
+                self.read_char();
+            }
+            self.read_digits();
+            is_float = true;
         }
-        self.position -= 1;
-        self.read_position -= 1;
-        let num = self.input[position..=self.position].parse().unwrap();
-        if self.peek_char() == 'i' {
+
+        let literal = self.input[position..self.read_position].replace('_', "");
+        let imaginary = self.peek_char() == 'i';
+        if imaginary {
             self.read_char();
-            Token::IMEGINARY(num)
+        }
+        if !is_float && !imaginary {
+            return match literal.parse() {
+                Ok(n) => Token::INT(n),
+                Err(_) => {
+                    self.lex_error(LexErrKind::IntegerTooLarge, position, line, column);
+                    Token::INT(0)
+                }
+            };
+        }
+        // Every literal of digits parses as a float, but it may be infinite
+        let n: f64 = literal.parse().unwrap_or(f64::INFINITY);
+        if n.is_infinite() {
+            self.lex_error(LexErrKind::FloatTooLarge, position, line, column);
+        }
+        if imaginary {
+            Token::IMEGINARY(n)
         } else {
-            Token::INT(num)
+            Token::FLOAT(n)
+        }
+    }
+
+    // Reads the digits after 0x, 0o or 0b. Letters and digits are read on
+    // to the end, so that a wrong digit is reported instead of starting a new
+    // token.
+    fn read_radix_number(
+        &mut self,
+        radix: u32,
+        name: &'static str,
+        (start, line, column): (usize, usize, usize),
+    ) -> Token {
+        let digits_start = self.read_position;
+        while self.peek_char().is_ascii_alphanumeric() || self.peek_char() == '_' {
+            self.read_char();
+        }
+        let digits = &self.input[digits_start..self.read_position];
+        if let Some(wrong) = digits.chars().find(|ch| *ch != '_' && !ch.is_digit(radix)) {
+            let kind = LexErrKind::InvalidDigit {
+                digit: wrong,
+                radix: name,
+            };
+            self.lex_error(kind, start, line, column);
+            return Token::INT(0);
+        }
+        let digits = digits.replace('_', "");
+        if digits.is_empty() {
+            self.lex_error(LexErrKind::NoDigits { radix: name }, start, line, column);
+            return Token::INT(0);
+        }
+        match i64::from_str_radix(&digits, radix) {
+            Ok(n) => Token::INT(n),
+            Err(_) => {
+                self.lex_error(LexErrKind::IntegerTooLarge, start, line, column);
+                Token::INT(0)
+            }
+        }
+    }
+
+    fn read_digits(&mut self) {
+        while self.peek_char().is_ascii_digit() || self.peek_char() == '_' {
+            self.read_char();
         }
     }
 }
 
 impl Iterator for Lexer<'_> {
-    type Item = Token;
+    type Item = Spanned<Token>;
     fn next(&mut self) -> Option<Self::Item> {
         if self.position > self.input.len() {
             None
@@ -0,0 +1,64 @@
+use std::fmt::{self, Display};
+
+// Byte range of the source plus the line and column where it starts.
+// Lines and columns are 1-based.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Self {
+        Self {
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+
+    // Span which starts at self and ends at the end of other
+    pub fn to(self, other: Span) -> Self {
+        Self {
+            end: other.end.max(self.start),
+            ..self
+        }
+    }
+}
+
+// Spans never take part in comparing tokens or ast nodes, so that the same
+// code written in a different layout still gives an equal tree.
+impl PartialEq for Span {
+    fn eq(&self, _other: &Span) -> bool {
+        true
+    }
+}
+
+impl Eq for Span {}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spanned<T> {
+    pub kind: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(kind: T, span: Span) -> Self {
+        Self { kind, span }
+    }
+}
+
+impl<T> From<T> for Spanned<T> {
+    fn from(kind: T) -> Self {
+        Self::new(kind, Span::default())
+    }
+}
@@ -1,35 +1,66 @@
+use std::fmt::{self, Display};
+
 use crate::error;
+use crate::lexer::span::Spanned;
+use unicode_xid::UnicodeXID;
 
 type Error = error::MonkeyErr;
 
-#[allow(clippy::upper_case_acronyms)]
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(clippy::upper_case_acronyms, non_camel_case_types)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     ILLIGAL,
     EOF,
     // Identifiers + Literals
     IDENT(String),
     STRING(String),
+    // Literal parts of an interpolated string "a${x}b${y}c": INTERP_START is
+    // "a${", INTERP_MID is "}b${" and INTERP_END is "}c"
+    INTERP_START(String),
+    INTERP_MID(String),
+    INTERP_END(String),
     INT(i64),
-    IMEGINARY(i64),
+    FLOAT(f64),
+    IMEGINARY(f64),
 
     // Operations
-    ASSIGN,   // =
-    PLUS,     // +
-    MINUS,    // -
-    BANG,     // !
-    ASTERISK, // *
-    SLASH,    // /
-    POWER,    // **
-    LT,       // <
-    GT,       // >
-    EQ,       // ==
-    NOTEQ,    // !=
+    ASSIGN,     // =
+    PLUSEQ,     // +=
+    MINUSEQ,    // -=
+    ASTERISKEQ, // *=
+    SLASHEQ,    // /=
+    PLUS,       // +
+    MINUS,      // -
+    BANG,       // !
+    ASTERISK,   // *
+    SLASH,      // /
+    PERCENT,    // %
+    POWER,      // **
+    LT,         // <
+    GT,         // >
+    LTEQ,       // <=
+    GTEQ,       // >=
+    EQ,         // ==
+    NOTEQ,      // !=
+    AND,        // &&
+    OR,         // ||
+    BITAND,     // &
+    BITOR,      // |
+    BITXOR,     // ^
+    BITNOT,     // ~
+    SHL,        // <<
+    SHR,        // >>
+    DOTDOT,     // ..
+    DOTDOTEQ,   // ..=
+    FATARROW,   // =>
+    PIPELINE,   // |>
 
     // Delimiters
     COMMA,     // ,
     SEMICOLON, // ;
     COLON,     // :
+    // A line break which ends a statement, see Lexer::line_break
+    NEWLINE,
 
     LPAREN,   // (
     RPAREN,   // )
@@ -46,6 +77,12 @@ pub enum Token {
     RETURN,
     TRUE,
     FALSE,
+    WHILE,
+    FOR,
+    IN,
+    BREAK,
+    CONTINUE,
+    MATCH,
 }
 
 impl Token {
@@ -58,6 +95,12 @@ impl Token {
             "return" => Some(Token::RETURN),
             "true" => Some(Token::TRUE),
             "false" => Some(Token::FALSE),
+            "while" => Some(Token::WHILE),
+            "for" => Some(Token::FOR),
+            "in" => Some(Token::IN),
+            "break" => Some(Token::BREAK),
+            "continue" => Some(Token::CONTINUE),
+            "match" => Some(Token::MATCH),
             _ => None,
         }
     }
@@ -66,17 +109,109 @@ impl Token {
         match (self, other) {
             (Token::IDENT(_), Token::IDENT(_)) => true,
             (Token::STRING(_), Token::STRING(_)) => true,
+            (Token::INTERP_START(_), Token::INTERP_START(_)) => true,
+            (Token::INTERP_MID(_), Token::INTERP_MID(_)) => true,
+            (Token::INTERP_END(_), Token::INTERP_END(_)) => true,
             (Token::INT(_), Token::INT(_)) => true,
+            (Token::FLOAT(_), Token::FLOAT(_)) => true,
             (Token::IMEGINARY(_), Token::IMEGINARY(_)) => true,
             _ => self == other,
         }
     }
 
-    pub fn unwrap_string(&self) -> error::Result<String> {
-        if let Token::IDENT(ref s) = self {
-            Ok(s.to_owned())
-        } else {
-            Err(Error::CannotConvertStringErr { got: self.clone() })
+    // Whether a statement can end with the token, so that a line break after
+    // it may end the statement
+    pub fn ends_statement(&self) -> bool {
+        matches!(
+            self,
+            Token::IDENT(_)
+                | Token::STRING(_)
+                | Token::INTERP_END(_)
+                | Token::INT(_)
+                | Token::FLOAT(_)
+                | Token::IMEGINARY(_)
+                | Token::TRUE
+                | Token::FALSE
+                | Token::BREAK
+                | Token::CONTINUE
+                | Token::RPAREN
+                | Token::RBRACKET
+                | Token::RBRACE
+        )
+    }
+
+    // Whether the token can only go on with something before it, like a
+    // binary operator. A line which starts with one continues the line
+    // before.
+    pub fn continues_line(&self) -> bool {
+        matches!(
+            self,
+            Token::EOF
+                | Token::INTERP_MID(_)
+                | Token::INTERP_END(_)
+                | Token::ASSIGN
+                | Token::PLUSEQ
+                | Token::MINUSEQ
+                | Token::ASTERISKEQ
+                | Token::SLASHEQ
+                | Token::PLUS
+                | Token::ASTERISK
+                | Token::SLASH
+                | Token::PERCENT
+                | Token::POWER
+                | Token::LT
+                | Token::GT
+                | Token::LTEQ
+                | Token::GTEQ
+                | Token::EQ
+                | Token::NOTEQ
+                | Token::AND
+                | Token::BITAND
+                | Token::BITXOR
+                | Token::SHL
+                | Token::SHR
+                | Token::DOTDOT
+                | Token::DOTDOTEQ
+                | Token::FATARROW
+                | Token::PIPELINE
+                | Token::COMMA
+                | Token::SEMICOLON
+                | Token::COLON
+                | Token::RPAREN
+                | Token::RBRACE
+                | Token::RBRACKET
+                | Token::ELSE
+                | Token::IN
+        )
+    }
+
+    // Whether the token may either start a statement or go on with the one
+    // before, like `-` or `(`
+    pub fn is_ambiguous_start(&self) -> bool {
+        matches!(
+            self,
+            Token::MINUS | Token::LPAREN | Token::LBRACKET | Token::BITOR | Token::OR
+        )
+    }
+
+    // The operator which a compound assignment like += applies
+    pub fn compound_operator(&self) -> Option<Token> {
+        match self {
+            Token::PLUSEQ => Some(Token::PLUS),
+            Token::MINUSEQ => Some(Token::MINUS),
+            Token::ASTERISKEQ => Some(Token::ASTERISK),
+            Token::SLASHEQ => Some(Token::SLASH),
+            _ => None,
+        }
+    }
+
+    // The token which closes self, or self if it is not an opening delimiter
+    pub fn closing_delimiter(&self) -> Token {
+        match self {
+            Token::LPAREN => Token::RPAREN,
+            Token::LBRACE => Token::RBRACE,
+            Token::LBRACKET => Token::RBRACKET,
+            _ => self.clone(),
         }
     }
 
@@ -84,22 +219,145 @@ impl Token {
         match self {
             Token::IDENT(_) => "IDENT".to_string(),
             Token::STRING(_) => "STRING".to_string(),
+            Token::INTERP_START(_) => "INTERP_START".to_string(),
+            Token::INTERP_MID(_) => "INTERP_MID".to_string(),
+            Token::INTERP_END(_) => "INTERP_END".to_string(),
             Token::INT(_) => "INT".to_string(),
+            Token::FLOAT(_) => "FLOAT".to_string(),
             Token::IMEGINARY(_) => "IMEGINARY".to_string(),
             _ => format!("{:?}", self),
         }
     }
 }
 
-pub fn is_letter(chr: char) -> bool {
-    chr.is_ascii_alphabetic() || chr == '_'
+impl Spanned<Token> {
+    pub fn unwrap_string(&self) -> error::Result<String> {
+        if let Token::IDENT(ref s) = self.kind {
+            Ok(s.to_owned())
+        } else {
+            Err(Error::CannotConvertStringErr {
+                got: self.kind.clone(),
+                span: self.span,
+            })
+        }
+    }
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::ILLIGAL => write!(f, "ILLIGAL"),
+            Token::EOF => write!(f, "EOF"),
+            Token::IDENT(s) => write!(f, "{}", s),
+            Token::STRING(s) => write!(f, "\"{}\"", escape_str(s)),
+            Token::INTERP_START(s) => write!(f, "\"{}${{", escape_str(s)),
+            Token::INTERP_MID(s) => write!(f, "}}{}${{", escape_str(s)),
+            Token::INTERP_END(s) => write!(f, "}}{}\"", escape_str(s)),
+            Token::INT(n) => write!(f, "{}", n),
+            Token::FLOAT(n) => write!(f, "{}", format_float(*n)),
+            Token::IMEGINARY(n) => write!(f, "{}i", format_float(*n)),
+            Token::ASSIGN => write!(f, "="),
+            Token::PLUSEQ => write!(f, "+="),
+            Token::MINUSEQ => write!(f, "-="),
+            Token::ASTERISKEQ => write!(f, "*="),
+            Token::SLASHEQ => write!(f, "/="),
+            Token::PLUS => write!(f, "+"),
+            Token::MINUS => write!(f, "-"),
+            Token::BANG => write!(f, "!"),
+            Token::ASTERISK => write!(f, "*"),
+            Token::SLASH => write!(f, "/"),
+            Token::PERCENT => write!(f, "%"),
+            Token::POWER => write!(f, "**"),
+            Token::LT => write!(f, "<"),
+            Token::GT => write!(f, ">"),
+            Token::LTEQ => write!(f, "<="),
+            Token::GTEQ => write!(f, ">="),
+            Token::EQ => write!(f, "=="),
+            Token::NOTEQ => write!(f, "!="),
+            Token::AND => write!(f, "&&"),
+            Token::OR => write!(f, "||"),
+            Token::BITAND => write!(f, "&"),
+            Token::BITOR => write!(f, "|"),
+            Token::BITXOR => write!(f, "^"),
+            Token::BITNOT => write!(f, "~"),
+            Token::SHL => write!(f, "<<"),
+            Token::SHR => write!(f, ">>"),
+            Token::DOTDOT => write!(f, ".."),
+            Token::DOTDOTEQ => write!(f, "..="),
+            Token::FATARROW => write!(f, "=>"),
+            Token::PIPELINE => write!(f, "|>"),
+            Token::COMMA => write!(f, ","),
+            Token::SEMICOLON => write!(f, ";"),
+            Token::NEWLINE => write!(f, "\\n"),
+            Token::COLON => write!(f, ":"),
+            Token::LPAREN => write!(f, "("),
+            Token::RPAREN => write!(f, ")"),
+            Token::LBRACE => write!(f, "{{"),
+            Token::RBRACE => write!(f, "}}"),
+            Token::LBRACKET => write!(f, "["),
+            Token::RBRACKET => write!(f, "]"),
+            Token::FUNCTION => write!(f, "fn"),
+            Token::LET => write!(f, "let"),
+            Token::IF => write!(f, "if"),
+            Token::ELSE => write!(f, "else"),
+            Token::RETURN => write!(f, "return"),
+            Token::TRUE => write!(f, "true"),
+            Token::FALSE => write!(f, "false"),
+            Token::WHILE => write!(f, "while"),
+            Token::FOR => write!(f, "for"),
+            Token::IN => write!(f, "in"),
+            Token::BREAK => write!(f, "break"),
+            Token::CONTINUE => write!(f, "continue"),
+            Token::MATCH => write!(f, "match"),
+        }
+    }
+}
+
+// Floats always show a point or an exponent, so that they read back as floats
+pub fn format_float(n: f64) -> String {
+    format!("{:?}", n)
+}
+
+// Escapes s so that it reads back as the same string between quotes
+pub fn escape_str(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '$' if chars.peek() == Some(&'{') => escaped.push_str("\\$"),
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\t' => escaped.push_str("\\t"),
+            '\r' => escaped.push_str("\\r"),
+            '\0' => escaped.push_str("\\0"),
+            _ if ch.is_control() => escaped.push_str(&format!("\\u{{{:x}}}", ch as u32)),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+// Identifiers follow Unicode XID, with `_` allowed at the start as well
+pub fn is_ident_start(chr: char) -> bool {
+    chr == '_' || UnicodeXID::is_xid_start(chr)
+}
+
+pub fn is_ident_continue(chr: char) -> bool {
+    UnicodeXID::is_xid_continue(chr)
 }
 
 #[test]
-fn check_is_letter() {
-    assert!(is_letter('a'));
-    assert!(is_letter('P'));
-    assert!(is_letter('_'));
-    assert!(!is_letter('3'));
-    assert!(!is_letter('{'));
+fn check_is_ident() {
+    assert!(is_ident_start('a'));
+    assert!(is_ident_start('P'));
+    assert!(is_ident_start('_'));
+    assert!(is_ident_start('변'));
+    assert!(is_ident_start('π'));
+    assert!(!is_ident_start('3'));
+    assert!(!is_ident_start('{'));
+    assert!(!is_ident_start('😀'));
+    assert!(is_ident_continue('3'));
+    assert!(is_ident_continue('\u{301}'));
+    assert!(!is_ident_continue('-'));
 }
@@ -1,10 +1,38 @@
 #![warn(rust_2018_idioms, clippy::all)]
+// Errors carry tokens and spans for diagnostics and only live on the failure path
+#![allow(clippy::result_large_err)]
 
+mod cli;
+mod code;
+mod compiler;
+mod diagnostic;
+mod emit;
+mod engine;
+#[macro_use]
 mod error;
+mod eval;
+mod formatter;
 mod lexer;
+mod object;
 mod parser;
 mod repl;
+mod vm;
+
+use std::env;
+use std::process;
+use std::thread;
+
+// The evaluator recurses on the native stack, up to vm::MAX_FRAMES calls
+// deep, which takes more than the stack of the main thread
+const STACK_SIZE: usize = 256 << 20;
 
 fn main() {
-    repl::start_repl();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let code = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || cli::run(&args))
+        .expect("cannot start the interpreter thread")
+        .join()
+        .unwrap_or(101);
+    process::exit(code);
 }
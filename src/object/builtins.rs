@@ -0,0 +1,160 @@
+use std::convert::TryFrom;
+
+use crate::error;
+use crate::object::{self, Object};
+
+type Error = error::MonkeyErr;
+
+pub const BUILTINS: &[(&str, super::BuiltinFn)] = &[
+    ("len", len),
+    ("puts", puts),
+    ("first", first),
+    ("last", last),
+    ("rest", rest),
+    ("push", push),
+    ("re", re),
+    ("im", im),
+    ("conj", conj),
+    ("abs", abs),
+    ("arg", arg),
+];
+
+pub fn lookup(name: &str) -> Option<Object> {
+    BUILTINS
+        .iter()
+        .find(|(builtin_name, _)| *builtin_name == name)
+        .map(|(name, func)| Object::Builtin { name, func: *func })
+}
+
+fn check_arg_len(name: &str, args: &[Object], expected: usize) -> error::Result<()> {
+    if args.len() == expected {
+        Ok(())
+    } else {
+        Err(Error::EvalErr {
+            msg: format!(
+                "wrong number of arguments to `{}`. got={}, want={}",
+                name,
+                args.len(),
+                expected
+            ),
+            span: None,
+        })
+    }
+}
+
+fn unsupported_arg(name: &str, arg: &Object) -> Error {
+    Error::EvalErr {
+        msg: format!(
+            "argument to `{}` not supported, got {}",
+            name,
+            arg.type_name()
+        ),
+        span: None,
+    }
+}
+
+fn len(args: Vec<Object>) -> error::Result<Object> {
+    check_arg_len("len", &args, 1)?;
+    match &args[0] {
+        Object::String(s) => Ok(Object::Integer(s.chars().count() as i64)),
+        Object::Array(elements) => Ok(Object::Integer(elements.len() as i64)),
+        Object::Hash(pairs) => Ok(Object::Integer(pairs.len() as i64)),
+        Object::Range {
+            start,
+            end,
+            inclusive,
+        } => i64::try_from(object::range_len(*start, *end, *inclusive))
+            .map(Object::Integer)
+            .map_err(|_| Error::EvalErr {
+                msg: format!("length of {} does not fit in an INTEGER", args[0]),
+                span: None,
+            }),
+        other => Err(unsupported_arg("len", other)),
+    }
+}
+
+#[allow(clippy::unnecessary_wraps)]
+fn puts(args: Vec<Object>) -> error::Result<Object> {
+    for arg in args {
+        println!("{}", arg);
+    }
+    Ok(Object::Null)
+}
+
+fn first(args: Vec<Object>) -> error::Result<Object> {
+    check_arg_len("first", &args, 1)?;
+    match &args[0] {
+        Object::Array(elements) => Ok(elements.first().cloned().unwrap_or(Object::Null)),
+        other => Err(unsupported_arg("first", other)),
+    }
+}
+
+fn last(args: Vec<Object>) -> error::Result<Object> {
+    check_arg_len("last", &args, 1)?;
+    match &args[0] {
+        Object::Array(elements) => Ok(elements.last().cloned().unwrap_or(Object::Null)),
+        other => Err(unsupported_arg("last", other)),
+    }
+}
+
+fn rest(args: Vec<Object>) -> error::Result<Object> {
+    check_arg_len("rest", &args, 1)?;
+    match &args[0] {
+        Object::Array(elements) if elements.is_empty() => Ok(Object::Null),
+        Object::Array(elements) => Ok(Object::Array(elements[1..].to_vec())),
+        other => Err(unsupported_arg("rest", other)),
+    }
+}
+
+fn push(args: Vec<Object>) -> error::Result<Object> {
+    check_arg_len("push", &args, 2)?;
+    match &args[0] {
+        Object::Array(elements) => {
+            let mut elements = elements.clone();
+            elements.push(args[1].clone());
+            Ok(Object::Array(elements))
+        }
+        other => Err(unsupported_arg("push", other)),
+    }
+}
+
+fn complex_arg(name: &str, args: &[Object]) -> error::Result<(f64, f64)> {
+    check_arg_len(name, args, 1)?;
+    args[0]
+        .to_complex()
+        .ok_or_else(|| unsupported_arg(name, &args[0]))
+}
+
+// The real and imaginary parts of any number, as floats
+fn re(args: Vec<Object>) -> error::Result<Object> {
+    complex_arg("re", &args).map(|(re, _)| Object::Float(re))
+}
+
+fn im(args: Vec<Object>) -> error::Result<Object> {
+    complex_arg("im", &args).map(|(_, im)| Object::Float(im))
+}
+
+// Real numbers are their own conjugates
+fn conj(args: Vec<Object>) -> error::Result<Object> {
+    complex_arg("conj", &args)?;
+    match &args[0] {
+        Object::Complex { re, im } => Ok(Object::Complex { re: *re, im: -im }),
+        real => Ok(real.clone()),
+    }
+}
+
+fn abs(args: Vec<Object>) -> error::Result<Object> {
+    let (re, im) = complex_arg("abs", &args)?;
+    match &args[0] {
+        Object::Integer(n) => n.checked_abs().map(Object::Integer).ok_or(Error::EvalErr {
+            msg: "integer overflow".to_string(),
+            span: None,
+        }),
+        Object::Float(n) => Ok(Object::Float(n.abs())),
+        _ => Ok(Object::Float(re.hypot(im))),
+    }
+}
+
+fn arg(args: Vec<Object>) -> error::Result<Object> {
+    complex_arg("arg", &args).map(|(re, im)| Object::Float(im.atan2(re)))
+}
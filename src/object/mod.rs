@@ -0,0 +1,274 @@
+pub mod builtins;
+pub mod environment;
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt::{self, Display};
+use std::rc::Rc;
+
+use crate::code::{Instructions, SourceMap};
+use crate::error;
+use crate::lexer::token;
+use crate::object::environment::Env;
+use crate::parser::ast::{BlockStmt, Parameters, Pattern};
+
+type Error = error::MonkeyErr;
+
+pub type BuiltinFn = fn(Vec<Object>) -> error::Result<Object>;
+
+#[derive(Debug, Clone)]
+pub enum Object {
+    Null,
+    Integer(i64),
+    Float(f64),
+    Boolean(bool),
+    String(String),
+    Complex {
+        re: f64,
+        im: f64,
+    },
+    Array(Vec<Object>),
+    // The integers from start up to end, which are made only when they are
+    // used
+    Range {
+        start: i64,
+        end: i64,
+        inclusive: bool,
+    },
+    Hash(BTreeMap<HashKey, Object>),
+    // name is the one of the let statement which made the function, for
+    // the messages of bad calls
+    Function {
+        name: Option<String>,
+        parameters: Parameters,
+        body: BlockStmt,
+        env: Env,
+    },
+    Builtin {
+        name: &'static str,
+        func: BuiltinFn,
+    },
+    ReturnValue(Box<Object>),
+    // Like ReturnValue, these stop the blocks up to the nearest loop
+    Break,
+    Continue,
+    CompiledFunction(Rc<CompiledFunction>),
+    Closure {
+        func: Rc<CompiledFunction>,
+        free: Vec<Object>,
+    },
+    // The pattern of a match arm, as a constant of the bytecode
+    Pattern(Rc<Pattern>),
+    // A local of the vm which closures capture. They share it with the
+    // function which defined it, so that an assignment is seen by all.
+    Cell(Rc<RefCell<Object>>),
+}
+
+#[derive(Debug, PartialEq)]
+pub struct CompiledFunction {
+    pub instructions: Instructions,
+    pub source_map: SourceMap,
+    pub num_locals: usize,
+    pub name: Option<String>,
+    pub parameters: Parameters,
+}
+
+// How a function is shown in the messages of bad calls, like f(x, y = 1)
+pub fn signature(name: Option<&str>, parameters: &Parameters) -> String {
+    format!("{}({})", name.unwrap_or("fn"), parameters)
+}
+
+// Only these values can be used as a key of a hash
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HashKey {
+    Integer(i64),
+    Boolean(bool),
+    String(String),
+}
+
+impl Object {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Null => "NULL",
+            Object::Integer(_) => "INTEGER",
+            Object::Float(_) => "FLOAT",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::String(_) => "STRING",
+            Object::Complex { .. } => "COMPLEX",
+            Object::Array(_) => "ARRAY",
+            Object::Range { .. } => "RANGE",
+            Object::Hash(_) => "HASH",
+            Object::Function { .. } => "FUNCTION",
+            Object::Builtin { .. } => "BUILTIN",
+            Object::ReturnValue(_) => "RETURN_VALUE",
+            Object::Break => "BREAK",
+            Object::Continue => "CONTINUE",
+            Object::CompiledFunction(_) => "COMPILED_FUNCTION",
+            Object::Closure { .. } => "CLOSURE",
+            Object::Pattern(_) => "PATTERN",
+            Object::Cell(_) => "CELL",
+        }
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Object::Null | Object::Boolean(false))
+    }
+
+    // Integers and floats as a real number, for mixed arithmetic
+    pub fn to_real(&self) -> Option<f64> {
+        match self {
+            Object::Integer(n) => Some(*n as f64),
+            Object::Float(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn to_complex(&self) -> Option<(f64, f64)> {
+        match self {
+            Object::Complex { re, im } => Some((*re, *im)),
+            _ => self.to_real().map(|re| (re, 0.0)),
+        }
+    }
+
+    pub fn to_hash_key(&self) -> error::Result<HashKey> {
+        match self {
+            Object::Integer(n) => Ok(HashKey::Integer(*n)),
+            Object::Boolean(b) => Ok(HashKey::Boolean(*b)),
+            Object::String(s) => Ok(HashKey::String(s.clone())),
+            _ => Err(Error::EvalErr {
+                msg: format!("unusable as hash key: {}", self.type_name()),
+                span: None,
+            }),
+        }
+    }
+}
+
+impl From<HashKey> for Object {
+    fn from(key: HashKey) -> Self {
+        match key {
+            HashKey::Integer(n) => Object::Integer(n),
+            HashKey::Boolean(b) => Object::Boolean(b),
+            HashKey::String(s) => Object::String(s),
+        }
+    }
+}
+
+impl PartialEq for Object {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Object::Null, Object::Null) => true,
+            (Object::Integer(a), Object::Integer(b)) => a == b,
+            (Object::Float(a), Object::Float(b)) => a == b,
+            (Object::Boolean(a), Object::Boolean(b)) => a == b,
+            (Object::String(a), Object::String(b)) => a == b,
+            (Object::Complex { re: r1, im: i1 }, Object::Complex { re: r2, im: i2 }) => {
+                r1 == r2 && i1 == i2
+            }
+            (Object::Array(a), Object::Array(b)) => a == b,
+            // Ranges are equal when they hold the same integers
+            (
+                Object::Range {
+                    start: s1,
+                    end: e1,
+                    inclusive: i1,
+                },
+                Object::Range {
+                    start: s2,
+                    end: e2,
+                    inclusive: i2,
+                },
+            ) => {
+                let (len1, len2) = (range_len(*s1, *e1, *i1), range_len(*s2, *e2, *i2));
+                len1 == len2 && (len1 == 0 || s1 == s2)
+            }
+            (Object::Hash(a), Object::Hash(b)) => a == b,
+            (
+                Object::Function {
+                    parameters: p1,
+                    body: b1,
+                    env: e1,
+                    ..
+                },
+                Object::Function {
+                    parameters: p2,
+                    body: b2,
+                    env: e2,
+                    ..
+                },
+            ) => p1 == p2 && b1 == b2 && Rc::ptr_eq(e1, e2),
+            (Object::Builtin { name: n1, .. }, Object::Builtin { name: n2, .. }) => n1 == n2,
+            (Object::ReturnValue(a), Object::ReturnValue(b)) => a == b,
+            (Object::Break, Object::Break) | (Object::Continue, Object::Continue) => true,
+            (Object::CompiledFunction(a), Object::CompiledFunction(b)) => a == b,
+            (Object::Closure { func: f1, free: v1 }, Object::Closure { func: f2, free: v2 }) => {
+                Rc::ptr_eq(f1, f2) && v1 == v2
+            }
+            (Object::Pattern(a), Object::Pattern(b)) => a == b,
+            (Object::Cell(a), Object::Cell(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Object::Null => write!(f, "null"),
+            Object::Integer(n) => write!(f, "{}", n),
+            Object::Float(n) => write!(f, "{}", token::format_float(*n)),
+            Object::Boolean(b) => write!(f, "{}", b),
+            Object::String(s) => write!(f, "{}", s),
+            Object::Complex { re, im } => write!(f, "{}{:+}i", re, im),
+            Object::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(inspect).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Object::Range {
+                start,
+                end,
+                inclusive,
+            } => write!(
+                f,
+                "{}{}{}",
+                start,
+                if *inclusive { "..=" } else { ".." },
+                end
+            ),
+            Object::Hash(pairs) => {
+                let pairs: Vec<String> = pairs
+                    .iter()
+                    .map(|(k, v)| format!("{}: {}", inspect(&k.clone().into()), inspect(v)))
+                    .collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Object::Function { parameters, .. } => {
+                write!(f, "fn({}) {{ ... }}", parameters)
+            }
+            Object::Builtin { name, .. } => write!(f, "builtin function {}", name),
+            Object::ReturnValue(value) => Display::fmt(value, f),
+            Object::Break => write!(f, "break"),
+            Object::Continue => write!(f, "continue"),
+            Object::CompiledFunction(func) => {
+                write!(f, "compiled function[{:p}]", Rc::as_ptr(func))
+            }
+            Object::Closure { func, .. } => write!(f, "closure[{:p}]", Rc::as_ptr(func)),
+            Object::Pattern(pattern) => write!(f, "pattern {}", pattern),
+            Object::Cell(cell) => Display::fmt(&cell.borrow(), f),
+        }
+    }
+}
+
+// Strings are quoted when they are shown inside of a container
+pub fn inspect(obj: &Object) -> String {
+    match obj {
+        Object::String(s) => format!("\"{}\"", token::escape_str(s)),
+        _ => obj.to_string(),
+    }
+}
+
+// How many integers a range holds. It is empty when the end comes first.
+pub fn range_len(start: i64, end: i64, inclusive: bool) -> u64 {
+    let len = i128::from(end) - i128::from(start) + i128::from(inclusive);
+    u64::try_from(len.max(0)).unwrap_or(u64::MAX)
+}
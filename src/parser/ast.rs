@@ -1,6 +1,13 @@
+use std::fmt::{self, Display};
+
+use crate::formatter::Printer;
+use crate::lexer::span::Spanned;
 use crate::lexer::token::Token;
 
 pub type BlockStmt = Vec<Statement>;
+pub type Statement = Spanned<StmtKind>;
+pub type Expression = Spanned<ExprKind>;
+pub type Pattern = Spanned<PatternKind>;
 
 #[repr(transparent)]
 #[derive(Debug)]
@@ -28,25 +35,51 @@ impl PartialEq<Program> for Vec<Statement> {
     }
 }
 
-#[derive(Debug, PartialEq, Clone, Eq)]
-pub enum Statement {
-    LetStmt { name: String, value: Expression },
-    ReturnStmt { value: Expression },
-    ExpressionStmt { expression: Expression },
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, PartialEq, Clone)]
+pub enum StmtKind {
+    LetStmt {
+        name: String,
+        value: Expression,
+    },
+    // let [a, b] = value; which fails when value does not match the pattern
+    LetPatternStmt {
+        pattern: Pattern,
+        value: Expression,
+    },
+    ReturnStmt {
+        value: Expression,
+    },
+    ExpressionStmt {
+        expression: Expression,
+    },
+    WhileStmt {
+        condition: Expression,
+        body: BlockStmt,
+    },
+    // variable is bound to each value of iterable in turn
+    ForStmt {
+        variable: String,
+        iterable: Expression,
+        body: BlockStmt,
+    },
+    BreakStmt,
+    ContinueStmt,
 }
 
-#[derive(Debug, PartialEq, Clone, Eq)]
-pub enum Expression {
+#[derive(Debug, PartialEq, Clone)]
+pub enum ExprKind {
     Ident(String),
     String(String),
+    // "a${x}b": the literal parts are kept apart from the interpolated
+    // expressions, empty ones are left out
+    Interpolated(Vec<StringPart>),
     Boolean(bool),
     Integer(i64),
+    Float(f64),
+    // An imaginary literal like 4i, which `3 + 4i` adds to a real number
+    Imaginary(f64),
     Array(Vec<Expression>),
-    // Yet a complex number is just implemented with integer values
-    Complex {
-        re: i64,
-        im: i64,
-    },
     Hash {
         key: Vec<Expression>,
         value: Vec<Expression>,
@@ -60,30 +93,223 @@ pub enum Expression {
         operator: Token,
         right: Box<Expression>,
     },
+    // a..b, or a..=b when inclusive. The bounds may be left out only when
+    // the range is the index of a slice, like arr[2..].
+    Range {
+        start: Option<Box<Expression>>,
+        end: Option<Box<Expression>>,
+        inclusive: bool,
+    },
+    // operator is ASSIGN or a compound one like PLUSEQ. target is a name or
+    // an index expression on one.
+    Assign {
+        target: Box<Expression>,
+        operator: Token,
+        value: Box<Expression>,
+    },
     IfExpr {
         condition: Box<Expression>,
         consequence: BlockStmt,
         alternative: Option<BlockStmt>,
     },
+    // A lambda like |x| x * 2 or x => x * 2 is a function too, whose body is
+    // the one expression. shorthand is set for it.
     Function {
-        parameters: Vec<String>,
+        parameters: Parameters,
         body: BlockStmt,
+        shorthand: bool,
     },
+    // The last keywords.len() arguments are given by name, as in f(1, y: 2).
+    // xs |> f(1) is the call f(xs, 1), which is piped.
     Call {
         function: Box<Expression>,
         arguments: Vec<Expression>,
+        keywords: Vec<String>,
+        piped: bool,
+    },
+    // The value of the first arm whose pattern matches the subject and whose
+    // guard holds, or null when there is none
+    Match {
+        subject: Box<Expression>,
+        arms: Vec<MatchArm>,
+    },
+}
+
+// The parameters of fn(x, y = 10, ..rest). Only the last names may have a
+// default, and the rest comes after all of them.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Parameters {
+    pub names: Vec<String>,
+    // The defaults of the last defaults.len() names
+    pub defaults: Vec<Expression>,
+    pub rest: Option<String>,
+}
+
+impl Parameters {
+    // Parameters without defaults or a rest
+    pub fn new(names: Vec<String>) -> Self {
+        Self {
+            names,
+            ..Self::default()
+        }
+    }
+
+    // How many arguments a call must give at least
+    pub fn required(&self) -> usize {
+        self.names.len() - self.defaults.len()
+    }
+
+    // The names which are bound in the body, the rest last
+    pub fn bound_names(&self) -> impl Iterator<Item = &String> {
+        self.names.iter().chain(&self.rest)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub guard: Option<Expression>,
+    pub body: Expression,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum PatternKind {
+    // `_`
+    Wildcard,
+    Binding(String),
+    // A number, string or boolean, which matches the values equal to it
+    Literal(Expression),
+    // [a, b, ..rest] matches arrays with at least two elements. Without a
+    // rest the length must be the same. rest is Some(None) for a `..` which
+    // names nothing.
+    Array {
+        elements: Vec<Pattern>,
+        rest: Option<Option<String>>,
+    },
+    // {"x": x} matches hashes which have every key, and maybe others
+    Hash {
+        keys: Vec<Expression>,
+        values: Vec<Pattern>,
     },
 }
 
+impl Pattern {
+    // The names the pattern binds, in the order their values are taken
+    pub fn bindings(&self) -> Vec<&str> {
+        let mut names = Vec::new();
+        self.collect_bindings(&mut names);
+        names
+    }
+
+    fn collect_bindings<'a>(&'a self, names: &mut Vec<&'a str>) {
+        match &self.kind {
+            PatternKind::Binding(name) => names.push(name),
+            PatternKind::Array { elements, rest } => {
+                for element in elements {
+                    element.collect_bindings(names);
+                }
+                if let Some(Some(rest)) = rest {
+                    names.push(rest);
+                }
+            }
+            PatternKind::Hash { values, .. } => {
+                for value in values {
+                    value.collect_bindings(names);
+                }
+            }
+            PatternKind::Wildcard | PatternKind::Literal(_) => {}
+        }
+    }
+
+    // Whether every value which other matches is matched by self too
+    pub fn covers(&self, other: &Pattern) -> bool {
+        match (&self.kind, &other.kind) {
+            (PatternKind::Wildcard, _) | (PatternKind::Binding(_), _) => true,
+            (PatternKind::Literal(a), PatternKind::Literal(b)) => a == b,
+            (
+                PatternKind::Array { elements, rest },
+                PatternKind::Array {
+                    elements: other_elements,
+                    rest: other_rest,
+                },
+            ) => {
+                let lengths = match (rest, other_rest) {
+                    (None, None) => elements.len() == other_elements.len(),
+                    (None, Some(_)) => false,
+                    (Some(_), _) => elements.len() <= other_elements.len(),
+                };
+                lengths
+                    && elements
+                        .iter()
+                        .zip(other_elements)
+                        .all(|(element, other)| element.covers(other))
+            }
+            (
+                PatternKind::Hash { keys, values },
+                PatternKind::Hash {
+                    keys: other_keys,
+                    values: other_values,
+                },
+            ) => keys.iter().zip(values).all(|(key, value)| {
+                other_keys
+                    .iter()
+                    .position(|other_key| other_key == key)
+                    .is_some_and(|i| value.covers(&other_values[i]))
+            }),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum StringPart {
+    Literal(String),
+    Expr(Expression),
+}
+
 impl From<Box<Expression>> for Statement {
     fn from(expr: Box<Expression>) -> Self {
-        Self::ExpressionStmt { expression: *expr }
+        Self::from(*expr)
     }
 }
 
 impl From<Expression> for Statement {
     fn from(expr: Expression) -> Self {
-        Self::ExpressionStmt { expression: expr }
+        let span = expr.span;
+        Self::new(StmtKind::ExpressionStmt { expression: expr }, span)
+    }
+}
+
+// Both are displayed as canonical source, see the formatter
+impl Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut printer = Printer::new(None);
+        printer.stmt(self);
+        write!(f, "{}", printer.out)
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut printer = Printer::new(None);
+        printer.expr(self);
+        write!(f, "{}", printer.out)
+    }
+}
+
+impl Display for Parameters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut printer = Printer::new(None);
+        printer.parameters(self);
+        write!(f, "{}", printer.out)
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut printer = Printer::new(None);
+        printer.pattern(self);
+        write!(f, "{}", printer.out)
     }
 }
 
@@ -91,8 +317,17 @@ impl From<Expression> for Statement {
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub enum Precedence {
     LOWEST,
+    ASSIGN,
+    PIPELINE,
+    RANGE,
+    OR,
+    AND,
+    BITOR,
+    BITXOR,
+    BITAND,
     EQUALS,
     LESSGREATER,
+    SHIFT,
     SUM,
     PRODUCT,
     POWER,
@@ -106,12 +341,32 @@ impl Precedence {
         match tok {
             Token::EQ => Precedence::EQUALS,
             Token::NOTEQ => Precedence::EQUALS,
+            Token::ASSIGN => Precedence::ASSIGN,
+            Token::PLUSEQ => Precedence::ASSIGN,
+            Token::MINUSEQ => Precedence::ASSIGN,
+            Token::ASTERISKEQ => Precedence::ASSIGN,
+            Token::SLASHEQ => Precedence::ASSIGN,
+            // xs |> f() |> g() takes in everything but assignments
+            Token::PIPELINE => Precedence::PIPELINE,
+            Token::DOTDOT => Precedence::RANGE,
+            Token::DOTDOTEQ => Precedence::RANGE,
+            Token::OR => Precedence::OR,
+            Token::AND => Precedence::AND,
+            // As in C, the bitwise operators bind looser than comparisons
+            Token::BITOR => Precedence::BITOR,
+            Token::BITXOR => Precedence::BITXOR,
+            Token::BITAND => Precedence::BITAND,
+            Token::SHL => Precedence::SHIFT,
+            Token::SHR => Precedence::SHIFT,
             Token::LT => Precedence::LESSGREATER,
             Token::GT => Precedence::LESSGREATER,
+            Token::LTEQ => Precedence::LESSGREATER,
+            Token::GTEQ => Precedence::LESSGREATER,
             Token::PLUS => Precedence::SUM,
             Token::MINUS => Precedence::SUM,
             Token::ASTERISK => Precedence::PRODUCT,
             Token::SLASH => Precedence::PRODUCT,
+            Token::PERCENT => Precedence::PRODUCT,
             Token::POWER => Precedence::POWER,
             Token::LPAREN => Precedence::CALL,
             Token::LBRACKET => Precedence::INDEX,
@@ -7,6 +7,23 @@ macro_rules! expect_peek {
             return Err(Error::ParseTokDiffErr {
                 expected: $e1,
                 got: $e.take_token().1.clone(),
+                span: $e.peek_span(),
+            });
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! expect_close {
+    ($e: expr => opened at $open: expr) => {
+        if $e.take_token().1 == &$open.kind.closing_delimiter() {
+            $e.next_token();
+        } else {
+            return Err(Error::UnclosedDelimErr {
+                open: $open.kind.clone(),
+                open_span: $open.span,
+                got: $e.take_token().1.clone(),
+                span: $e.peek_span(),
             });
         }
     };
@@ -15,8 +32,8 @@ macro_rules! expect_peek {
 #[macro_export]
 macro_rules! check_position {
     ($ident: ident := $self: expr, $num: expr) => {
-        let $ident = if $self.cur_position < $self.l.len() - $num {
-            &$self.l[$self.cur_position + $num]
+        let $ident = if $self.cur_position + $num < $self.l.len() {
+            &$self.l[$self.cur_position + $num].kind
         } else {
             &Token::EOF
         };
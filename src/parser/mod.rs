@@ -4,18 +4,35 @@ pub mod ast;
 #[cfg(test)]
 mod parser_test;
 
+use std::mem;
+
 use crate::error;
+use crate::lexer::span::{Span, Spanned};
 use crate::lexer::token::Token;
 use crate::lexer::Lexer;
-use crate::parser::ast::{BlockStmt, Expression, Precedence, Program, Statement};
+use crate::parser::ast::{
+    BlockStmt, ExprKind, Expression, MatchArm, Parameters, Pattern, PatternKind, Precedence,
+    Program, Statement, StmtKind, StringPart,
+};
 
 type Error = error::MonkeyErr;
+type Warning = error::MonkeyWarn;
 type PrefixParseFn = fn(&mut Parser) -> error::Result<Expression>;
 type InfixParseFn = fn(&mut Parser, &Expression) -> error::Result<Expression>;
 
 pub struct Parser {
-    l: Vec<Token>,
+    l: Vec<Spanned<Token>>,
     cur_position: usize,
+    // Errors of the lexer, and the ones collected by parse_program_recovering
+    errors: Vec<Error>,
+    recovering: bool,
+    block_depth: usize,
+    // Loops around the current token, up to the nearest function literal
+    loop_depth: usize,
+    warnings: Vec<Warning>,
+    // Inside of a match guard, `x =>` ends the guard instead of starting a
+    // lambda
+    in_guard: bool,
 }
 
 impl Parser {
@@ -30,12 +47,51 @@ impl Parser {
         (cur_tok, peek_tok, twopeek_tok)
     }
 
-    pub fn new(l: Lexer<'_>) -> Self {
-        let l: Vec<Token> = l.collect();
-        Self { l, cur_position: 0 }
+    fn spanned_token(&self, num: usize) -> &Spanned<Token> {
+        let last = self.l.len() - 1;
+        &self.l[(self.cur_position + num).min(last)]
+    }
+
+    fn cur_span(&self) -> Span {
+        self.spanned_token(0).span
+    }
+
+    fn peek_span(&self) -> Span {
+        self.spanned_token(1).span
+    }
+
+    // Span from start to the current token
+    fn span_from(&self, start: Span) -> Span {
+        start.to(self.cur_span())
     }
 
+    pub fn new(mut l: Lexer<'_>) -> Self {
+        let toks: Vec<Spanned<Token>> = l.by_ref().collect();
+        Self {
+            l: toks,
+            cur_position: 0,
+            errors: l.errors,
+            recovering: false,
+            block_depth: 0,
+            loop_depth: 0,
+            warnings: Vec::new(),
+            in_guard: false,
+        }
+    }
+
+    // The warnings found so far, in the order of the source
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        let mut warnings = mem::take(&mut self.warnings);
+        warnings.sort_by_key(|warning| warning.span().start);
+        warnings
+    }
+
+    // Stops at the first error; only the tests need this for now
+    #[allow(dead_code)]
     pub fn parse_program(&mut self) -> error::Result<Program> {
+        if !self.errors.is_empty() {
+            return Err(self.errors.remove(0));
+        }
         let mut program = Program::new(vec![]);
         while self.take_token().0 != &Token::EOF {
             program.push(self.parse_statement()?);
@@ -44,21 +100,99 @@ impl Parser {
         Ok(program)
     }
 
+    // Parses the whole input without stopping at the first error. Broken
+    // statements are skipped and every error is returned along with the
+    // statements which could be parsed.
+    pub fn parse_program_recovering(&mut self) -> (Program, Vec<Error>) {
+        self.recovering = true;
+        let mut program = Program::new(vec![]);
+        while self.take_token().0 != &Token::EOF {
+            if let Ok(Some(stmt)) = self.parse_statement_or_skip() {
+                program.push(stmt);
+            }
+            self.next_token();
+        }
+        self.recovering = false;
+        let mut errors = mem::take(&mut self.errors);
+        errors.sort_by_key(|err| err.span().map(|span| span.start));
+        (program, errors)
+    }
+
+    // Only fails when not recovering. Otherwise a broken statement gives None.
+    fn parse_statement_or_skip(&mut self) -> error::Result<Option<Statement>> {
+        let start = self.cur_position;
+        match self.parse_statement() {
+            Ok(stmt) => Ok(Some(stmt)),
+            Err(err) if self.recovering => {
+                self.errors.push(err);
+                self.synchronize(start);
+                Ok(None)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    // Skips to the end of the broken statement which started at start: the
+    // next `;` or line break, the `}` of the enclosing block, or just before
+    // the next `let`, `return`, `while` or `for`. Braces opened inside the
+    // statement are skipped as a whole, so that a block is not mistaken for
+    // the end of the statement.
+    fn synchronize(&mut self, start: usize) {
+        let mut depth = self.l[start..self.cur_position.min(self.l.len())]
+            .iter()
+            .fold(0usize, |depth, tok| match tok.kind {
+                Token::LBRACE => depth + 1,
+                Token::RBRACE => depth.saturating_sub(1),
+                _ => depth,
+            });
+        loop {
+            match self.take_token().0 {
+                Token::EOF => return,
+                Token::SEMICOLON | Token::NEWLINE if depth == 0 => return,
+                Token::RBRACE if depth == 0 && self.block_depth > 0 => {
+                    // Leave the closing brace to the enclosing block
+                    self.cur_position -= 1;
+                    return;
+                }
+                Token::LBRACE => depth += 1,
+                Token::RBRACE => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            if depth == 0
+                && matches!(
+                    self.take_token().1,
+                    Token::LET | Token::RETURN | Token::WHILE | Token::FOR
+                )
+            {
+                return;
+            }
+            self.next_token();
+        }
+    }
+
     fn prefix_fn(&mut self) -> Option<PrefixParseFn> {
         match self.take_token().0 {
+            Token::IDENT(_) if self.take_token().1 == &Token::FATARROW && !self.in_guard => {
+                Some(Parser::parse_lambda)
+            }
             Token::IDENT(_) => Some(Parser::parse_identifier),
             Token::STRING(_) => Some(Parser::parse_string),
+            Token::INTERP_START(_) => Some(Parser::parse_interpolated),
             Token::INT(_) => Some(Parser::parse_number),
+            Token::FLOAT(_) => Some(Parser::parse_number),
             Token::IMEGINARY(_) => Some(Parser::parse_number),
             Token::TRUE => Some(Parser::parse_boolean),
             Token::FALSE => Some(Parser::parse_boolean),
             Token::BANG => Some(Parser::parse_prefix_expr),
             Token::MINUS => Some(Parser::parse_prefix_expr),
+            Token::BITNOT => Some(Parser::parse_prefix_expr),
             Token::LPAREN => Some(Parser::parse_grouped_expr),
             Token::LBRACE => Some(Parser::parse_hash_expr),
             Token::LBRACKET => Some(Parser::parse_array_expr),
             Token::IF => Some(Parser::parse_if_expr),
             Token::FUNCTION => Some(Parser::parse_function_literal),
+            Token::BITOR | Token::OR => Some(Parser::parse_lambda),
+            Token::MATCH => Some(Parser::parse_match_expr),
             _ => None,
         }
     }
@@ -69,68 +203,207 @@ impl Parser {
             Token::MINUS => Some(Parser::parse_infix_expr),
             Token::ASTERISK => Some(Parser::parse_infix_expr),
             Token::SLASH => Some(Parser::parse_infix_expr),
+            Token::PERCENT => Some(Parser::parse_infix_expr),
+            Token::LTEQ => Some(Parser::parse_infix_expr),
+            Token::GTEQ => Some(Parser::parse_infix_expr),
+            Token::AND => Some(Parser::parse_infix_expr),
+            Token::OR => Some(Parser::parse_infix_expr),
+            Token::BITAND => Some(Parser::parse_infix_expr),
+            Token::BITOR => Some(Parser::parse_infix_expr),
+            Token::BITXOR => Some(Parser::parse_infix_expr),
+            Token::SHL => Some(Parser::parse_infix_expr),
+            Token::SHR => Some(Parser::parse_infix_expr),
             Token::EQ => Some(Parser::parse_infix_expr),
             Token::NOTEQ => Some(Parser::parse_infix_expr),
             Token::LT => Some(Parser::parse_infix_expr),
             Token::GT => Some(Parser::parse_infix_expr),
             Token::POWER => Some(Parser::parse_infix_expr),
+            Token::DOTDOT => Some(Parser::parse_range_expr),
+            Token::DOTDOTEQ => Some(Parser::parse_range_expr),
+            Token::ASSIGN => Some(Parser::parse_assign_expr),
+            Token::PLUSEQ => Some(Parser::parse_assign_expr),
+            Token::MINUSEQ => Some(Parser::parse_assign_expr),
+            Token::ASTERISKEQ => Some(Parser::parse_assign_expr),
+            Token::SLASHEQ => Some(Parser::parse_assign_expr),
             Token::LPAREN => Some(Parser::parse_call_expr),
             Token::LBRACKET => Some(Parser::parse_index_expr),
+            Token::PIPELINE => Some(Parser::parse_pipeline_expr),
             _ => None,
         }
     }
 
     fn parse_statement(&mut self) -> error::Result<Statement> {
-        match self.take_token().0 {
+        let mut stmt = match self.take_token().0 {
             Token::LET => self.parse_let_stmt(),
             Token::RETURN => self.parse_return_stmt(),
+            Token::WHILE => self.parse_while_stmt(),
+            Token::FOR => self.parse_for_stmt(),
+            Token::BREAK | Token::CONTINUE => self.parse_loop_control_stmt(),
             _ => self.parse_expression_stmt(),
+        }?;
+        self.end_statement(&mut stmt)?;
+        Ok(stmt)
+    }
+
+    // A statement ends at a `;`, at a line break which the lexer turned into
+    // a NEWLINE, or where its block or the input ends. After a statement
+    // which ends with a block, like a loop, the next one may follow on the
+    // same line.
+    fn end_statement(&mut self, stmt: &mut Statement) -> error::Result<()> {
+        match self.take_token() {
+            (_, Token::SEMICOLON, _) => {
+                self.next_token();
+                // The block of a loop is found by where its statement ends
+                if !matches!(
+                    stmt.kind,
+                    StmtKind::WhileStmt { .. } | StmtKind::ForStmt { .. }
+                ) {
+                    stmt.span = stmt.span.to(self.cur_span());
+                }
+            }
+            (_, Token::NEWLINE, _) => self.next_token(),
+            (_, Token::EOF, _) | (_, Token::RBRACE, _) | (Token::RBRACE, _, _) => {}
+            (_, got, _) => {
+                return Err(Error::StatementEndErr {
+                    got: got.clone(),
+                    span: self.peek_span(),
+                })
+            }
         }
+        Ok(())
     }
 
     fn parse_let_stmt(&mut self) -> error::Result<Statement> {
-        expect_peek!(self => Token::IDENT(String::new()));
-        let name = self.take_token().0.unwrap_string()?;
+        let start = self.cur_span();
+        // A name, or an array or hash pattern which destructures the value
+        let pattern = if matches!(self.take_token().1, Token::LBRACKET | Token::LBRACE) {
+            self.next_token();
+            self.parse_pattern()?
+        } else {
+            expect_peek!(self => Token::IDENT(String::new()));
+            let name = self.spanned_token(0).unwrap_string()?;
+            Pattern::new(PatternKind::Binding(name), self.cur_span())
+        };
         expect_peek!(self => Token::ASSIGN);
         self.next_token();
 
         let value = self.parse_expression(Precedence::LOWEST)?;
 
-        if self.take_token().0 != &Token::SEMICOLON {
-            self.next_token();
-        }
-
-        Ok(Statement::LetStmt { name, value })
+        let kind = match pattern.kind {
+            PatternKind::Binding(name) => StmtKind::LetStmt { name, value },
+            _ => StmtKind::LetPatternStmt { pattern, value },
+        };
+        Ok(Statement::new(kind, self.span_from(start)))
     }
 
     fn parse_return_stmt(&mut self) -> error::Result<Statement> {
+        let start = self.cur_span();
         self.next_token();
 
         let value = self.parse_expression(Precedence::LOWEST)?;
 
-        if self.take_token().0 != &Token::SEMICOLON {
-            self.next_token();
+        Ok(Statement::new(
+            StmtKind::ReturnStmt { value },
+            self.span_from(start),
+        ))
+    }
+
+    fn parse_while_stmt(&mut self) -> error::Result<Statement> {
+        let start = self.cur_span();
+        expect_peek!(self => Token::LPAREN);
+        let open = self.spanned_token(0).clone();
+
+        self.next_token();
+        let condition = self.parse_expression(Precedence::LOWEST)?;
+
+        expect_close!(self => opened at open);
+        expect_peek!(self => Token::LBRACE);
+        let body = self.parse_loop_body()?;
+
+        Ok(Statement::new(
+            StmtKind::WhileStmt { condition, body },
+            self.span_from(start),
+        ))
+    }
+
+    fn parse_for_stmt(&mut self) -> error::Result<Statement> {
+        let start = self.cur_span();
+        expect_peek!(self => Token::LPAREN);
+        let open = self.spanned_token(0).clone();
+        expect_peek!(self => Token::IDENT(String::new()));
+        let variable = self.spanned_token(0).unwrap_string()?;
+        expect_peek!(self => Token::IN);
+
+        self.next_token();
+        let iterable = self.parse_expression(Precedence::LOWEST)?;
+
+        expect_close!(self => opened at open);
+        expect_peek!(self => Token::LBRACE);
+        let body = self.parse_loop_body()?;
+
+        Ok(Statement::new(
+            StmtKind::ForStmt {
+                variable,
+                iterable,
+                body,
+            },
+            self.span_from(start),
+        ))
+    }
+
+    fn parse_loop_body(&mut self) -> error::Result<BlockStmt> {
+        self.loop_depth += 1;
+        let body = self.parse_block_statement();
+        self.loop_depth -= 1;
+        body
+    }
+
+    // break and continue belong to the nearest loop, which must be in the
+    // same function
+    fn parse_loop_control_stmt(&mut self) -> error::Result<Statement> {
+        let start = self.cur_span();
+        let keyword = self.take_token().0.clone();
+        if self.loop_depth == 0 {
+            return Err(Error::LoopControlErr {
+                keyword,
+                span: start,
+            });
         }
-        Ok(Statement::ReturnStmt { value })
+        let kind = if keyword == Token::BREAK {
+            StmtKind::BreakStmt
+        } else {
+            StmtKind::ContinueStmt
+        };
+        Ok(Statement::new(kind, self.span_from(start)))
     }
 
     fn parse_expression_stmt(&mut self) -> error::Result<Statement> {
+        let start = self.cur_span();
         let expression = self.parse_expression(Precedence::LOWEST)?;
-        if self.take_token().1 == &Token::SEMICOLON {
-            self.next_token();
-        }
-        Ok(Statement::ExpressionStmt { expression })
+        Ok(Statement::new(
+            StmtKind::ExpressionStmt { expression },
+            self.span_from(start),
+        ))
     }
 
     fn parse_expression(&mut self, prece: Precedence) -> error::Result<Expression> {
-        let mut left_exp = if let Some(prefix) = self.prefix_fn() {
+        let left_exp = if let Some(prefix) = self.prefix_fn() {
             prefix(self)?
         } else {
             return Err(Error::PrefixParseNoneErr {
                 got: self.take_token().0.clone(),
+                span: self.cur_span(),
             });
         };
+        self.continue_expression(left_exp, prece)
+    }
 
+    // Parses the infix operators which follow left_exp
+    fn continue_expression(
+        &mut self,
+        mut left_exp: Expression,
+        prece: Precedence,
+    ) -> error::Result<Expression> {
         while self.take_token().1 != &Token::SEMICOLON
             && prece < Precedence::take_precedence(self.take_token().1)
         {
@@ -140,6 +413,7 @@ impl Parser {
             } else {
                 return Err(Error::InfixParseNoneErr {
                     got: self.take_token().1.clone(),
+                    span: self.peek_span(),
                 });
             };
         }
@@ -149,84 +423,131 @@ impl Parser {
 
     fn parse_identifier(&mut self) -> error::Result<Expression> {
         if let Token::IDENT(i) = self.take_token().0 {
-            Ok(Expression::Ident(i.to_string()))
+            Ok(Expression::new(
+                ExprKind::Ident(i.to_string()),
+                self.cur_span(),
+            ))
         } else {
             Err(Error::ParseExprErr {
                 expected: "identifier".to_string(),
                 got: self.take_token().0.clone(),
+                span: self.cur_span(),
             })
         }
     }
 
     fn parse_string(&mut self) -> error::Result<Expression> {
         if let Token::STRING(s) = self.take_token().0 {
-            Ok(Expression::String(s.to_string()))
+            Ok(Expression::new(
+                ExprKind::String(s.to_string()),
+                self.cur_span(),
+            ))
         } else {
             Err(Error::ParseExprErr {
                 expected: "string".to_string(),
                 got: self.take_token().0.clone(),
+                span: self.cur_span(),
             })
         }
     }
 
-    fn parse_number(&mut self) -> error::Result<Expression> {
-        let (expr, move_num) = match self.take_token().0 {
-            Token::IMEGINARY(i) => (Expression::Complex { re: 0, im: *i }, false),
-            Token::INT(n) => {
-                if let Token::IMEGINARY(i) = self.take_token().2 {
-                    if self.take_token().1 == &Token::PLUS {
-                        (Expression::Complex { re: *n, im: *i }, true)
-                    } else if self.take_token().1 == &Token::MINUS {
-                        (Expression::Complex { re: *n, im: -*i }, true)
-                    } else {
-                        (Expression::Integer(*n), false)
+    // The lexer gives the literal parts as INTERP_START, INTERP_MID and
+    // INTERP_END tokens, with the interpolated expressions between them
+    fn parse_interpolated(&mut self) -> error::Result<Expression> {
+        let start = self.cur_span();
+        let mut parts = Vec::new();
+        loop {
+            match self.take_token().0 {
+                Token::INTERP_START(s) | Token::INTERP_MID(s) if !s.is_empty() => {
+                    parts.push(StringPart::Literal(s.clone()))
+                }
+                _ => {}
+            }
+            self.next_token();
+            parts.push(StringPart::Expr(self.parse_expression(Precedence::LOWEST)?));
+
+            match self.take_token().1 {
+                Token::INTERP_MID(_) => self.next_token(),
+                Token::INTERP_END(s) => {
+                    if !s.is_empty() {
+                        parts.push(StringPart::Literal(s.clone()));
                     }
-                } else {
-                    (Expression::Integer(*n), false)
+                    self.next_token();
+                    break;
+                }
+                got => {
+                    return Err(Error::ParseTokDiffErr {
+                        expected: Token::INTERP_END(String::new()),
+                        got: got.clone(),
+                        span: self.peek_span(),
+                    })
                 }
             }
+        }
+        Ok(Expression::new(
+            ExprKind::Interpolated(parts),
+            self.span_from(start),
+        ))
+    }
+
+    fn parse_number(&mut self) -> error::Result<Expression> {
+        let expr = match self.take_token().0 {
+            Token::INT(n) => ExprKind::Integer(*n),
+            Token::FLOAT(n) => ExprKind::Float(*n),
+            Token::IMEGINARY(n) => ExprKind::Imaginary(*n),
             _ => {
                 return Err(Error::ParseExprErr {
                     expected: "number".to_string(),
                     got: self.take_token().0.clone(),
+                    span: self.cur_span(),
                 })
             }
         };
-
-        if move_num {
-            self.next_token();
-            self.next_token();
-        }
-        Ok(expr)
+        Ok(Expression::new(expr, self.cur_span()))
     }
 
     #[allow(clippy::unnecessary_wraps)]
     fn parse_boolean(&mut self) -> error::Result<Expression> {
-        Ok(Expression::Boolean(self.take_token().0 == &Token::TRUE))
+        Ok(Expression::new(
+            ExprKind::Boolean(self.take_token().0 == &Token::TRUE),
+            self.cur_span(),
+        ))
     }
 
     fn parse_prefix_expr(&mut self) -> error::Result<Expression> {
+        let start = self.cur_span();
         let operator = self.take_token().0.clone();
         self.next_token();
 
         let right = Box::new(self.parse_expression(Precedence::PREFIX)?);
-        Ok(Expression::Prefix { operator, right })
+        Ok(Expression::new(
+            ExprKind::Prefix { operator, right },
+            self.span_from(start),
+        ))
     }
 
     fn parse_grouped_expr(&mut self) -> error::Result<Expression> {
+        let open = self.spanned_token(0).clone();
         self.next_token();
 
         let exp = self.parse_expression(Precedence::LOWEST)?;
-        expect_peek!(self => Token::RPAREN);
+        expect_close!(self => opened at open);
 
-        Ok(exp)
+        // The parentheses belong to the span of the inner expression
+        Ok(Expression::new(exp.kind, self.span_from(open.span)))
     }
 
     fn parse_array_expr(&mut self) -> error::Result<Expression> {
-        Ok(Expression::Array(self.parse_expr_list(Token::RBRACKET)?))
+        let start = self.cur_span();
+        let elements = self.parse_expr_list(Token::RBRACKET)?;
+        Ok(Expression::new(
+            ExprKind::Array(elements),
+            self.span_from(start),
+        ))
     }
 
     fn parse_hash_expr(&mut self) -> error::Result<Expression> {
+        let open = self.spanned_token(0).clone();
         let mut key = Vec::<Expression>::new();
         let mut value = Vec::<Expression>::new();
 
@@ -238,35 +559,111 @@ impl Parser {
             self.next_token();
             value.push(self.parse_expression(Precedence::LOWEST)?);
 
-            if self.take_token().1 != &Token::RBRACE {
-                expect_peek!(self => Token::COMMA);
+            if self.take_token().1 == &Token::COMMA {
+                self.next_token();
+            } else {
+                break;
             }
         }
 
-        expect_peek!(self => Token::RBRACE);
+        expect_close!(self => opened at open);
+
+        Ok(Expression::new(
+            ExprKind::Hash { key, value },
+            self.span_from(open.span),
+        ))
+    }
 
-        Ok(Expression::Hash { key, value })
+    // The value is parsed at the lowest precedence, which makes assignment
+    // right associative: `a = b = 1` assigns to b first
+    fn parse_assign_expr(&mut self, target: &Expression) -> error::Result<Expression> {
+        if !is_assignable(target) {
+            return Err(Error::AssignTargetErr { span: target.span });
+        }
+        let operator = self.take_token().0.clone();
+        self.next_token();
+        let value = Box::new(self.parse_expression(Precedence::LOWEST)?);
+
+        Ok(Expression::new(
+            ExprKind::Assign {
+                target: Box::new(target.clone()),
+                operator,
+                value,
+            },
+            self.span_from(target.span),
+        ))
     }
 
     fn parse_index_expr(&mut self, left: &Expression) -> error::Result<Expression> {
+        let open = self.spanned_token(0).clone();
+        let right = Box::new(self.parse_index()?);
+        expect_close!(self => opened at open);
+
+        Ok(Expression::new(
+            ExprKind::Infix {
+                left: Box::new(left.clone()),
+                operator: Token::LBRACKET,
+                right,
+            },
+            self.span_from(left.span),
+        ))
+    }
+
+    // The index may be a range without a start or an end, like the ones of
+    // arr[..2] and arr[2..], which slices the indexed value
+    fn parse_index(&mut self) -> error::Result<Expression> {
+        let start = if is_range_operator(self.take_token().1) {
+            None
+        } else {
+            self.next_token();
+            let start = self.parse_expression(Precedence::RANGE)?;
+            if !is_range_operator(self.take_token().1) {
+                return self.continue_expression(start, Precedence::LOWEST);
+            }
+            Some(Box::new(start))
+        };
         self.next_token();
-        let right = Box::new(self.parse_expression(Precedence::LOWEST)?);
-        expect_peek!(self => Token::RBRACKET);
+        let start_span = start.as_ref().map_or(self.cur_span(), |start| start.span);
+        let inclusive = self.take_token().0 == &Token::DOTDOTEQ;
+        let end = if !inclusive && self.take_token().1 == &Token::RBRACKET {
+            None
+        } else {
+            self.next_token();
+            Some(Box::new(self.parse_expression(Precedence::RANGE)?))
+        };
+        Ok(Expression::new(
+            ExprKind::Range {
+                start,
+                end,
+                inclusive,
+            },
+            self.span_from(start_span),
+        ))
+    }
 
-        Ok(Expression::Infix {
-            left: Box::new(left.clone()),
-            operator: Token::LBRACKET,
-            right,
-        })
+    fn parse_range_expr(&mut self, start: &Expression) -> error::Result<Expression> {
+        let inclusive = self.take_token().0 == &Token::DOTDOTEQ;
+        self.next_token();
+        let end = self.parse_expression(Precedence::RANGE)?;
+        Ok(Expression::new(
+            ExprKind::Range {
+                start: Some(Box::new(start.clone())),
+                end: Some(Box::new(end)),
+                inclusive,
+            },
+            self.span_from(start.span),
+        ))
     }
 
     fn parse_if_expr(&mut self) -> error::Result<Expression> {
+        let start = self.cur_span();
         expect_peek!(self => Token::LPAREN);
+        let open = self.spanned_token(0).clone();
 
         self.next_token();
         let condition = Box::new(self.parse_expression(Precedence::LOWEST)?);
 
-        expect_peek!(self => Token::RPAREN);
+        expect_close!(self => opened at open);
         expect_peek!(self => Token::LBRACE);
 
         let consequence = self.parse_block_statement()?;
@@ -278,64 +675,374 @@ impl Parser {
             alternative = Some(self.parse_block_statement()?);
         }
 
-        Ok(Expression::IfExpr {
-            condition,
-            consequence,
-            alternative,
-        })
+        Ok(Expression::new(
+            ExprKind::IfExpr {
+                condition,
+                consequence,
+                alternative,
+            },
+            self.span_from(start),
+        ))
     }
 
-    fn parse_function_literal(&mut self) -> error::Result<Expression> {
+    fn parse_match_expr(&mut self) -> error::Result<Expression> {
+        let start = self.cur_span();
         expect_peek!(self => Token::LPAREN);
-        let parameters = self.parse_function_parameters()?;
+        let open = self.spanned_token(0).clone();
+
+        self.next_token();
+        let subject = Box::new(self.parse_expression(Precedence::LOWEST)?);
+
+        expect_close!(self => opened at open);
         expect_peek!(self => Token::LBRACE);
-        let body = self.parse_block_statement()?;
+        let open = self.spanned_token(0).clone();
 
-        Ok(Expression::Function { parameters, body })
+        let mut arms = Vec::new();
+        while self.take_token().1 != &Token::RBRACE {
+            self.next_token();
+            arms.push(self.parse_match_arm()?);
+            if self.take_token().1 == &Token::COMMA {
+                self.next_token();
+            } else {
+                break;
+            }
+        }
+        expect_close!(self => opened at open);
+        self.check_reachable(&arms);
+
+        Ok(Expression::new(
+            ExprKind::Match { subject, arms },
+            self.span_from(start),
+        ))
     }
 
-    fn parse_function_parameters(&mut self) -> error::Result<Vec<String>> {
-        let mut identifiers: Vec<_> = Vec::new();
-        if self.take_token().1 == &Token::RPAREN {
+    // The guard and the body are parsed at the lowest precedence, which stops
+    // before the `=>` and the `,` which follow them
+    fn parse_match_arm(&mut self) -> error::Result<MatchArm> {
+        let pattern = self.parse_pattern()?;
+        let guard = if self.take_token().1 == &Token::IF {
+            self.next_token();
             self.next_token();
-            return Ok(identifiers);
+            let outer = mem::replace(&mut self.in_guard, true);
+            let guard = self.parse_expression(Precedence::LOWEST);
+            self.in_guard = outer;
+            Some(guard?)
+        } else {
+            None
+        };
+        expect_peek!(self => Token::FATARROW);
+        self.next_token();
+        let body = self.parse_expression(Precedence::LOWEST)?;
+        Ok(MatchArm {
+            pattern,
+            guard,
+            body,
+        })
+    }
+
+    // An arm is never reached when an earlier arm without a guard matches
+    // everything it matches
+    fn check_reachable(&mut self, arms: &[MatchArm]) {
+        for (i, arm) in arms.iter().enumerate() {
+            let covering = arms[..i]
+                .iter()
+                .find(|earlier| earlier.guard.is_none() && earlier.pattern.covers(&arm.pattern));
+            if let Some(covering) = covering {
+                self.warnings.push(Warning::UnreachableArm {
+                    span: arm.pattern.span,
+                    covered_by: covering.pattern.span,
+                });
+            }
         }
+    }
 
-        self.next_token();
-        identifiers.push(self.take_token().0.unwrap_string()?);
+    fn parse_pattern(&mut self) -> error::Result<Pattern> {
+        let kind = match self.take_token().0 {
+            Token::IDENT(name) if name == "_" => PatternKind::Wildcard,
+            Token::IDENT(name) => PatternKind::Binding(name.clone()),
+            Token::LBRACKET => return self.parse_array_pattern(),
+            Token::LBRACE => return self.parse_hash_pattern(),
+            _ => {
+                let literal = self.parse_literal_pattern()?;
+                let span = literal.span;
+                return Ok(Pattern::new(PatternKind::Literal(literal), span));
+            }
+        };
+        Ok(Pattern::new(kind, self.cur_span()))
+    }
 
-        while self.take_token().1 == &Token::COMMA {
+    // Numbers, which may be negative, strings and booleans
+    fn parse_literal_pattern(&mut self) -> error::Result<Expression> {
+        match self.take_token() {
+            (Token::INT(_), ..) | (Token::FLOAT(_), ..) | (Token::IMEGINARY(_), ..) => {
+                self.parse_number()
+            }
+            (Token::STRING(_), ..) => self.parse_string(),
+            (Token::TRUE, ..) | (Token::FALSE, ..) => self.parse_boolean(),
+            (Token::MINUS, Token::INT(_), _)
+            | (Token::MINUS, Token::FLOAT(_), _)
+            | (Token::MINUS, Token::IMEGINARY(_), _) => {
+                let start = self.cur_span();
+                self.next_token();
+                let right = Box::new(self.parse_number()?);
+                Ok(Expression::new(
+                    ExprKind::Prefix {
+                        operator: Token::MINUS,
+                        right,
+                    },
+                    self.span_from(start),
+                ))
+            }
+            (got, ..) => Err(Error::ParseExprErr {
+                expected: "pattern".to_string(),
+                got: got.clone(),
+                span: self.cur_span(),
+            }),
+        }
+    }
+
+    // The rest, `..name` or `..`, comes after every element
+    fn parse_array_pattern(&mut self) -> error::Result<Pattern> {
+        let open = self.spanned_token(0).clone();
+        let mut elements = Vec::new();
+        let mut rest = None;
+
+        while self.take_token().1 != &Token::RBRACKET {
             self.next_token();
+            if self.take_token().0 == &Token::DOTDOT {
+                rest = Some(match self.take_token().1.clone() {
+                    Token::IDENT(name) => {
+                        self.next_token();
+                        if name == "_" {
+                            None
+                        } else {
+                            Some(name)
+                        }
+                    }
+                    _ => None,
+                });
+                break;
+            }
+            elements.push(self.parse_pattern()?);
+            if self.take_token().1 == &Token::COMMA {
+                self.next_token();
+            } else {
+                break;
+            }
+        }
+        expect_close!(self => opened at open);
+
+        Ok(Pattern::new(
+            PatternKind::Array { elements, rest },
+            self.span_from(open.span),
+        ))
+    }
+
+    fn parse_hash_pattern(&mut self) -> error::Result<Pattern> {
+        let open = self.spanned_token(0).clone();
+        let mut keys = Vec::new();
+        let mut values = Vec::new();
+
+        while self.take_token().1 != &Token::RBRACE {
             self.next_token();
-            identifiers.push(self.take_token().0.unwrap_string()?);
+            keys.push(self.parse_literal_pattern()?);
+            expect_peek!(self => Token::COLON);
+
+            self.next_token();
+            values.push(self.parse_pattern()?);
+
+            if self.take_token().1 == &Token::COMMA {
+                self.next_token();
+            } else {
+                break;
+            }
         }
+        expect_close!(self => opened at open);
 
-        expect_peek!(self => Token::RPAREN);
+        Ok(Pattern::new(
+            PatternKind::Hash { keys, values },
+            self.span_from(open.span),
+        ))
+    }
+
+    // Neither the defaults nor the body are inside the loops around the
+    // function
+    fn parse_function_literal(&mut self) -> error::Result<Expression> {
+        let start = self.cur_span();
+        let outer_loops = mem::take(&mut self.loop_depth);
+        let function = self.parse_function_parts();
+        self.loop_depth = outer_loops;
+        let (parameters, body) = function?;
+
+        Ok(Expression::new(
+            ExprKind::Function {
+                parameters,
+                body,
+                shorthand: false,
+            },
+            self.span_from(start),
+        ))
+    }
 
-        Ok(identifiers)
+    fn parse_function_parts(&mut self) -> error::Result<(Parameters, BlockStmt)> {
+        expect_peek!(self => Token::LPAREN);
+        let parameters = self.parse_function_parameters()?;
+        expect_peek!(self => Token::LBRACE);
+        Ok((parameters, self.parse_block_statement()?))
+    }
+
+    // The parameters between `(` and `)`, or `|` and `|` for a lambda. A rest
+    // parameter, `..name`, comes after every other parameter.
+    fn parse_function_parameters(&mut self) -> error::Result<Parameters> {
+        let open = self.spanned_token(0).clone();
+        let close = open.kind.closing_delimiter();
+        let mut parameters = Parameters::default();
+
+        while self.take_token().1 != &close {
+            self.next_token();
+            if self.take_token().0 == &Token::DOTDOT {
+                self.next_token();
+                parameters.rest = Some(self.spanned_token(0).unwrap_string()?);
+                break;
+            }
+            let name = self.spanned_token(0).unwrap_string()?;
+            if self.take_token().1 == &Token::ASSIGN {
+                self.next_token();
+                self.next_token();
+                // The default of a lambda parameter stops before the `|`
+                let default = self.parse_expression(Precedence::take_precedence(&close))?;
+                parameters.defaults.push(default);
+            } else if !parameters.defaults.is_empty() {
+                return Err(Error::ParameterOrderErr {
+                    name,
+                    span: self.cur_span(),
+                });
+            }
+            parameters.names.push(name);
+
+            if self.take_token().1 == &Token::COMMA {
+                self.next_token();
+            } else {
+                break;
+            }
+        }
+
+        expect_close!(self => opened at open);
+
+        Ok(parameters)
+    }
+
+    // |x, y| x + y, or x => x + 1 for a single parameter. The body is an
+    // expression, which goes on as far as it can.
+    fn parse_lambda(&mut self) -> error::Result<Expression> {
+        let start = self.cur_span();
+        let outer_loops = mem::take(&mut self.loop_depth);
+        let lambda = self.parse_lambda_parts();
+        self.loop_depth = outer_loops;
+        let (parameters, body) = lambda?;
+
+        let span = self.span_from(start);
+        let body = Statement::new(StmtKind::ExpressionStmt { expression: body }, span);
+        Ok(Expression::new(
+            ExprKind::Function {
+                parameters,
+                body: vec![body],
+                shorthand: true,
+            },
+            span,
+        ))
+    }
+
+    fn parse_lambda_parts(&mut self) -> error::Result<(Parameters, Expression)> {
+        let parameters = match self.take_token().0 {
+            Token::IDENT(name) => {
+                let parameters = Parameters::new(vec![name.clone()]);
+                self.next_token();
+                parameters
+            }
+            Token::OR => Parameters::default(),
+            _ => self.parse_function_parameters()?,
+        };
+        self.next_token();
+        let outer_guard = mem::replace(&mut self.in_guard, false);
+        let body = self.parse_expression(Precedence::LOWEST);
+        self.in_guard = outer_guard;
+        Ok((parameters, body?))
     }
 
     fn parse_block_statement(&mut self) -> error::Result<BlockStmt> {
+        self.block_depth += 1;
+        let stmts = self.parse_block_body();
+        self.block_depth -= 1;
+        stmts
+    }
+
+    fn parse_block_body(&mut self) -> error::Result<BlockStmt> {
+        let open = self.spanned_token(0).clone();
         let mut stmts: BlockStmt = Vec::new();
         self.next_token();
 
         while self.take_token().0 != &Token::RBRACE && self.take_token().0 != &Token::EOF {
-            stmts.push(self.parse_statement()?);
+            if let Some(stmt) = self.parse_statement_or_skip()? {
+                stmts.push(stmt);
+            }
             self.next_token();
         }
 
+        if self.take_token().0 == &Token::EOF {
+            return Err(Error::UnclosedDelimErr {
+                open: open.kind,
+                open_span: open.span,
+                got: Token::EOF,
+                span: self.cur_span(),
+            });
+        }
+
         Ok(stmts)
     }
 
     fn parse_call_expr(&mut self, fnt: &Expression) -> error::Result<Expression> {
-        let arguments = self.parse_expr_list(Token::RPAREN)?;
-        Ok(Expression::Call {
-            function: Box::new(fnt.clone()),
-            arguments,
-        })
+        let open = self.spanned_token(0).clone();
+        let mut arguments = Vec::new();
+        let mut keywords = Vec::new();
+
+        while self.take_token().1 != &Token::RPAREN {
+            self.next_token();
+            // `name: value` gives the argument by name. Nothing else puts a
+            // colon right after a name outside of a hash.
+            if let (Token::IDENT(name), Token::COLON, _) = self.take_token() {
+                keywords.push(name.clone());
+                self.next_token();
+                self.next_token();
+            } else if !keywords.is_empty() {
+                return Err(Error::ArgumentOrderErr {
+                    span: self.cur_span(),
+                });
+            }
+            arguments.push(self.parse_expression(Precedence::LOWEST)?);
+
+            if self.take_token().1 == &Token::COMMA {
+                self.next_token();
+            } else {
+                break;
+            }
+        }
+
+        expect_close!(self => opened at open);
+
+        Ok(Expression::new(
+            ExprKind::Call {
+                function: Box::new(fnt.clone()),
+                arguments,
+                keywords,
+                piped: false,
+            },
+            self.span_from(fnt.span),
+        ))
     }
 
     fn parse_expr_list(&mut self, end: Token) -> error::Result<Vec<Expression>> {
+        let open = self.spanned_token(0).clone();
         let mut args: Vec<Expression> = Vec::new();
 
         if self.take_token().1 == &end {
@@ -351,21 +1058,73 @@ impl Parser {
             args.push(self.parse_expression(Precedence::LOWEST)?);
         }
 
-        expect_peek!(self => end);
+        expect_close!(self => opened at open);
 
         Ok(args)
     }
 
+    // The right side is a call, which takes left as its first argument
+    fn parse_pipeline_expr(&mut self, left: &Expression) -> error::Result<Expression> {
+        self.next_token();
+        let right = self.parse_expression(Precedence::PIPELINE)?;
+        match right.kind {
+            ExprKind::Call {
+                function,
+                mut arguments,
+                keywords,
+                ..
+            } => {
+                arguments.insert(0, left.clone());
+                Ok(Expression::new(
+                    ExprKind::Call {
+                        function,
+                        arguments,
+                        keywords,
+                        piped: true,
+                    },
+                    self.span_from(left.span),
+                ))
+            }
+            _ => Err(Error::PipelineErr { span: right.span }),
+        }
+    }
+
     fn parse_infix_expr(&mut self, left: &Expression) -> error::Result<Expression> {
         let operator = self.take_token().0.clone();
         let precedence = Precedence::take_precedence(self.take_token().0);
         self.next_token();
 
         let right = Box::new(self.parse_expression(precedence)?);
-        Ok(Expression::Infix {
-            left: Box::new(left.clone()),
-            operator,
+        Ok(Expression::new(
+            ExprKind::Infix {
+                left: Box::new(left.clone()),
+                operator,
+                right,
+            },
+            self.span_from(left.span),
+        ))
+    }
+}
+
+fn is_range_operator(tok: &Token) -> bool {
+    matches!(tok, Token::DOTDOT | Token::DOTDOTEQ)
+}
+
+// Names, and index expressions whose left side is assignable. Slices are
+// copies, so they cannot be assigned.
+fn is_assignable(expr: &Expression) -> bool {
+    match &expr.kind {
+        ExprKind::Ident(_) => true,
+        ExprKind::Infix {
+            operator: Token::LBRACKET,
             right,
-        })
+            ..
+        } if matches!(right.kind, ExprKind::Range { .. }) => false,
+        ExprKind::Infix {
+            left,
+            operator: Token::LBRACKET,
+            ..
+        } => is_assignable(left),
+        _ => false,
     }
 }
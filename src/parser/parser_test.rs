@@ -1,5 +1,6 @@
 use super::*;
 use crate::error;
+use crate::lexer::span::Span;
 use crate::lexer::Lexer;
 
 macro_rules! test_parser {
@@ -19,95 +20,115 @@ test_parser!(
     parse_let => r#"let x = 5;
         let y = 10;
         let foobar = 838383;"#;
-    Statement::LetStmt {
+    Statement::from(StmtKind::LetStmt {
         name: "x".to_string(),
-        value: Expression::Integer(5),
-    },
-    Statement::LetStmt {
+        value: Expression::from(ExprKind::Integer(5)),
+    }),
+    Statement::from(StmtKind::LetStmt {
         name: "y".to_string(),
-        value: Expression::Integer(10),
-    },
-    Statement::LetStmt {
+        value: Expression::from(ExprKind::Integer(10)),
+    }),
+    Statement::from(StmtKind::LetStmt {
         name: "foobar".to_string(),
-        value: Expression::Integer(838383),
-    }
+        value: Expression::from(ExprKind::Integer(838383)),
+    })
 );
 
 test_parser!(
     parse_return => r#" return 5;
         return 10;
         return 993322;"#;
-    Statement::ReturnStmt {
-        value: Expression::Integer(5),
-    },
-    Statement::ReturnStmt {
-        value: Expression::Integer(10),
-    },
-    Statement::ReturnStmt {
-        value: Expression::Integer(993322),
-    }
+    Statement::from(StmtKind::ReturnStmt {
+        value: Expression::from(ExprKind::Integer(5)),
+    }),
+    Statement::from(StmtKind::ReturnStmt {
+        value: Expression::from(ExprKind::Integer(10)),
+    }),
+    Statement::from(StmtKind::ReturnStmt {
+        value: Expression::from(ExprKind::Integer(993322)),
+    })
 );
 
 test_parser!(
     parse_ident => r#" foobar;
         add;
         asdf; "#;
-    Statement::ExpressionStmt {
-        expression: Expression::Ident("foobar".to_string()),
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Ident("add".to_string()),
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Ident("asdf".to_string()),
-    }
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Ident("foobar".to_string())),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Ident("add".to_string())),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Ident("asdf".to_string())),
+    })
 );
 
 test_parser!(
     parse_integer => r#" 5;
         155;
         32415;"#;
-    Statement::ExpressionStmt {
-        expression: Expression::Integer(5),
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Integer(155),
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Integer(32415),
-    }
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Integer(5)),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Integer(155)),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Integer(32415)),
+    })
 );
 
 test_parser!(
     parse_complex => r#"5i;
         1 + 12i;
-        532 - 221i;"#;
-    Statement::ExpressionStmt {
-        expression: Expression::Complex { re: 0, im: 5 },
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Complex { re: 1, im: 12 },
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Complex { re: 532, im: -221 },
-    }
+        532 - 221i;
+        2 * 3 + 4i;"#;
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Imaginary(5.0)),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Infix {
+            left: Box::new(Expression::from(ExprKind::Integer(1))),
+            operator: Token::PLUS,
+            right: Box::new(Expression::from(ExprKind::Imaginary(12.0))),
+        }),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Infix {
+            left: Box::new(Expression::from(ExprKind::Integer(532))),
+            operator: Token::MINUS,
+            right: Box::new(Expression::from(ExprKind::Imaginary(221.0))),
+        }),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Infix {
+            left: Box::new(Expression::from(ExprKind::Infix {
+                left: Box::new(Expression::from(ExprKind::Integer(2))),
+                operator: Token::ASTERISK,
+                right: Box::new(Expression::from(ExprKind::Integer(3))),
+            })),
+            operator: Token::PLUS,
+            right: Box::new(Expression::from(ExprKind::Imaginary(4.0))),
+        }),
+    })
 );
 
 test_parser!(
     parse_prefix => r#"!5;
         -15;"#;
-    Statement::ExpressionStmt {
-        expression: Expression::Prefix {
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Prefix {
             operator: Token::BANG,
-            right: Box::new(Expression::Integer(5)),
-        },
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Prefix {
+            right: Box::new(Expression::from(ExprKind::Integer(5))),
+        }),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Prefix {
             operator: Token::MINUS,
-            right: Box::new(Expression::Integer(15)),
-        },
-    }
+            right: Box::new(Expression::from(ExprKind::Integer(15))),
+        }),
+    })
 );
 
 test_parser!(
@@ -119,124 +140,126 @@ test_parser!(
         5 < 11;
         5 == 12;
         5 != 13; "#;
-    Statement::ExpressionStmt {
-        expression: Expression::Infix {
-            left: Box::new(Expression::Integer(5)),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Infix {
+            left: Box::new(Expression::from(ExprKind::Integer(5))),
             operator: Token::PLUS,
-            right: Box::new(Expression::Integer(6)),
-        },
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Infix {
-            left: Box::new(Expression::Integer(5)),
+            right: Box::new(Expression::from(ExprKind::Integer(6))),
+        }),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Infix {
+            left: Box::new(Expression::from(ExprKind::Integer(5))),
             operator: Token::MINUS,
-            right: Box::new(Expression::Integer(7)),
-        },
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Infix {
-            left: Box::new(Expression::Integer(5)),
+            right: Box::new(Expression::from(ExprKind::Integer(7))),
+        }),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Infix {
+            left: Box::new(Expression::from(ExprKind::Integer(5))),
             operator: Token::ASTERISK,
-            right: Box::new(Expression::Integer(8)),
-        },
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Infix {
-            left: Box::new(Expression::Integer(5)),
+            right: Box::new(Expression::from(ExprKind::Integer(8))),
+        }),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Infix {
+            left: Box::new(Expression::from(ExprKind::Integer(5))),
             operator: Token::SLASH,
-            right: Box::new(Expression::Integer(9)),
-        },
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Infix {
-            left: Box::new(Expression::Integer(5)),
+            right: Box::new(Expression::from(ExprKind::Integer(9))),
+        }),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Infix {
+            left: Box::new(Expression::from(ExprKind::Integer(5))),
             operator: Token::GT,
-            right: Box::new(Expression::Integer(10)),
-        },
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Infix {
-            left: Box::new(Expression::Integer(5)),
+            right: Box::new(Expression::from(ExprKind::Integer(10))),
+        }),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Infix {
+            left: Box::new(Expression::from(ExprKind::Integer(5))),
             operator: Token::LT,
-            right: Box::new(Expression::Integer(11)),
-        },
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Infix {
-            left: Box::new(Expression::Integer(5)),
+            right: Box::new(Expression::from(ExprKind::Integer(11))),
+        }),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Infix {
+            left: Box::new(Expression::from(ExprKind::Integer(5))),
             operator: Token::EQ,
-            right: Box::new(Expression::Integer(12)),
-        },
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Infix {
-            left: Box::new(Expression::Integer(5)),
+            right: Box::new(Expression::from(ExprKind::Integer(12))),
+        }),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Infix {
+            left: Box::new(Expression::from(ExprKind::Integer(5))),
             operator: Token::NOTEQ,
-            right: Box::new(Expression::Integer(13)),
-        },
-    }
+            right: Box::new(Expression::from(ExprKind::Integer(13))),
+        }),
+    })
 );
 
 test_parser!(
     parse_complex_infix => r#"3 - - 2;
         2 + 3i + 5 - 4i;
         3 + 4 * - 5 == 3 * 1 + -4 / 5;"#;
-    Statement::ExpressionStmt {
-        expression: Expression::Infix {
-            left: Box::new(Expression::Integer(3)),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Infix {
+            left: Box::new(Expression::from(ExprKind::Integer(3))),
             operator: Token::MINUS,
-            right: Box::new(Expression::Prefix {
+            right: Box::new(Expression::from(ExprKind::Prefix {
                 operator: Token::MINUS,
-                right: Box::new(Expression::Integer(2)),
-            }),
-        },
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Infix {
-            left: Box::new(Expression::Complex {
-                re: 2,
-                im: 3,
-            }),
-            operator: Token::PLUS,
-            right: Box::new(Expression::Complex {
-                re: 5,
-                im: -4,
-            }),
-        }
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Infix {
-            left: Box::new(Expression::Infix {
-                left: Box::new(Expression::Integer(3)),
+                right: Box::new(Expression::from(ExprKind::Integer(2))),
+            })),
+        }),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Infix {
+            left: Box::new(Expression::from(ExprKind::Infix {
+                left: Box::new(Expression::from(ExprKind::Infix {
+                    left: Box::new(Expression::from(ExprKind::Integer(2))),
+                    operator: Token::PLUS,
+                    right: Box::new(Expression::from(ExprKind::Imaginary(3.0))),
+                })),
+                operator: Token::PLUS,
+                right: Box::new(Expression::from(ExprKind::Integer(5))),
+            })),
+            operator: Token::MINUS,
+            right: Box::new(Expression::from(ExprKind::Imaginary(4.0))),
+        })
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Infix {
+            left: Box::new(Expression::from(ExprKind::Infix {
+                left: Box::new(Expression::from(ExprKind::Integer(3))),
                 operator: Token::PLUS,
-                right: Box::new(Expression::Infix {
-                    left: Box::new(Expression::Integer(4)),
+                right: Box::new(Expression::from(ExprKind::Infix {
+                    left: Box::new(Expression::from(ExprKind::Integer(4))),
                     operator: Token::ASTERISK,
-                    right: Box::new(Expression::Prefix {
+                    right: Box::new(Expression::from(ExprKind::Prefix {
                         operator: Token::MINUS,
-                        right: Box::new(Expression::Integer(5)),
-                    }),
-                }),
-            }),
+                        right: Box::new(Expression::from(ExprKind::Integer(5))),
+                    })),
+                })),
+            })),
             operator: Token::EQ,
-            right: Box::new(Expression::Infix {
-                left: Box::new(Expression::Infix {
-                    left: Box::new(Expression::Integer(3)),
+            right: Box::new(Expression::from(ExprKind::Infix {
+                left: Box::new(Expression::from(ExprKind::Infix {
+                    left: Box::new(Expression::from(ExprKind::Integer(3))),
                     operator: Token::ASTERISK,
-                    right: Box::new(Expression::Integer(1)),
-                }),
+                    right: Box::new(Expression::from(ExprKind::Integer(1))),
+                })),
                 operator: Token::PLUS,
-                right: Box::new(Expression::Infix {
-                    left: Box::new(Expression::Prefix {
+                right: Box::new(Expression::from(ExprKind::Infix {
+                    left: Box::new(Expression::from(ExprKind::Prefix {
                         operator: Token::MINUS,
-                        right: Box::new(Expression::Integer(4)),
-                    }),
+                        right: Box::new(Expression::from(ExprKind::Integer(4))),
+                    })),
                     operator: Token::SLASH,
-                    right: Box::new(Expression::Integer(5)),
-                }),
-            }),
-        },
-    }
+                    right: Box::new(Expression::from(ExprKind::Integer(5))),
+                })),
+            })),
+        }),
+    })
 );
 
 test_parser!(
@@ -246,97 +269,97 @@ test_parser!(
         let barfoo = false;
         3 > 5 == false;
         !true;"#;
-    Statement::ExpressionStmt {
-        expression: Expression::Boolean(true),
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Boolean(false),
-    },
-    Statement::LetStmt {
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Boolean(true)),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Boolean(false)),
+    }),
+    Statement::from(StmtKind::LetStmt {
         name: "foobar".to_string(),
-        value: Expression::Boolean(true),
-    },
-    Statement::LetStmt {
+        value: Expression::from(ExprKind::Boolean(true)),
+    }),
+    Statement::from(StmtKind::LetStmt {
         name: "barfoo".to_string(),
-        value: Expression::Boolean(false),
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Infix {
-            left: Box::new(Expression::Infix {
-                left: Box::new(Expression::Integer(3)),
+        value: Expression::from(ExprKind::Boolean(false)),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Infix {
+            left: Box::new(Expression::from(ExprKind::Infix {
+                left: Box::new(Expression::from(ExprKind::Integer(3))),
                 operator: Token::GT,
-                right: Box::new(Expression::Integer(5))
-            }),
+                right: Box::new(Expression::from(ExprKind::Integer(5)))
+            })),
             operator: Token::EQ,
-            right: Box::new(Expression::Boolean(false))
-        }
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Prefix {
+            right: Box::new(Expression::from(ExprKind::Boolean(false)))
+        })
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Prefix {
             operator: Token::BANG,
-            right: Box::new(Expression::Boolean(true))
-        }
-    }
+            right: Box::new(Expression::from(ExprKind::Boolean(true)))
+        })
+    })
 );
 
 test_parser!(
     parse_grouped => r#"-(5+6);
     (2 + 3) * 4;"#;
-    Statement::ExpressionStmt {
-        expression: Expression::Prefix {
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Prefix {
             operator: Token::MINUS,
-            right: Box::new(Expression::Infix {
-                left: Box::new(Expression::Integer(5)),
+            right: Box::new(Expression::from(ExprKind::Infix {
+                left: Box::new(Expression::from(ExprKind::Integer(5))),
                 operator: Token::PLUS,
-                right: Box::new(Expression::Integer(6)),
-            })
-        }
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Infix {
-            left: Box::new(Expression::Infix {
-                left: Box::new(Expression::Integer(2)),
+                right: Box::new(Expression::from(ExprKind::Integer(6))),
+            }))
+        })
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Infix {
+            left: Box::new(Expression::from(ExprKind::Infix {
+                left: Box::new(Expression::from(ExprKind::Integer(2))),
                 operator: Token::PLUS,
-                right: Box::new(Expression::Integer(3)),
-            }),
+                right: Box::new(Expression::from(ExprKind::Integer(3))),
+            })),
             operator: Token::ASTERISK,
-            right: Box::new(Expression::Integer(4)),
-        }
-    }
+            right: Box::new(Expression::from(ExprKind::Integer(4))),
+        })
+    })
 );
 
 test_parser!(
     parse_if_expr => r#"
         if (x < y) { x };
         "#;
-    Statement::ExpressionStmt {
-        expression: Expression::IfExpr {
-            condition: Box::new(Expression::Infix {
-                left: Box::new(Expression::Ident("x".to_string())),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::IfExpr {
+            condition: Box::new(Expression::from(ExprKind::Infix {
+                left: Box::new(Expression::from(ExprKind::Ident("x".to_string()))),
                 operator: Token::LT,
-                right: Box::new(Expression::Ident("y".to_string())),
-            }),
-            consequence: vec![Statement::ExpressionStmt { expression: Expression::Ident("x".to_string()) }],
+                right: Box::new(Expression::from(ExprKind::Ident("y".to_string()))),
+            })),
+            consequence: vec![Statement::from(StmtKind::ExpressionStmt { expression: Expression::from(ExprKind::Ident("x".to_string())) })],
             alternative: None
-        }
-    }
+        })
+    })
 );
 
 test_parser!(
     parse_if_else_expr => r#"
         if(foo!=bar){bar}else{foo};
         "#;
-    Statement::ExpressionStmt {
-        expression: Expression::IfExpr {
-            condition: Box::new(Expression::Infix {
-                left: Box::new(Expression::Ident("foo".to_string())),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::IfExpr {
+            condition: Box::new(Expression::from(ExprKind::Infix {
+                left: Box::new(Expression::from(ExprKind::Ident("foo".to_string()))),
                 operator: Token::NOTEQ,
-                right: Box::new(Expression::Ident("bar".to_string())),
-            }),
-            consequence: vec![Statement::ExpressionStmt { expression: Expression::Ident("bar".to_string()) }],
-            alternative: Some(vec![Statement::ExpressionStmt { expression: Expression::Ident("foo".to_string()) }]),
-        }
-    }
+                right: Box::new(Expression::from(ExprKind::Ident("bar".to_string()))),
+            })),
+            consequence: vec![Statement::from(StmtKind::ExpressionStmt { expression: Expression::from(ExprKind::Ident("bar".to_string())) })],
+            alternative: Some(vec![Statement::from(StmtKind::ExpressionStmt { expression: Expression::from(ExprKind::Ident("foo".to_string())) })]),
+        })
+    })
 );
 
 test_parser!(
@@ -345,54 +368,59 @@ test_parser!(
         fn() {};
         fn(x, y, z) {};
         "#;
-    Statement::ExpressionStmt {
-        expression: Expression::Function {
-            parameters: vec!["x".to_string(), "y".to_string()],
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Function {
+            parameters: Parameters::new(vec!["x".to_string(), "y".to_string()]),
             body: vec![
-                Statement::ExpressionStmt {
-                expression: Expression::Infix {
-                left: Box::new(Expression::Ident("x".to_string())),
+                Statement::from(StmtKind::ExpressionStmt {
+                expression: Expression::from(ExprKind::Infix {
+                left: Box::new(Expression::from(ExprKind::Ident("x".to_string()))),
                 operator: Token::PLUS,
-                right: Box::new(Expression::Ident("y".to_string())),
-            }}]
-        }
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Function {
-            parameters: vec![],
-            body: vec![]
-        }
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Function {
-            parameters: vec!["x".to_string(), "y".to_string(), "z".to_string()],
-            body: vec![]
-        }
-    }
+                right: Box::new(Expression::from(ExprKind::Ident("y".to_string()))),
+            })})],
+            shorthand: false,
+        })
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Function {
+            parameters: Parameters::default(),
+            body: vec![],
+            shorthand: false,
+        })
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Function {
+            parameters: Parameters::new(vec!["x".to_string(), "y".to_string(), "z".to_string()]),
+            body: vec![],
+            shorthand: false,
+        })
+    })
 );
 
 test_parser!(
     parse_call_expr => r#"
         add(1, 2 * 3, 4 + 5);
         "#;
-    Statement::ExpressionStmt {
-        expression: Expression::Call {
-            function: Box::new(Expression::Ident("add".to_string())),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Call {
+            function: Box::new(Expression::from(ExprKind::Ident("add".to_string()))),
             arguments: vec![
-                Expression::Integer(1),
-                Expression::Infix {
-                    left: Box::new(Expression::Integer(2)),
+                Expression::from(ExprKind::Integer(1)),
+                Expression::from(ExprKind::Infix {
+                    left: Box::new(Expression::from(ExprKind::Integer(2))),
                     operator: Token::ASTERISK,
-                    right: Box::new(Expression::Integer(3))
-                },
-                Expression::Infix {
-                    left: Box::new(Expression::Integer(4)),
+                    right: Box::new(Expression::from(ExprKind::Integer(3)))
+                }),
+                Expression::from(ExprKind::Infix {
+                    left: Box::new(Expression::from(ExprKind::Integer(4))),
                     operator: Token::PLUS,
-                    right: Box::new(Expression::Integer(5))
-                }
-            ]
-        }
-    }
+                    right: Box::new(Expression::from(ExprKind::Integer(5)))
+                })
+            ],
+            keywords: vec![],
+            piped: false,
+        })
+    })
 );
 
 test_parser!(
@@ -400,63 +428,773 @@ test_parser!(
             [1, 2*2, 3+3];
             a * [1,2,3,4][b*c] * d;
             "#;
-    Statement::ExpressionStmt {
-        expression: Expression::Array(vec![
-            Expression::Integer(1),
-            Expression::Infix {
-                left: Box::new(Expression::Integer(2)),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Array(vec![
+            Expression::from(ExprKind::Integer(1)),
+            Expression::from(ExprKind::Infix {
+                left: Box::new(Expression::from(ExprKind::Integer(2))),
                 operator: Token::ASTERISK,
-                right: Box::new(Expression::Integer(2))
-            },
-            Expression::Infix {
-                left: Box::new(Expression::Integer(3)),
+                right: Box::new(Expression::from(ExprKind::Integer(2)))
+            }),
+            Expression::from(ExprKind::Infix {
+                left: Box::new(Expression::from(ExprKind::Integer(3))),
                 operator: Token::PLUS,
-                right: Box::new(Expression::Integer(3))
-            },
-        ]),
-    },
-    Statement::ExpressionStmt {
-        expression: Expression::Infix {
-            left: Box::new(Expression::Infix {
-                left: Box::new(Expression::Ident("a".to_string())),
+                right: Box::new(Expression::from(ExprKind::Integer(3)))
+            }),
+        ])),
+    }),
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Infix {
+            left: Box::new(Expression::from(ExprKind::Infix {
+                left: Box::new(Expression::from(ExprKind::Ident("a".to_string()))),
                 operator: Token::ASTERISK,
-                right: Box::new(Expression::Infix {
-                    left: Box::new(Expression::Array(vec![
-                        Expression::Integer(1),
-                        Expression::Integer(2),
-                        Expression::Integer(3),
-                        Expression::Integer(4),
-                    ])),
+                right: Box::new(Expression::from(ExprKind::Infix {
+                    left: Box::new(Expression::from(ExprKind::Array(vec![
+                        Expression::from(ExprKind::Integer(1)),
+                        Expression::from(ExprKind::Integer(2)),
+                        Expression::from(ExprKind::Integer(3)),
+                        Expression::from(ExprKind::Integer(4)),
+                    ]))),
                     operator: Token::LBRACKET,
-                    right: Box::new(Expression::Infix {
-                        left: Box::new(Expression::Ident("b".to_string())),
+                    right: Box::new(Expression::from(ExprKind::Infix {
+                        left: Box::new(Expression::from(ExprKind::Ident("b".to_string()))),
                         operator: Token::ASTERISK,
-                        right: Box::new(Expression::Ident("c".to_string())),
-                    })
-                })
-            }),
+                        right: Box::new(Expression::from(ExprKind::Ident("c".to_string()))),
+                    }))
+                }))
+            })),
             operator: Token::ASTERISK,
-            right: Box::new(Expression::Ident("d".to_string())),
-        }
-    }
+            right: Box::new(Expression::from(ExprKind::Ident("d".to_string()))),
+        })
+    })
 );
 
 test_parser!(
     parse_hash => r#"
             {"one": 1, "two": 2, "three": 3};
             "#;
-    Statement::ExpressionStmt {
-        expression: Expression::Hash {
+    Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Hash {
             key: vec![
-                Expression::String("one".to_string()),
-                Expression::String("two".to_string()),
-                Expression::String("three".to_string()),
+                Expression::from(ExprKind::String("one".to_string())),
+                Expression::from(ExprKind::String("two".to_string())),
+                Expression::from(ExprKind::String("three".to_string())),
             ],
             value: vec![
-                Expression::Integer(1),
-                Expression::Integer(2),
-                Expression::Integer(3),
+                Expression::from(ExprKind::Integer(1)),
+                Expression::from(ExprKind::Integer(2)),
+                Expression::from(ExprKind::Integer(3)),
             ]
+        })
+    })
+);
+
+#[test]
+fn parse_node_spans() -> error::Result<()> {
+    let input = "let x = 1 + 2;\nadd(x, [3])";
+    let program = Parser::new(Lexer::new(input)).parse_program()?;
+    let stmts = program.get_stmts();
+    let span_text = |span: Span| &input[span.start..span.end];
+
+    assert_eq!("let x = 1 + 2;", span_text(stmts[0].span));
+    if let StmtKind::LetStmt { value, .. } = &stmts[0].kind {
+        assert_eq!("1 + 2", span_text(value.span));
+        assert_eq!((1, 9), (value.span.line, value.span.column));
+    } else {
+        panic!("expected a let statement, got {:?}", stmts[0]);
+    }
+
+    assert_eq!((2, 1), (stmts[1].span.line, stmts[1].span.column));
+    if let StmtKind::ExpressionStmt { expression } = &stmts[1].kind {
+        assert_eq!("add(x, [3])", span_text(expression.span));
+    } else {
+        panic!("expected an expression statement, got {:?}", stmts[1]);
+    }
+    Ok(())
+}
+
+#[test]
+fn parse_error_spans() {
+    let cases = &[
+        ("let x = (1 + 2;", (1, 15)),
+        ("let = 5;", (1, 5)),
+        ("1 +\n  ;", (2, 3)),
+    ];
+    for (input, (line, column)) in cases {
+        let span = match Parser::new(Lexer::new(input)).parse_program() {
+            Err(err) => err.span().expect("parse errors have a location"),
+            other => panic!("expected a parse error for {}, got {:?}", input, other),
+        };
+        assert_eq!(
+            (*line, *column),
+            (span.line, span.column),
+            "input: {}",
+            input
+        );
+    }
+}
+
+#[test]
+fn parse_unclosed_delimiters() {
+    let cases = &[
+        ("let x = (1 + 2;", Token::RPAREN, (1, 9)),
+        ("add(1,\n  2", Token::RPAREN, (1, 4)),
+        ("[1, 2", Token::RBRACKET, (1, 1)),
+        (r#"{"a": 1 "b": 2}"#, Token::RBRACE, (1, 1)),
+        ("if (x) {\n  x", Token::RBRACE, (1, 8)),
+    ];
+    for (input, close, (line, column)) in cases {
+        match Parser::new(Lexer::new(input)).parse_program() {
+            Err(Error::UnclosedDelimErr {
+                open, open_span, ..
+            }) => {
+                assert_eq!(close, &open.closing_delimiter(), "input: {}", input);
+                assert_eq!(
+                    (*line, *column),
+                    (open_span.line, open_span.column),
+                    "input: {}",
+                    input
+                );
+            }
+            other => panic!(
+                "expected an unclosed delimiter for {}, got {:?}",
+                input, other
+            ),
         }
     }
-);
+}
+
+#[test]
+fn parse_recovering_collects_all_errors() {
+    let input = "
+let x = 1;
+let = 5;
+let y = (2 + ;
+let f = fn(a) {
+  let 3 = a;
+  a * 2
+};
+f(x));
+return x;
+";
+    let (program, errors) = Parser::new(Lexer::new(input)).parse_program_recovering();
+    let positions: Vec<_> = errors
+        .iter()
+        .map(|err| {
+            let span = err.span().expect("parse errors have a location");
+            (span.line, span.column)
+        })
+        .collect();
+    assert_eq!(vec![(3, 5), (4, 14), (6, 7), (9, 5)], positions);
+
+    let stmts = program.get_stmts();
+    assert_eq!(3, stmts.len(), "got {:?}", stmts);
+    match &stmts[1].kind {
+        StmtKind::LetStmt {
+            value:
+                Expression {
+                    kind: ExprKind::Function { body, .. },
+                    ..
+                },
+            ..
+        } => assert_eq!(1, body.len()),
+        other => panic!("expected the function binding, got {:?}", other),
+    }
+    assert!(matches!(stmts[2].kind, StmtKind::ReturnStmt { .. }));
+}
+
+#[test]
+fn parse_recovering_without_errors() -> error::Result<()> {
+    let input = "let a = fn(x) { x }; a(1);";
+    let (program, errors) = Parser::new(Lexer::new(input)).parse_program_recovering();
+    assert!(errors.is_empty());
+    assert_eq!(
+        Parser::new(Lexer::new(input)).parse_program()?.get_stmts(),
+        program.get_stmts()
+    );
+    Ok(())
+}
+
+#[test]
+fn parse_empty_input() -> error::Result<()> {
+    for input in &["", "  \n\t", "// only a comment", "/* a */ // b\n"] {
+        assert!(Parser::new(Lexer::new(input))
+            .parse_program()?
+            .get_stmts()
+            .is_empty());
+        let (program, errors) = Parser::new(Lexer::new(input)).parse_program_recovering();
+        assert!(errors.is_empty(), "input: {:?}, got {:?}", input, errors);
+        assert!(program.get_stmts().is_empty());
+    }
+    Ok(())
+}
+
+#[test]
+fn parse_recovering_skips_nested_braces() {
+    let input = r#"let h = {"a" 1}; let b = 2; }; 3"#;
+    let (program, errors) = Parser::new(Lexer::new(input)).parse_program_recovering();
+    assert_eq!(2, errors.len(), "got {:?}", errors);
+    assert_eq!(2, program.get_stmts().len());
+}
+
+#[test]
+fn parse_lexing_errors() {
+    let input = "let x = 1 +\n/* never closed";
+    match Parser::new(Lexer::new(input)).parse_program() {
+        Err(Error::LexErr { span, .. }) => assert_eq!((2, 1), (span.line, span.column)),
+        other => panic!("expected a lexing error, got {:?}", other),
+    }
+
+    let (_, errors) = Parser::new(Lexer::new(input)).parse_program_recovering();
+    assert!(
+        matches!(
+            errors.as_slice(),
+            [Error::LexErr { .. }, Error::PrefixParseNoneErr { .. }]
+        ),
+        "got {:?}",
+        errors
+    );
+}
+
+#[test]
+fn parse_interpolated_string() -> error::Result<()> {
+    let program = Parser::new(Lexer::new(r#""a${x + 1}${y}""#)).parse_program()?;
+    let expected = vec![Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Interpolated(vec![
+            StringPart::Literal("a".to_string()),
+            StringPart::Expr(Expression::from(ExprKind::Infix {
+                left: Box::new(Expression::from(ExprKind::Ident("x".to_string()))),
+                operator: Token::PLUS,
+                right: Box::new(Expression::from(ExprKind::Integer(1))),
+            })),
+            StringPart::Expr(Expression::from(ExprKind::Ident("y".to_string()))),
+        ])),
+    })];
+    assert_eq!(expected, program);
+
+    let err = Parser::new(Lexer::new(r#""a${}""#)).parse_program();
+    assert!(matches!(err, Err(Error::PrefixParseNoneErr { .. })));
+    Ok(())
+}
+
+#[test]
+fn parse_assignment() -> error::Result<()> {
+    let ident = |name: &str| Box::new(Expression::from(ExprKind::Ident(name.to_string())));
+    let program = Parser::new(Lexer::new("a = b[0] += 1 + 2;")).parse_program()?;
+    let expected = vec![Statement::from(StmtKind::ExpressionStmt {
+        expression: Expression::from(ExprKind::Assign {
+            target: ident("a"),
+            operator: Token::ASSIGN,
+            value: Box::new(Expression::from(ExprKind::Assign {
+                target: Box::new(Expression::from(ExprKind::Infix {
+                    left: ident("b"),
+                    operator: Token::LBRACKET,
+                    right: Box::new(Expression::from(ExprKind::Integer(0))),
+                })),
+                operator: Token::PLUSEQ,
+                value: Box::new(Expression::from(ExprKind::Infix {
+                    left: Box::new(Expression::from(ExprKind::Integer(1))),
+                    operator: Token::PLUS,
+                    right: Box::new(Expression::from(ExprKind::Integer(2))),
+                })),
+            })),
+        }),
+    })];
+    assert_eq!(expected, program);
+
+    for input in &["1 = 2", "a + b = 1", "f()[0] = 1", "-a -= 1"] {
+        match Parser::new(Lexer::new(input)).parse_program() {
+            Err(Error::AssignTargetErr { .. }) => {}
+            other => panic!(
+                "expected an assignment error for {}, got {:?}",
+                input, other
+            ),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn parse_loops() -> error::Result<()> {
+    let ident = |name: &str| Expression::from(ExprKind::Ident(name.to_string()));
+    let program = Parser::new(Lexer::new(
+        "while (x) { break; } for (i in xs) { continue }; 1",
+    ))
+    .parse_program()?;
+    let expected = vec![
+        Statement::from(StmtKind::WhileStmt {
+            condition: ident("x"),
+            body: vec![Statement::from(StmtKind::BreakStmt)],
+        }),
+        Statement::from(StmtKind::ForStmt {
+            variable: "i".to_string(),
+            iterable: ident("xs"),
+            body: vec![Statement::from(StmtKind::ContinueStmt)],
+        }),
+        Statement::from(Expression::from(ExprKind::Integer(1))),
+    ];
+    assert_eq!(expected, program);
+    Ok(())
+}
+
+#[test]
+fn parse_loop_control_outside_of_loop() {
+    let cases = &[
+        ("break;", Token::BREAK, (1, 1)),
+        ("if (x) { continue }", Token::CONTINUE, (1, 10)),
+        ("while (x) { fn() { break } }", Token::BREAK, (1, 20)),
+        ("while (x) { } continue", Token::CONTINUE, (1, 15)),
+        (
+            "while (true) { let f = fn(x = if (true) { break }) { x }; }",
+            Token::BREAK,
+            (1, 43),
+        ),
+        (
+            "for (i in xs) { |x = if (i) { continue }| x }",
+            Token::CONTINUE,
+            (1, 31),
+        ),
+    ];
+    for (input, expected, (line, column)) in cases {
+        match Parser::new(Lexer::new(input)).parse_program() {
+            Err(Error::LoopControlErr { keyword, span }) => {
+                assert_eq!(expected, &keyword, "input: {}", input);
+                assert_eq!(
+                    (*line, *column),
+                    (span.line, span.column),
+                    "input: {}",
+                    input
+                );
+            }
+            other => panic!(
+                "expected a loop control error for {}, got {:?}",
+                input, other
+            ),
+        }
+    }
+    let program =
+        Parser::new(Lexer::new("for (x in xs) { if (x) { fn() { 1 }; break } }")).parse_program();
+    assert!(program.is_ok(), "{:?}", program);
+}
+
+#[test]
+fn parse_ranges() -> error::Result<()> {
+    let int = |n| Some(Box::new(Expression::from(ExprKind::Integer(n))));
+    let ident = |name: &str| Box::new(Expression::from(ExprKind::Ident(name.to_string())));
+    let range = |start, end, inclusive| {
+        Expression::from(ExprKind::Range {
+            start,
+            end,
+            inclusive,
+        })
+    };
+    let index = |index| {
+        Statement::from(Expression::from(ExprKind::Infix {
+            left: ident("a"),
+            operator: Token::LBRACKET,
+            right: Box::new(index),
+        }))
+    };
+    let program = Parser::new(Lexer::new(
+        "0..n + 1; 1..=2 == x; a[1..2]; a[..2]; a[1..]; a[..]; a[..=0]",
+    ))
+    .parse_program()?;
+    let expected = vec![
+        Statement::from(range(
+            int(0),
+            Some(Box::new(Expression::from(ExprKind::Infix {
+                left: ident("n"),
+                operator: Token::PLUS,
+                right: int(1).unwrap(),
+            }))),
+            false,
+        )),
+        Statement::from(range(
+            int(1),
+            Some(Box::new(Expression::from(ExprKind::Infix {
+                left: int(2).unwrap(),
+                operator: Token::EQ,
+                right: ident("x"),
+            }))),
+            true,
+        )),
+        index(range(int(1), int(2), false)),
+        index(range(None, int(2), false)),
+        index(range(int(1), None, false)),
+        index(range(None, None, false)),
+        index(range(None, int(0), true)),
+    ];
+    assert_eq!(expected, program);
+
+    for input in &["[1..]", "(..2)", "a[1..=]", "f(1..)"] {
+        assert!(
+            Parser::new(Lexer::new(input)).parse_program().is_err(),
+            "input: {}",
+            input
+        );
+    }
+    match Parser::new(Lexer::new("a[1..2] = 3")).parse_program() {
+        Err(Error::AssignTargetErr { .. }) => {}
+        other => panic!("expected an assignment error, got {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn parse_match() -> error::Result<()> {
+    let ident = |name: &str| Expression::from(ExprKind::Ident(name.to_string()));
+    let int = |n| Expression::from(ExprKind::Integer(n));
+    let pattern = |kind| Pattern::from(kind);
+    let binding = |name: &str| pattern(PatternKind::Binding(name.to_string()));
+    let program = Parser::new(Lexer::new(
+        r#"match (x) { -1 => 0, [a, ..rest] if a > 1 => a, {"k": [_, ..]} => 2, _ => 3, }"#,
+    ))
+    .parse_program()?;
+    let expected = vec![Statement::from(Expression::from(ExprKind::Match {
+        subject: Box::new(ident("x")),
+        arms: vec![
+            MatchArm {
+                pattern: pattern(PatternKind::Literal(Expression::from(ExprKind::Prefix {
+                    operator: Token::MINUS,
+                    right: Box::new(int(1)),
+                }))),
+                guard: None,
+                body: int(0),
+            },
+            MatchArm {
+                pattern: pattern(PatternKind::Array {
+                    elements: vec![binding("a")],
+                    rest: Some(Some("rest".to_string())),
+                }),
+                guard: Some(Expression::from(ExprKind::Infix {
+                    left: Box::new(ident("a")),
+                    operator: Token::GT,
+                    right: Box::new(int(1)),
+                })),
+                body: ident("a"),
+            },
+            MatchArm {
+                pattern: pattern(PatternKind::Hash {
+                    keys: vec![Expression::from(ExprKind::String("k".to_string()))],
+                    values: vec![pattern(PatternKind::Array {
+                        elements: vec![pattern(PatternKind::Wildcard)],
+                        rest: Some(None),
+                    })],
+                }),
+                guard: None,
+                body: int(2),
+            },
+            MatchArm {
+                pattern: pattern(PatternKind::Wildcard),
+                guard: None,
+                body: int(3),
+            },
+        ],
+    }))];
+    assert_eq!(expected, program);
+
+    for input in &[
+        "match (x) { a + 1 => 1 }",
+        "match (x) { [..r, a] => 1 }",
+        "match (x) { 1 }",
+        "match x { _ => 1 }",
+        r#"match (x) { {k: 1} => 1 }"#,
+    ] {
+        let result = Parser::new(Lexer::new(input)).parse_program();
+        assert!(result.is_err(), "input: {}, got {:?}", input, result);
+    }
+    Ok(())
+}
+
+#[test]
+fn parse_unreachable_match_arms() -> error::Result<()> {
+    let cases: &[(&str, &[(usize, usize)])] = &[
+        ("match (x) { n => 1, 2 => 2 }", &[(21, 13)]),
+        ("match (x) { n if n => 1, 2 => 2 }", &[]),
+        ("match (x) { 1 => 1, 2 => 2, 1 => 3 }", &[(29, 13)]),
+        (
+            "match (x) { [a, ..] => 1, [1, 2] => 2, [] => 3 }",
+            &[(27, 13)],
+        ),
+        ("match (x) { [a, b] => 1, [1, ..] => 2 }", &[]),
+        (
+            r#"match (x) { {"a": 1} => 1, {"b": 2, "a": 1} => 2, {"b": 2} => 3 }"#,
+            &[(28, 13)],
+        ),
+        (
+            "match (x) { _ => 1, [] => 2, y => 3 }",
+            &[(21, 13), (30, 13)],
+        ),
+    ];
+    for (input, expected) in cases {
+        let mut parser = Parser::new(Lexer::new(input));
+        parser.parse_program()?;
+        let warnings: Vec<(usize, usize)> = parser
+            .take_warnings()
+            .iter()
+            .map(|warning| match warning {
+                Warning::UnreachableArm { span, covered_by } => (span.column, covered_by.column),
+            })
+            .collect();
+        assert_eq!(*expected, &warnings[..], "input: {}", input);
+    }
+    Ok(())
+}
+
+#[test]
+fn parse_let_patterns() -> error::Result<()> {
+    let binding = |name: &str| Pattern::from(PatternKind::Binding(name.to_string()));
+    let program = Parser::new(Lexer::new(
+        r#"let [a, [b], ..rest] = x; let {"k": v} = y; let _ = z;"#,
+    ))
+    .parse_program()?;
+    let ident = |name: &str| Expression::from(ExprKind::Ident(name.to_string()));
+    let expected = vec![
+        Statement::from(StmtKind::LetPatternStmt {
+            pattern: Pattern::from(PatternKind::Array {
+                elements: vec![
+                    binding("a"),
+                    Pattern::from(PatternKind::Array {
+                        elements: vec![binding("b")],
+                        rest: None,
+                    }),
+                ],
+                rest: Some(Some("rest".to_string())),
+            }),
+            value: ident("x"),
+        }),
+        Statement::from(StmtKind::LetPatternStmt {
+            pattern: Pattern::from(PatternKind::Hash {
+                keys: vec![Expression::from(ExprKind::String("k".to_string()))],
+                values: vec![binding("v")],
+            }),
+            value: ident("y"),
+        }),
+        Statement::from(StmtKind::LetStmt {
+            name: "_".to_string(),
+            value: ident("z"),
+        }),
+    ];
+    assert_eq!(expected, program);
+
+    for input in &[
+        "let [a, b = x;",
+        "let [a] x;",
+        "let {a: 1} = x;",
+        "let 1 = x;",
+    ] {
+        let result = Parser::new(Lexer::new(input)).parse_program();
+        assert!(result.is_err(), "input: {}, got {:?}", input, result);
+    }
+    Ok(())
+}
+
+#[test]
+fn parse_parameters_and_keyword_arguments() -> error::Result<()> {
+    let program =
+        Parser::new(Lexer::new("fn(x, y = 10, ..rest) {}; f(1, y: 2, x: 3)")).parse_program()?;
+    let int = |n| Expression::from(ExprKind::Integer(n));
+    let expected = vec![
+        Statement::from(StmtKind::ExpressionStmt {
+            expression: Expression::from(ExprKind::Function {
+                parameters: Parameters {
+                    names: vec!["x".to_string(), "y".to_string()],
+                    defaults: vec![int(10)],
+                    rest: Some("rest".to_string()),
+                },
+                body: vec![],
+                shorthand: false,
+            }),
+        }),
+        Statement::from(StmtKind::ExpressionStmt {
+            expression: Expression::from(ExprKind::Call {
+                function: Box::new(Expression::from(ExprKind::Ident("f".to_string()))),
+                arguments: vec![int(1), int(2), int(3)],
+                keywords: vec!["y".to_string(), "x".to_string()],
+                piped: false,
+            }),
+        }),
+    ];
+    assert_eq!(expected, program);
+
+    let cases = &[
+        ("fn(x = 1, y) {}", (1, 11)),
+        ("fn(x, ..r = 1) {}", (1, 11)),
+        ("fn(..r, x) {}", (1, 7)),
+        ("f(x: 1, 2)", (1, 9)),
+    ];
+    for (input, (line, column)) in cases {
+        let span = match Parser::new(Lexer::new(input)).parse_program() {
+            Err(err) => err.span().expect("parse errors have a location"),
+            other => panic!("expected a parse error for {}, got {:?}", input, other),
+        };
+        assert_eq!(
+            (*line, *column),
+            (span.line, span.column),
+            "input: {}",
+            input
+        );
+    }
+    match Parser::new(Lexer::new("fn(x = 1, y) {}")).parse_program() {
+        Err(Error::ParameterOrderErr { name, .. }) => assert_eq!("y", name),
+        other => panic!("expected a parameter order error, got {:?}", other),
+    }
+    match Parser::new(Lexer::new("f(x: 1, 2)")).parse_program() {
+        Err(Error::ArgumentOrderErr { .. }) => {}
+        other => panic!("expected an argument order error, got {:?}", other),
+    }
+    Ok(())
+}
+
+// Clears the flags which mark a lambda or a pipeline, in the expressions
+// which the cases below nest them in
+fn without_sugar(expr: &mut Expression) {
+    match &mut expr.kind {
+        ExprKind::Function {
+            body, shorthand, ..
+        } => {
+            *shorthand = false;
+            for stmt in body {
+                if let StmtKind::ExpressionStmt { expression } = &mut stmt.kind {
+                    without_sugar(expression);
+                }
+            }
+        }
+        ExprKind::Call {
+            function,
+            arguments,
+            piped,
+            ..
+        } => {
+            *piped = false;
+            without_sugar(function);
+            arguments.iter_mut().for_each(without_sugar);
+        }
+        ExprKind::Infix { left, right, .. } => {
+            without_sugar(left);
+            without_sugar(right);
+        }
+        ExprKind::Assign { value, .. } => without_sugar(value),
+        _ => {}
+    }
+}
+
+#[test]
+fn parse_lambdas_and_pipelines() -> error::Result<()> {
+    // Each is the same as the plain function or call, but for its flags
+    let cases = &[
+        ("|x| x * 2", "fn(x) { x * 2 }"),
+        ("x => x * 2", "fn(x) { x * 2 }"),
+        ("|| 1", "fn() { 1 }"),
+        (
+            "|x, y = 1 + 2, ..r| x | y",
+            "fn(x, y = 1 + 2, ..r) { x | y }",
+        ),
+        ("f(|x| x, 1)", "f(fn(x) { x }, 1)"),
+        ("xs |> map(f) |> sum()", "sum(map(xs, f))"),
+        ("a + 1 |> f(y: 2)", "f(a + 1, y: 2)"),
+        ("x = 0..n |> f()", "x = f(0..n)"),
+        ("(xs |> f())[0]", "f(xs)[0]"),
+    ];
+    for (input, plain) in cases {
+        let sugared = Parser::new(Lexer::new(input))
+            .parse_program()?
+            .get_stmts()
+            .clone();
+        let mut stmts = sugared.clone();
+        for stmt in &mut stmts {
+            if let StmtKind::ExpressionStmt { expression } = &mut stmt.kind {
+                without_sugar(expression);
+            }
+        }
+        assert_ne!(sugared, stmts, "input: {}", input);
+        assert_eq!(
+            Parser::new(Lexer::new(plain)).parse_program()?.get_stmts(),
+            &stmts,
+            "input: {}",
+            input
+        );
+    }
+
+    // A guard ends at the `=>` of its arm, but a lambda may still follow it
+    let program = Parser::new(Lexer::new("match (v) { n if n => x => x }")).parse_program()?;
+    match &program.get_stmts()[0].kind {
+        StmtKind::ExpressionStmt { expression } => match &expression.kind {
+            ExprKind::Match { arms, .. } => {
+                assert_eq!(
+                    Some(Expression::from(ExprKind::Ident("n".to_string()))),
+                    arms[0].guard
+                );
+                assert!(matches!(arms[0].body.kind, ExprKind::Function { .. }));
+            }
+            other => panic!("expected a match, got {:?}", other),
+        },
+        other => panic!("expected an expression, got {:?}", other),
+    }
+
+    for input in &["xs |> f", "1 |> f() + 1", "xs |> f()[0]", "|x x"] {
+        let result = Parser::new(Lexer::new(input)).parse_program();
+        assert!(result.is_err(), "input: {}, got {:?}", input, result);
+    }
+    match Parser::new(Lexer::new("xs |> f")).parse_program() {
+        Err(Error::PipelineErr { span }) => assert_eq!(7, span.column),
+        other => panic!("expected a pipeline error, got {:?}", other),
+    }
+    Ok(())
+}
+
+#[test]
+fn parse_optional_semicolons() -> error::Result<()> {
+    let cases = &[
+        (
+            "let a = 1\nlet b = a\nreturn b\n",
+            "let a = 1; let b = a; return b;",
+        ),
+        ("let a = 1\nf(a)", "let a = 1; f(a);"),
+        ("return 1\n[2]", "return 1; [2];"),
+        ("let s = a +\n  b\n  |> f()\n", "let s = a + b |> f();"),
+        ("f(1,\n  2)\ng()", "f(1, 2); g();"),
+        (
+            "let f = fn(x) {\n  let y = x\n  y * 2\n}\nf(1)",
+            "let f = fn(x) { let y = x; y * 2 }; f(1);",
+        ),
+        (
+            "if (a) {\n  1\n} else {\n  2\n}\nb",
+            "if (a) { 1 } else { 2 }; b;",
+        ),
+        (
+            "while (a) { break } for (x in xs) { continue }",
+            "while (a) { break; }; for (x in xs) { continue; };",
+        ),
+        ("let a = 1;\nlet b = 2", "let a = 1; let b = 2"),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(
+            Parser::new(Lexer::new(expected))
+                .parse_program()?
+                .get_stmts(),
+            Parser::new(Lexer::new(input)).parse_program()?.get_stmts(),
+            "input: {}",
+            input
+        );
+    }
+
+    match Parser::new(Lexer::new("let a = 1 let b = 2")).parse_program() {
+        Err(Error::StatementEndErr { got, span }) => {
+            assert_eq!(Token::LET, got);
+            assert_eq!((1, 11), (span.line, span.column));
+        }
+        other => panic!("expected a statement end error, got {:?}", other),
+    }
+    match Parser::new(Lexer::new("let a = b\n  (1)")).parse_program() {
+        Err(Error::AmbiguousLineBreakErr { next, span }) => {
+            assert_eq!(Token::LPAREN, next);
+            assert_eq!((2, 3), (span.line, span.column));
+        }
+        other => panic!("expected an ambiguous line break error, got {:?}", other),
+    }
+    // Not indented, so the line is a statement of its own
+    let program = Parser::new(Lexer::new("let a = b\n-1")).parse_program()?;
+    assert_eq!(2, program.get_stmts().len());
+    Ok(())
+}
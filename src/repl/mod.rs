@@ -1,10 +1,17 @@
+use crate::diagnostic;
+use crate::engine::Engine;
+use crate::error;
 use crate::lexer::Lexer;
+use crate::object::{self, Object};
+use crate::parser::ast::Program;
 use crate::parser::Parser;
 use rustyline::error::ReadlineError;
 use rustyline::Editor;
 
 pub fn start_repl() {
     let mut rl = Editor::<()>::new();
+    let mut engine = Engine::new_eval();
+    let mut show_bytecode = false;
     loop {
         let readline = rl.readline(">> ");
         match readline {
@@ -16,16 +23,26 @@ pub fn start_repl() {
             }
             Ok(line) => {
                 let mut given_str = String::new();
-                match line.as_str() {
+                match line.trim() {
                     ":quit" => break,
+                    ":eval" => {
+                        engine = Engine::new_eval();
+                        continue;
+                    }
+                    ":vm" => {
+                        engine = Engine::new_vm();
+                        continue;
+                    }
+                    ":bytecode" => {
+                        show_bytecode = !show_bytecode;
+                        continue;
+                    }
                     ":{" => multiline_reading(&mut rl, &mut given_str),
-                    _ => given_str += &line,
+                    _ => given_str += line.trim_end_matches(&['\r', '\n'][..]),
                 }
                 rl.add_history_entry(&given_str);
                 if !given_str.is_empty() {
-                    let mut parser = Parser::new(Lexer::new(&given_str));
-                    let program = parser.parse_program();
-                    println!("{:#?}", program);
+                    run_line(&given_str, &mut engine, show_bytecode);
                 }
             }
         }
@@ -37,7 +54,7 @@ fn multiline_reading(rl: &mut Editor<()>, given_str: &mut String) {
         let read_inner_line = rl.readline(">| ");
         match read_inner_line {
             Ok(inner_line) => {
-                if inner_line == ":}" {
+                if inner_line.trim() == ":}" {
                     break;
                 } else {
                     *given_str += "\n";
@@ -53,3 +70,28 @@ fn multiline_reading(rl: &mut Editor<()>, given_str: &mut String) {
         }
     }
 }
+
+// Every syntax error of the line is reported, and nothing is run then
+fn run_line(input: &str, engine: &mut Engine, show_bytecode: bool) {
+    let mut parser = Parser::new(Lexer::new(input));
+    let (program, errors) = parser.parse_program_recovering();
+    for warning in parser.take_warnings() {
+        if let Err(render_error) = diagnostic::emit_warning(&warning, "<repl>", input) {
+            panic!("{}", render_error);
+        }
+    }
+    if errors.is_empty() {
+        handle_error!(run_program(&program, engine, show_bytecode), "<repl>", input => ());
+    }
+    for err in errors {
+        handle_error!(Err::<(), _>(err), "<repl>", input => ());
+    }
+}
+
+fn run_program(program: &Program, engine: &mut Engine, show_bytecode: bool) -> error::Result<()> {
+    match engine.run(program, show_bytecode)? {
+        Object::Null => {}
+        evaluated => println!("{}", object::inspect(&evaluated)),
+    }
+    Ok(())
+}
@@ -0,0 +1,34 @@
+use std::rc::Rc;
+
+use crate::object::{CompiledFunction, Object};
+
+#[derive(Debug)]
+pub struct Frame {
+    pub func: Rc<CompiledFunction>,
+    pub free: Vec<Object>,
+    pub ip: usize,
+    pub base_pointer: usize,
+    // Which parameters the call gave an argument for. It is empty when it
+    // gave all of them.
+    pub given: Vec<bool>,
+    // The stack pointers at the start of the loops being run, innermost
+    // last
+    pub loops: Vec<usize>,
+}
+
+impl Frame {
+    pub fn new(func: Rc<CompiledFunction>, free: Vec<Object>, base_pointer: usize) -> Self {
+        Self {
+            func,
+            free,
+            ip: 0,
+            base_pointer,
+            given: Vec::new(),
+            loops: Vec::new(),
+        }
+    }
+
+    pub fn instructions(&self) -> &[u8] {
+        &self.func.instructions
+    }
+}
@@ -0,0 +1,499 @@
+mod frame;
+#[cfg(test)]
+mod vm_test;
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::mem;
+use std::rc::Rc;
+
+use crate::code::{self, Opcode};
+use crate::compiler::{Bytecode, ASSIGN_OPERATORS};
+use crate::error;
+use crate::eval;
+use crate::lexer::token::Token;
+use crate::object::builtins::BUILTINS;
+use crate::object::{CompiledFunction, Object};
+use crate::parser::ast::{Parameters, Pattern};
+
+use self::frame::Frame;
+
+type Error = error::MonkeyErr;
+
+pub const GLOBALS_SIZE: usize = 65536;
+pub const MAX_FRAMES: usize = 1024;
+// Room for every frame, so that deep recursion runs out of frames first
+const STACK_SIZE: usize = MAX_FRAMES * 32;
+
+pub struct VM {
+    constants: Vec<Object>,
+    stack: Vec<Object>,
+    // Always points to the next free slot. Top of the stack is stack[sp - 1]
+    sp: usize,
+    globals: Vec<Object>,
+    frames: Vec<Frame>,
+}
+
+impl VM {
+    // The REPL keeps the globals between each line, so the caller owns them
+    pub fn new_with_globals(bytecode: Bytecode, globals: Vec<Object>) -> Self {
+        let main_fn = Rc::new(CompiledFunction {
+            instructions: bytecode.instructions,
+            source_map: bytecode.source_map,
+            num_locals: 0,
+            name: None,
+            parameters: Parameters::default(),
+        });
+        Self {
+            constants: bytecode.constants,
+            stack: vec![Object::Null; STACK_SIZE],
+            sp: 0,
+            globals,
+            frames: vec![Frame::new(main_fn, Vec::new(), 0)],
+        }
+    }
+
+    pub fn into_globals(self) -> Vec<Object> {
+        self.globals
+    }
+
+    pub fn last_popped_stack_elem(&self) -> Option<&Object> {
+        self.stack.get(self.sp)
+    }
+
+    pub fn run(&mut self) -> error::Result<()> {
+        self.execute().map_err(|err| {
+            let frame = self.current_frame();
+            match frame.func.source_map.span_at(frame.ip.saturating_sub(1)) {
+                Some(span) => err.at(span),
+                None => err,
+            }
+        })
+    }
+
+    fn execute(&mut self) -> error::Result<()> {
+        while self.current_frame().ip < self.current_frame().instructions().len() {
+            let ip = self.current_frame().ip;
+            let op = Opcode::try_from(self.current_frame().instructions()[ip])?;
+            let (operands, read) = code::read_operands(
+                &op.definition(),
+                &self.current_frame().instructions()[ip + 1..],
+            );
+            self.current_frame_mut().ip += 1 + read;
+
+            match op {
+                Opcode::Constant => {
+                    let constant = self.constants[operands[0]].clone();
+                    self.push(constant)?;
+                }
+                Opcode::Pop => {
+                    self.pop();
+                }
+                Opcode::Add
+                | Opcode::Sub
+                | Opcode::Mul
+                | Opcode::Div
+                | Opcode::Mod
+                | Opcode::Pow
+                | Opcode::BitAnd
+                | Opcode::BitOr
+                | Opcode::BitXor
+                | Opcode::Shl
+                | Opcode::Shr => self.execute_binary_operation(op)?,
+                Opcode::Equal
+                | Opcode::NotEqual
+                | Opcode::GreaterThan
+                | Opcode::GreaterEqual
+                | Opcode::LessThan
+                | Opcode::LessEqual => self.execute_binary_operation(op)?,
+                Opcode::True => self.push(Object::Boolean(true))?,
+                Opcode::False => self.push(Object::Boolean(false))?,
+                Opcode::Null => self.push(Object::Null)?,
+                Opcode::Minus => {
+                    let right = self.pop();
+                    self.push(eval::eval_prefix_expr(&Token::MINUS, right)?)?;
+                }
+                Opcode::Assign => {
+                    let value = self.pop();
+                    let indexes = self.stack[self.sp - operands[0]..self.sp].to_vec();
+                    self.sp -= operands[0];
+                    let container = self.pop();
+                    let operator = &ASSIGN_OPERATORS[operands[1]];
+                    let (updated, assigned) =
+                        eval::assign_index(container, &indexes, operator, value)?;
+                    self.push(assigned)?;
+                    self.push(updated)?;
+                }
+                // The sequence and the position of the next value stay on
+                // the stack while a for loop goes through the iterable
+                Opcode::Iter => {
+                    let iterable = self.pop();
+                    self.push(eval::iteration_sequence(iterable)?)?;
+                    self.push(Object::Integer(0))?;
+                }
+                Opcode::IterNext => {
+                    let next = match &self.stack[self.sp - 1] {
+                        Object::Integer(i) => {
+                            eval::iteration_nth(&self.stack[self.sp - 2], *i as usize)
+                        }
+                        _ => None,
+                    };
+                    match next {
+                        Some(value) => {
+                            if let Object::Integer(i) = &mut self.stack[self.sp - 1] {
+                                *i += 1;
+                            }
+                            self.push(value)?;
+                        }
+                        None => {
+                            self.sp -= 2;
+                            self.current_frame_mut().ip = operands[0];
+                        }
+                    }
+                }
+                // break and continue may come in the middle of an
+                // expression, so they first drop what it left on the stack
+                Opcode::EnterLoop => {
+                    let sp = self.sp;
+                    self.current_frame_mut().loops.push(sp);
+                }
+                Opcode::ExitLoop => {
+                    self.current_frame_mut().loops.pop();
+                }
+                // The operand is the depth of the loop in its function. The
+                // condition of a loop may break out of the one around it.
+                Opcode::UnwindLoop => {
+                    let frame = self.current_frame_mut();
+                    frame.loops.truncate(operands[0] + 1);
+                    if let Some(&sp) = frame.loops.get(operands[0]) {
+                        self.sp = sp;
+                    }
+                }
+                Opcode::Range => {
+                    let end = self.pop();
+                    let start = self.pop();
+                    self.push(eval::make_range(start, end, operands[0] == 1)?)?;
+                }
+                Opcode::MatchPattern => {
+                    let pattern = self.pattern_constant(operands[0])?;
+                    let mut bound = Vec::new();
+                    if eval::match_pattern(&pattern, &self.stack[self.sp - 1], &mut bound)? {
+                        for value in bound {
+                            self.push(value)?;
+                        }
+                    } else {
+                        self.current_frame_mut().ip = operands[1];
+                    }
+                }
+                Opcode::Destructure => {
+                    let pattern = self.pattern_constant(operands[0])?;
+                    let value = self.pop();
+                    let mut bound = Vec::new();
+                    eval::destructure(&pattern, &value, &mut bound)?;
+                    for value in bound {
+                        self.push(value)?;
+                    }
+                }
+                Opcode::Slice => {
+                    let end = self.pop();
+                    let start = self.pop();
+                    let container = self.pop();
+                    self.push(eval::eval_slice(container, start, end, operands[0] == 1)?)?;
+                }
+                Opcode::BitNot => {
+                    let right = self.pop();
+                    self.push(eval::eval_prefix_expr(&Token::BITNOT, right)?)?;
+                }
+                Opcode::Bang => {
+                    let right = self.pop();
+                    self.push(eval::eval_prefix_expr(&Token::BANG, right)?)?;
+                }
+                Opcode::Jump => self.current_frame_mut().ip = operands[0],
+                Opcode::JumpNotTruthy => {
+                    if !self.pop().is_truthy() {
+                        self.current_frame_mut().ip = operands[0];
+                    }
+                }
+                Opcode::SetGlobal => self.globals[operands[0]] = self.pop(),
+                Opcode::GetGlobal => {
+                    let global = self.globals[operands[0]].clone();
+                    self.push(global)?;
+                }
+                // A local which a closure captured is in a cell, which the
+                // closure shares
+                Opcode::SetLocal => {
+                    let value = self.pop();
+                    let base_pointer = self.current_frame().base_pointer;
+                    match &self.stack[base_pointer + operands[0]] {
+                        Object::Cell(cell) => *cell.borrow_mut() = value,
+                        _ => self.stack[base_pointer + operands[0]] = value,
+                    }
+                }
+                // A new variable, which closures of the one before in its slot do
+                // not see
+                Opcode::BindLocal => {
+                    let value = self.pop();
+                    let base_pointer = self.current_frame().base_pointer;
+                    self.stack[base_pointer + operands[0]] = value;
+                }
+                Opcode::GetLocal => {
+                    let base_pointer = self.current_frame().base_pointer;
+                    let local = deref(&self.stack[base_pointer + operands[0]]);
+                    self.push(local)?;
+                }
+                Opcode::GetLocalCell => {
+                    let base_pointer = self.current_frame().base_pointer;
+                    let slot = &mut self.stack[base_pointer + operands[0]];
+                    if !matches!(slot, Object::Cell(_)) {
+                        let value = mem::replace(slot, Object::Null);
+                        *slot = Object::Cell(Rc::new(RefCell::new(value)));
+                    }
+                    let cell = slot.clone();
+                    self.push(cell)?;
+                }
+                Opcode::GetBuiltin => {
+                    let (name, func) = BUILTINS[operands[0]];
+                    self.push(Object::Builtin { name, func })?;
+                }
+                Opcode::GetFree => {
+                    let free = deref(&self.current_frame().free[operands[0]]);
+                    self.push(free)?;
+                }
+                Opcode::GetFreeCell => {
+                    let cell = self.current_frame().free[operands[0]].clone();
+                    self.push(cell)?;
+                }
+                Opcode::SetFree => {
+                    let value = self.pop();
+                    match &mut self.current_frame_mut().free[operands[0]] {
+                        Object::Cell(cell) => *cell.borrow_mut() = value,
+                        free => *free = value,
+                    }
+                }
+                Opcode::CurrentClosure => {
+                    let closure = Object::Closure {
+                        func: Rc::clone(&self.current_frame().func),
+                        free: self.current_frame().free.clone(),
+                    };
+                    self.push(closure)?;
+                }
+                Opcode::Array => {
+                    let elements = self.stack[self.sp - operands[0]..self.sp].to_vec();
+                    self.sp -= operands[0];
+                    self.push(Object::Array(elements))?;
+                }
+                Opcode::Interpolate => {
+                    let s: String = self.stack[self.sp - operands[0]..self.sp]
+                        .iter()
+                        .map(|part| part.to_string())
+                        .collect();
+                    self.sp -= operands[0];
+                    self.push(Object::String(s))?;
+                }
+                Opcode::Hash => {
+                    let mut pairs = BTreeMap::new();
+                    for pair in self.stack[self.sp - operands[0]..self.sp].chunks(2) {
+                        pairs.insert(pair[0].to_hash_key()?, pair[1].clone());
+                    }
+                    self.sp -= operands[0];
+                    self.push(Object::Hash(pairs))?;
+                }
+                Opcode::Index => {
+                    let index = self.pop();
+                    let left = self.pop();
+                    self.push(eval::eval_index_expr(left, index)?)?;
+                }
+                Opcode::Call => self.execute_call(operands[0], &[])?,
+                Opcode::CallNamed => {
+                    let keywords = match &self.constants[operands[1]] {
+                        Object::Array(names) => names.iter().map(Object::to_string).collect(),
+                        _ => Vec::new(),
+                    };
+                    self.execute_call(operands[0], &keywords)?
+                }
+                Opcode::JumpArgGiven => {
+                    let given = &self.current_frame().given;
+                    if given.get(operands[0]).copied().unwrap_or(true) {
+                        self.current_frame_mut().ip = operands[1];
+                    }
+                }
+                Opcode::ReturnValue => {
+                    let return_value = self.pop();
+                    if !self.return_from_frame(return_value)? {
+                        return Ok(());
+                    }
+                }
+                Opcode::Return => {
+                    if !self.return_from_frame(Object::Null)? {
+                        return Ok(());
+                    }
+                }
+                Opcode::Closure => {
+                    let func = match &self.constants[operands[0]] {
+                        Object::CompiledFunction(func) => Rc::clone(func),
+                        other => {
+                            return Err(Error::EvalErr {
+                                msg: format!("not a function: {}", other.type_name()),
+                                span: None,
+                            })
+                        }
+                    };
+                    let free = self.stack[self.sp - operands[1]..self.sp].to_vec();
+                    self.sp -= operands[1];
+                    self.push(Object::Closure { func, free })?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_binary_operation(&mut self, op: Opcode) -> error::Result<()> {
+        let right = self.pop();
+        let left = self.pop();
+        let operator = match op {
+            Opcode::Add => Token::PLUS,
+            Opcode::Sub => Token::MINUS,
+            Opcode::Mul => Token::ASTERISK,
+            Opcode::Div => Token::SLASH,
+            Opcode::Mod => Token::PERCENT,
+            Opcode::BitAnd => Token::BITAND,
+            Opcode::BitOr => Token::BITOR,
+            Opcode::BitXor => Token::BITXOR,
+            Opcode::Shl => Token::SHL,
+            Opcode::Shr => Token::SHR,
+            Opcode::Pow => Token::POWER,
+            Opcode::Equal => Token::EQ,
+            Opcode::NotEqual => Token::NOTEQ,
+            Opcode::GreaterThan => Token::GT,
+            Opcode::GreaterEqual => Token::GTEQ,
+            Opcode::LessThan => Token::LT,
+            Opcode::LessEqual => Token::LTEQ,
+            _ => return Err(Error::IlligalOpcodeFoundErr),
+        };
+        self.push(eval::eval_infix_expr(&operator, left, right)?)
+    }
+
+    // The last keywords.len() arguments are given by name
+    fn execute_call(&mut self, num_args: usize, keywords: &[String]) -> error::Result<()> {
+        match self.stack[self.sp - 1 - num_args].clone() {
+            Object::Closure { func, free } => {
+                if self.frames.len() >= MAX_FRAMES {
+                    return Err(call_depth_err());
+                }
+                let base_pointer = self.sp - num_args;
+                let new_sp = base_pointer + func.num_locals;
+                if new_sp >= STACK_SIZE {
+                    return Err(call_depth_err());
+                }
+                let parameters = &func.parameters;
+                let exact = keywords.is_empty()
+                    && parameters.rest.is_none()
+                    && num_args == parameters.names.len();
+                let mut given = Vec::new();
+                if !exact {
+                    let arguments = self.stack[base_pointer..self.sp].to_vec();
+                    let slots = eval::bind_arguments(
+                        func.name.as_deref(),
+                        parameters,
+                        arguments,
+                        keywords,
+                    )?;
+                    given = slots.iter().map(Option::is_some).collect();
+                    for (i, slot) in slots.into_iter().enumerate() {
+                        self.stack[base_pointer + i] = slot.unwrap_or(Object::Null);
+                    }
+                }
+                // The locals may still hold the cells of an earlier call
+                let num_bound = parameters.bound_names().count();
+                for slot in &mut self.stack[base_pointer + num_bound..new_sp] {
+                    *slot = Object::Null;
+                }
+                let mut frame = Frame::new(func, free, base_pointer);
+                frame.given = given;
+                self.frames.push(frame);
+                self.sp = new_sp;
+                Ok(())
+            }
+            Object::Builtin { name, .. } if !keywords.is_empty() => Err(Error::EvalErr {
+                msg: format!("builtin function {} takes no arguments by name", name),
+                span: None,
+            }),
+            Object::Builtin { func, .. } => {
+                let args = self.stack[self.sp - num_args..self.sp].to_vec();
+                self.sp -= num_args + 1;
+                self.push(func(args)?)
+            }
+            other => Err(Error::EvalErr {
+                msg: format!("not a function: {}", other.type_name()),
+                span: None,
+            }),
+        }
+    }
+
+    // Returns false if the main frame returned, that is, the program ends
+    fn return_from_frame(&mut self, return_value: Object) -> error::Result<bool> {
+        if self.frames.len() == 1 {
+            self.push(return_value)?;
+            self.pop();
+            return Ok(false);
+        }
+        let frame = self.frames.pop().expect("frame is checked above");
+        self.sp = frame.base_pointer - 1;
+        self.push(return_value)?;
+        Ok(true)
+    }
+
+    fn current_frame(&self) -> &Frame {
+        self.frames.last().expect("main frame is never popped")
+    }
+
+    fn current_frame_mut(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("main frame is never popped")
+    }
+
+    fn push(&mut self, obj: Object) -> error::Result<()> {
+        if self.sp >= STACK_SIZE {
+            return Err(stack_overflow_err());
+        }
+        self.stack[self.sp] = obj;
+        self.sp += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Object {
+        self.sp -= 1;
+        self.stack[self.sp].clone()
+    }
+
+    fn pattern_constant(&self, index: usize) -> error::Result<Rc<Pattern>> {
+        match &self.constants[index] {
+            Object::Pattern(pattern) => Ok(Rc::clone(pattern)),
+            other => Err(Error::EvalErr {
+                msg: format!("not a pattern: {}", other.type_name()),
+                span: None,
+            }),
+        }
+    }
+}
+
+fn deref(obj: &Object) -> Object {
+    match obj {
+        Object::Cell(cell) => cell.borrow().clone(),
+        _ => obj.clone(),
+    }
+}
+
+pub fn call_depth_err() -> Error {
+    Error::EvalErr {
+        msg: "maximum call depth exceeded".to_string(),
+        span: None,
+    }
+}
+
+fn stack_overflow_err() -> Error {
+    Error::EvalErr {
+        msg: "stack overflow".to_string(),
+        span: None,
+    }
+}
@@ -0,0 +1,352 @@
+use super::*;
+use crate::compiler::Compiler;
+use crate::error;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+
+fn run(input: &str) -> error::Result<Object> {
+    let program = Parser::new(Lexer::new(input)).parse_program()?;
+    let mut compiler = Compiler::new();
+    compiler.compile(&program)?;
+    let mut vm = VM::new_with_globals(compiler.bytecode(), vec![Object::Null; GLOBALS_SIZE]);
+    vm.run()?;
+    Ok(vm.last_popped_stack_elem().cloned().unwrap_or(Object::Null))
+}
+
+macro_rules! test_vm {
+    ($test: ident => $($input: expr => $expected: expr),+ $(,)?) => {
+        #[test]
+        fn $test() -> error::Result<()> {
+            $(assert_eq!($expected, run($input)?, "input: {}", $input);)+
+            Ok(())
+        }
+    };
+}
+
+test_vm!(
+    vm_integer_arithmetic =>
+    "1 + 2" => Object::Integer(3),
+    "4 / 2" => Object::Integer(2),
+    "5 * (2 + 10)" => Object::Integer(60),
+    "-50 + 100 + -50" => Object::Integer(0),
+    "2 ** 10" => Object::Integer(1024),
+    "1 + 2i" => Object::Complex { re: 1.0, im: 2.0 },
+);
+
+test_vm!(
+    vm_boolean =>
+    "1 <= 1" => Object::Boolean(true),
+    "2 >= 3" => Object::Boolean(false),
+    "2 <= 1.5" => Object::Boolean(false),
+    "true && 1 < 2" => Object::Boolean(true),
+    "1 > 2 || 0 && false" => Object::Boolean(false),
+    "5 || 1 % 0" => Object::Boolean(true),
+    "7 % 3 + -7 % 3" => Object::Integer(0),
+    "(6 & 3 | 8 ^ 1) == ~~11" => Object::Boolean(true),
+    "-16 >> 2 << 1" => Object::Integer(-8),
+    "1 < 2" => Object::Boolean(true),
+    "1 > 2" => Object::Boolean(false),
+    "(1 < 2) == true" => Object::Boolean(true),
+    "!5" => Object::Boolean(false),
+    "!(if (false) { 5; })" => Object::Boolean(true),
+);
+
+test_vm!(
+    vm_conditionals =>
+    "if (true) { 10 }" => Object::Integer(10),
+    "if (1 > 2) { 10 } else { 20 }" => Object::Integer(20),
+    "if (1 > 2) { 10 }" => Object::Null,
+    "if ((if (false) { 10 })) { 10 } else { 20 }" => Object::Integer(20),
+    "if (true) { let a = 1; }" => Object::Null,
+);
+
+test_vm!(
+    vm_global_let =>
+    "let one = 1; let two = one + one; one + two" => Object::Integer(3),
+    "let a = 1; let a = a + 1; a" => Object::Integer(2),
+);
+
+test_vm!(
+    vm_collections =>
+    r#""mon" + "key""# => Object::String("monkey".to_string()),
+    "[1, 2 * 2, 3 + 3][1]" => Object::Integer(4),
+    "[1, 2, 3][99]" => Object::Null,
+    "{1: 1, 2: 2}[2]" => Object::Integer(2),
+    "{}[0]" => Object::Null,
+);
+
+test_vm!(
+    vm_functions =>
+    "let f = fn() { 5 + 10; }; f();" => Object::Integer(15),
+    "let f = fn() { return 99; 100; }; f();" => Object::Integer(99),
+    "let f = fn() { }; f();" => Object::Null,
+    "let sum = fn(a, b) { let c = a + b; c; }; sum(1, 2) + sum(3, 4);" => Object::Integer(10),
+    "let f = fn() { 1 }; let g = fn() { f }; g()();" => Object::Integer(1),
+    "return 5; 10;" => Object::Integer(5),
+);
+
+test_vm!(
+    vm_builtins =>
+    r#"len("four")"# => Object::Integer(4),
+    "push([], 1)" => Object::Array(vec![Object::Integer(1)]),
+    "rest([1, 2])" => Object::Array(vec![Object::Integer(2)]),
+);
+
+test_vm!(
+    vm_closures =>
+    "let adder = fn(a) { fn(b) { a + b } }; adder(1)(2);" => Object::Integer(3),
+    "let f = fn(a) { fn(b) { fn(c) { a + b + c } } }; f(1)(2)(3);" => Object::Integer(6),
+    "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15);"
+        => Object::Integer(610),
+    "let wrapper = fn() {
+        let count_down = fn(x) { if (x == 0) { return 0; } else { count_down(x - 1); } };
+        count_down(1);
+    };
+    wrapper();" => Object::Integer(0),
+);
+
+test_vm!(
+    vm_assignment =>
+    "let x = 1; x += 10; x *= 2; x -= 1; x /= 3; x" => Object::Integer(7),
+    "let a = 0; let b = 0; a = b = 7; a + b" => Object::Integer(14),
+    "let a = [1, [2, 3]]; a[1][1] += 100; a[0] = 5; a" => Object::Array(vec![
+        Object::Integer(5),
+        Object::Array(vec![Object::Integer(2), Object::Integer(103)]),
+    ]),
+    r#"let h = {}; h["k"] = 1; h["k"] += 1"# => Object::Integer(2),
+    "let n = 0; let f = fn() { n = 5; let m = 1; m += n; m }; f() + n" => Object::Integer(11),
+);
+
+test_vm!(
+    vm_loops =>
+    "let s = 0; let i = 0;
+     while (true) { i += 1; if (i % 2 == 0) { continue; } if (i > 9) { break; } s += i; }
+     s" => Object::Integer(25),
+    r#"let s = ""; for (k in {"b": 1, "a": 2}) { for (ch in k + "!") { s += ch; } } s"#
+        => Object::String("a!b!".to_string()),
+    "let n = 0; for (x in [1, 2]) { for (y in [1, 2, 3]) { if (y > x) { break } n += 1; } } n"
+        => Object::Integer(3),
+    "let f = fn(xs) { let s = 0; for (x in xs) { if (x > 9) { return -1; } s += x; } s };
+     [f([1, 2, 3]), f([1, 20]), f([])]"
+        => Object::Array(vec![Object::Integer(6), Object::Integer(-1), Object::Integer(0)]),
+    "let f = fn() { for (x in [1]) { } }; f()" => Object::Null,
+    "let f = fn(c) { if (c) { while (false) { } } }; f(true)" => Object::Null,
+    "let i = 0; while (i < 100000) { i += 1 } i" => Object::Integer(100000),
+    "let n = 0; let f = fn(a, b) { a };
+     for (i in 0..3000) { n += 1; f(1, if (true) { continue } else { 2 }) } n"
+        => Object::Integer(3000),
+    "let i = 0; while (i < 3000) { i += 1; [1, 2, if (true) { continue } else { 2 }] } i"
+        => Object::Integer(3000),
+    "let s = 0; for (i in 0..5) { s += i * if (i == 3) { break } else { 1 } } s"
+        => Object::Integer(3),
+    "let n = 0; let a = [1, if (true) { for (x in [1, 2]) { n += -x * if (true) { continue } else { 0 } } n } else { 0 }]; a"
+        => Object::Array(vec![Object::Integer(1), Object::Integer(0)]),
+);
+
+test_vm!(
+    vm_ranges =>
+    "let s = 0; for (i in 1..=4) { s += i; } s" => Object::Integer(10),
+    "let r = 2..5; [len(r), r[2], r[3]]" => Object::Array(vec![
+        Object::Integer(3),
+        Object::Integer(4),
+        Object::Null,
+    ]),
+    "let a = [0, 1, 2, 3]; [a[1..3], a[2..], a[..=0]]" => Object::Array(vec![
+        Object::Array(vec![Object::Integer(1), Object::Integer(2)]),
+        Object::Array(vec![Object::Integer(2), Object::Integer(3)]),
+        Object::Array(vec![Object::Integer(0)]),
+    ]),
+    r#"let s = "héllo"; let n = 2; s[n - 1..n + 1]"# => Object::String("él".to_string()),
+    "(0..10)[5..]" => Object::Range { start: 5, end: 10, inclusive: false },
+);
+
+test_vm!(
+    vm_match =>
+    r#"let f = fn(v) {
+        match (v) {
+            0 => "zero",
+            [x] => "one ${x}",
+            [x, ..rest] if x > 0 => "many ${rest}",
+            {"x": x, "y": [y, _]} => "point ${x} ${y}",
+            _ => "other",
+        }
+    };
+    [f(0.0), f([1]), f([1, 2, 3]), f([0, 1]), f({"x": 1, "y": [2, 3]}), f(true)]"#
+        => Object::Array(
+            ["zero", "one 1", "many [2, 3]", "other", "point 1 2", "other"]
+                .iter()
+                .map(|s| Object::String(s.to_string()))
+                .collect()
+        ),
+    "match (3) { 1 => 1 }" => Object::Null,
+    "let n = 0; for (v in [[1], [1, 2], 3]) { match (v) { [_, ..] => n += 1, _ => 0 }; } n"
+        => Object::Integer(2),
+    "let f = fn(p) { match (p) { [a, b] if a > b => match (a - b) { 1 => 0, d => d }, _ => -1 } };
+     [f([5, 1]), f([2, 1]), f([1, 2])]"
+        => Object::Array(vec![Object::Integer(4), Object::Integer(0), Object::Integer(-1)]),
+);
+
+test_vm!(
+    vm_destructuring =>
+    r#"let [a, [b, c], {"x": x}] = [1, [2, 3], {"x": 4, "y": 5}]; a + b + c + x"#
+        => Object::Integer(10),
+    "let f = fn(p) { let [x, ..ys] = p; let g = fn() { x + len(ys) }; g() }; f([6, 7, 8])"
+        => Object::Integer(8),
+    "let [a, a] = [1, 2]; a" => Object::Integer(2),
+);
+
+#[test]
+fn vm_errors() {
+    let cases = &[
+        "fn(a) { a }();",
+        "5 + true",
+        "let f = fn(n) { f(n + 1) }; f(0);",
+        "let f = fn(n) { 1 + f(n + 1) }; f(0)",
+        "1 / 0",
+        "1 << 64",
+        "let a = [1]; a[1] = 2",
+        "for (x in true) { }",
+        "[1, 2][..3]",
+        "true..2",
+        r#"match ("a") { x if x > 1 => 1 }"#,
+        "let [a, [b]] = [1, [2, 3]]",
+    ];
+    for input in cases {
+        match run(input) {
+            Err(Error::EvalErr { .. }) => {}
+            other => panic!("expected a runtime error for {}, got {:?}", input, other),
+        }
+    }
+}
+
+#[test]
+fn vm_assignment_errors() {
+    let cases = &[
+        ("len = 1", "cannot assign to builtin function len"),
+        (
+            "let f = fn() { f = 1 }",
+            "cannot assign to f inside of the function itself",
+        ),
+    ];
+    for (input, expected) in cases {
+        match run(input) {
+            Err(Error::CompileErr { msg, .. }) => assert_eq!(*expected, msg, "input: {}", input),
+            other => panic!("expected a compile error for {}, got {:?}", input, other),
+        }
+    }
+}
+
+test_vm!(
+    vm_interpolated_strings =>
+    r#"let name = "world"; "hello ${name}!""# => Object::String("hello world!".to_string()),
+    r#"let f = fn(x) { "<${x * 2}>" }; "${f(1)}${f(2)}""# => Object::String("<2><4>".to_string()),
+);
+
+test_vm!(
+    vm_numeric_tower =>
+    "1 + 0.5" => Object::Float(1.5),
+    "-0.5 * 3" => Object::Float(-1.5),
+    "2 * 1.5i" => Object::Complex { re: 0.0, im: 3.0 },
+    "1 < 1.5" => Object::Boolean(true),
+    "2 * 3 + 4i" => Object::Complex { re: 6.0, im: 4.0 },
+    "abs(conj(3 + 4i) * 1i)" => Object::Float(5.0),
+);
+
+test_vm!(
+    vm_parameters =>
+    "let f = fn(x, y = x * 10) { [x, y] }; f(1)" => Object::Array(vec![Object::Integer(1), Object::Integer(10)]),
+    "let f = fn(x, y = 10) { x - y }; f(1, 2)" => Object::Integer(-1),
+    "let f = fn(x, y) { x - y }; f(y: 1, x: 5)" => Object::Integer(4),
+    "let f = fn(x, y = 2, z = 3) { x * y + z }; f(1, z: 0)" => Object::Integer(2),
+    "let f = fn(first, ..rest) { rest }; f(1, 2, 3)" => Object::Array(vec![Object::Integer(2), Object::Integer(3)]),
+    "fn(..xs) { len(xs) }()" => Object::Integer(0),
+    "fn(x) { fn(y = x) { y } }(3)()" => Object::Integer(3),
+    "fn(x = 1) { x }(false)" => Object::Boolean(false),
+);
+
+#[test]
+fn vm_argument_errors() {
+    let cases = &[
+        (
+            "let f = fn(x, y = 1) { x }; f(1, 2, 3)",
+            "wrong number of arguments to f(x, y = 1): want=1 to 2, got=3",
+        ),
+        (
+            "fn(x) { x }(1, 2)",
+            "wrong number of arguments to fn(x): want=1, got=2",
+        ),
+        (
+            "let f = fn(x, y) { x }; f(y: 1)",
+            "missing argument for x in call to f(x, y)",
+        ),
+        (
+            "len(x: [])",
+            "builtin function len takes no arguments by name",
+        ),
+    ];
+    for (input, expected) in cases {
+        match run(input) {
+            Err(Error::EvalErr { msg, .. }) => assert_eq!(*expected, msg, "input: {}", input),
+            other => panic!("expected a runtime error for {}, got {:?}", input, other),
+        }
+    }
+}
+
+test_vm!(
+    vm_lambdas_and_pipelines =>
+    "let double = |x| x * 2; double(4)" => Object::Integer(8),
+    "(x => x + 1)(1)" => Object::Integer(2),
+    "(|| 3)()" => Object::Integer(3),
+    "let add = |a, b = 10| a + b; [add(1), add(1, 2)]" => Object::Array(vec![Object::Integer(11), Object::Integer(3)]),
+    "let f = fn(y) { |x| x + y }; f(5)(1)" => Object::Integer(6),
+    "let sub = |a, b| a - b; 10 |> sub(3) |> sub(2)" => Object::Integer(5),
+    "[1, 2, 3] |> len()" => Object::Integer(3),
+    "let double = |x| x * 2; 1 + 2 |> double()" => Object::Integer(6),
+    "match (2) { n if n > 1 => x => x * n, _ => 0 }(3)" => Object::Integer(6),
+);
+
+test_vm!(
+    vm_without_semicolons =>
+    "let a = 1\nlet b = a +\n  2\nb * 2" => Object::Integer(6),
+    "let f = fn(x) {\n  let y = x * 2\n  return y\n}\nf(3)" => Object::Integer(6),
+    "let i = 0\nwhile (i < 3) { i += 1 }\ni" => Object::Integer(3),
+    "let xs = [1,\n  2]\nxs\n  |> len()" => Object::Integer(2),
+);
+
+test_vm!(
+    vm_loop_control_in_expressions =>
+    "let s = 0; for (i in 0..5) { let y = if (i == 3) { break } else { i }; s += y } s"
+        => Object::Integer(3),
+    "let s = 0; for (i in 0..4) { s += 1 + if (i % 2 == 0) { continue } else { 0 } } s"
+        => Object::Integer(2),
+    "let n = 0; let f = fn(a, b) { a }; for (i in 0..10) { n += 1; f(1, if (true) { continue } else { 2 }) } n"
+        => Object::Integer(10),
+    "let i = 0; while (i < 5) { i += 1; let [a] = [if (i > 2) { break } else { i }] } i"
+        => Object::Integer(3),
+    r#"let s = ""; for (x in [1, 2]) { s += "${if (x == 2) { continue } else { x }}" } s"#
+        => Object::String("1".to_string()),
+    "let f = fn(x) { 1 + if (x) { return 10 } else { 0 } }; [f(true), f(false)]"
+        => Object::Array(vec![Object::Integer(10), Object::Integer(1)]),
+    "let n = 0; let f = fn(a, b) { a };
+     for (x in [1, 2, 3]) {
+         f(1, if (true) { while (if (x == 2) { continue } else { false }) { } 0 } else { 0 });
+         n += x
+     }
+     n"
+        => Object::Integer(4),
+);
+
+#[test]
+fn vm_operand_limits() -> error::Result<()> {
+    let lets: String = (0..256).map(|i| format!("let a{} = {};", i, i)).collect();
+    for (name, expected) in &[("a1", 1), ("a255", 255)] {
+        let input = format!("fn() {{ {} {} }}()", lets, name);
+        assert_eq!(Object::Integer(*expected), run(&input)?);
+    }
+    let args = vec!["1"; 255].join(", ");
+    assert_eq!(
+        Object::Integer(255),
+        run(&format!("fn(..r) {{ len(r) }}({})", args))?
+    );
+    Ok(())
+}